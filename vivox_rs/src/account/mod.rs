@@ -0,0 +1,588 @@
+use crate::archive::{self, ArchiveQuery, ArchivedMessage, Scope};
+use crate::client::{Payload, VivoxClient};
+use crate::devices::AudioDevices;
+use crate::enums::VivoxError;
+use crate::events::Event;
+use crate::helpers::*;
+use crate::messages::UserMessageEvent;
+use crate::presence::{self, BuddyList, BuddyManagementMode, BuddyState};
+use crate::sessiongroup::SessionGroup;
+use crate::tokengen::TokenGenerator;
+use crate::uri::UserUri;
+
+use futures::Stream;
+use serde::{Serialize, Deserialize};
+use std::ffi::CStr;
+use std::future::Future;
+use std::os::raw::c_int;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc};
+use vivox_rs_sys::*;
+
+const MESSAGE_CAPACITY: usize = 64;
+
+pub struct AnonymousLogin {
+  req_ptr: *mut vx_req_account_anonymous_login_t,
+  uri: String,
+}
+
+#[allow(dead_code)]
+impl AnonymousLogin {
+  pub fn new() -> Self {
+    use std::mem;
+
+    unsafe {
+      let mut new_req = Self {
+        req_ptr: mem::zeroed(),
+        uri: String::new(),
+      };
+
+      vx_req_account_anonymous_login_create(&mut new_req.req_ptr);
+
+      new_req
+    }
+  }
+
+  pub fn acct_mgmt_server<'a>(&'a mut self, input: &str) -> &'a mut Self {
+    unsafe { (*self.req_ptr).acct_mgmt_server = strdup(input); }
+
+    self
+  }
+
+  pub fn acct_name<'a>(&'a mut self, input: &str) -> &'a mut Self {
+    unsafe { (*self.req_ptr).acct_name = strdup(input); }
+
+    self
+  }
+
+  pub fn account_handle<'a>(&'a mut self, input: &str) -> &'a mut Self {
+    unsafe { (*self.req_ptr).account_handle = strdup(input); }
+
+    self
+  }
+
+  /// Signs a login token with `key`, the issuer's secret. Only for tools
+  /// and tests holding the key, games should use `signed_access_token`.
+  pub fn access_token(
+    &mut self,
+    key: &str,
+    issuer: &str,
+    domain: &str,
+    generator: &TokenGenerator,
+  ) -> &mut Self
+  {
+    unsafe {
+      self.uri = UserUri::new(
+        CStr::from_ptr((*self.req_ptr).acct_name).to_str().unwrap(),
+        domain,
+      ).to_string();
+
+      (*self.req_ptr).access_token = strdup(
+        &generator.generate(
+          key,
+          issuer,
+          token_expiry(),
+          "login",
+          &self.uri,
+          None,
+        )
+      );
+    }
+
+    self
+  }
+
+  /// Uses a token minted elsewhere, such as on the game's server where the
+  /// signing key belongs. `domain` completes the account URI the token was
+  /// issued for.
+  pub fn signed_access_token(&mut self, domain: &str, token: &str) -> &mut Self {
+    unsafe {
+      self.uri = UserUri::new(
+        CStr::from_ptr((*self.req_ptr).acct_name).to_str().unwrap(),
+        domain,
+      ).to_string();
+
+      (*self.req_ptr).access_token = strdup(token);
+    }
+
+    self
+  }
+
+  pub fn application_override<'a>(&'a mut self, input: &str) -> &'a mut Self {
+    unsafe { (*self.req_ptr).application_override = strdup(input); }
+
+    self
+  }
+
+  pub fn application_token<'a>(&'a mut self, input: &str) -> &'a mut Self {
+    unsafe { (*self.req_ptr).application_token = strdup(input); }
+
+    self
+  }
+
+  pub fn autopost_crash_dumps<'a>(&'a mut self, input: c_int) -> &'a mut Self {
+    unsafe { (*self.req_ptr).autopost_crash_dumps = input; }
+
+    self
+  }
+
+  /// How subscription requests are answered, only used together with
+  /// `enable_social`.
+  pub fn buddy_management_mode<'a>(&'a mut self, mode: BuddyManagementMode) -> &'a mut Self {
+    unsafe { (*self.req_ptr).buddy_management_mode = mode.to_raw(); }
+
+    self
+  }
+
+  pub fn connector_handle<'a>(&'a mut self, input: &str) -> &'a mut Self {
+    unsafe { (*self.req_ptr).connector_handle = strdup(input); }
+
+    self
+  }
+
+  pub fn displayname<'a>(&'a mut self, input: &str) -> &'a mut Self {
+    unsafe { (*self.req_ptr).displayname = strdup(input); }
+
+    self
+  }
+
+  pub fn enable_social<'a>(&'a mut self, input: c_int) -> &'a mut Self {
+    unsafe { (*self.req_ptr).enable_buddies_and_presence = input; }
+
+    self
+  }
+
+  pub fn issue<'a>(&'a mut self) {
+    unsafe { vx_issue_request(&mut (*self.req_ptr).base); }
+  }
+
+  pub fn languages<'a>(&'a mut self, input: &str) -> &'a mut Self {
+    unsafe { (*self.req_ptr).languages = strdup(input); }
+
+    self
+  }
+
+  pub fn participant_property_frequency<'a>(&'a mut self, input: c_int) -> &'a mut Self {
+    unsafe { (*self.req_ptr).participant_property_frequency = input; }
+
+    self
+  }
+
+  pub fn persist_presence<'a>(&'a mut self, input: c_int) -> &'a mut Self {
+    unsafe { (*self.req_ptr).enable_presence_persistence = input; }
+
+    self
+  }
+
+  pub(crate) fn base(&mut self) -> *mut vx_req_base_t {
+    unsafe { &mut (*self.req_ptr).base }
+  }
+
+  pub(crate) fn get_account_handle(&self) -> String {
+    unsafe { to_owned_string((*self.req_ptr).account_handle) }
+  }
+
+  pub(crate) fn get_acct_name(&self) -> String {
+    unsafe { to_owned_string((*self.req_ptr).acct_name) }
+  }
+
+  pub(crate) fn get_uri(&self) -> String {
+    self.uri.clone()
+  }
+}
+
+/// Master microphone and speaker levels of an account.
+///
+/// Volumes run from 0 to 100 where 50 leaves the level unchanged. The
+/// struct is meant to be stored with the user's preferences and handed
+/// back to `Account::restore_audio_settings` on the next login.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AudioSettings {
+  pub mic_volume: i32,
+  pub speaker_volume: i32,
+  pub mic_muted: bool,
+  pub speaker_muted: bool,
+}
+
+impl Default for AudioSettings {
+  fn default() -> Self {
+    Self {
+      mic_volume: 50,
+      speaker_volume: 50,
+      mic_muted: false,
+      speaker_muted: false,
+    }
+  }
+}
+
+/// A logged in user.
+///
+/// Each account has its own event channel carrying the events for the
+/// account itself and for every session it has joined.
+#[derive(Clone)]
+pub struct Account {
+  client: VivoxClient,
+  connector_handle: String,
+  handle: String,
+  name: String,
+  uri: String,
+  events: broadcast::Sender<Event>,
+  audio: Arc<Mutex<AudioSettings>>,
+  buddies: Arc<BuddyState>,
+}
+
+impl Account {
+  pub(crate) fn new(
+    client: VivoxClient,
+    connector_handle: String,
+    handle: String,
+    name: String,
+    uri: String,
+    events: broadcast::Sender<Event>,
+    // Positioned right after the login so no buddy event is missed
+    pending: broadcast::Receiver<Event>,
+  ) -> Self {
+    let buddies = Arc::new(BuddyState::new());
+
+    tokio::spawn(presence::track(buddies.clone(), pending));
+
+    Self {
+      client,
+      connector_handle,
+      handle,
+      name,
+      uri,
+      events,
+      audio: Arc::new(Mutex::new(AudioSettings::default())),
+      buddies,
+    }
+  }
+
+  pub fn connector_handle(&self) -> &str {
+    &self.connector_handle
+  }
+
+  pub fn handle(&self) -> &str {
+    &self.handle
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn uri(&self) -> &str {
+    &self.uri
+  }
+
+  pub fn events(&self) -> broadcast::Receiver<Event> {
+    self.events.subscribe()
+  }
+
+  /// Sends a private message to another logged in user. Resolves to the
+  /// message id once the SDK accepted it. Delivery failures arrive later
+  /// as `UserMessageEvent::Failed` carrying the same id.
+  pub fn send_user_message(
+    &self,
+    to: &UserUri,
+    body: &str,
+  ) -> impl Future<Output = Result<String, VivoxError>>
+  {
+    use std::mem;
+
+    let pending = unsafe {
+      let mut req: *mut vx_req_account_send_message = mem::zeroed();
+
+      vx_req_account_send_message_create(&mut req);
+
+      (*req).account_handle = strdup(&self.handle);
+      (*req).user_uri = strdup(&to.to_string());
+      (*req).message_body = strdup(body);
+
+      self.client.issue_for(&mut (*req).base)
+    };
+
+    async move {
+      match pending.await? {
+        Payload::RequestId(id) => Ok(id),
+        _ => Err(VivoxError::FAILED),
+      }
+    }
+  }
+
+  /// Private messages to this account and delivery failures of the ones it
+  /// sent, from now on. Must be called from within a Tokio runtime.
+  pub fn user_messages(&self) -> mpsc::Receiver<UserMessageEvent> {
+    let mut events = self.events.subscribe();
+    let (mut tx, rx) = mpsc::channel(MESSAGE_CAPACITY);
+
+    tokio::spawn(async move {
+      loop {
+        let evt = match events.recv().await {
+          Ok(Event::UserMessageReceived { message, .. }) => UserMessageEvent::Received(message),
+          Ok(Event::UserMessageFailed { message_id, status, .. }) => UserMessageEvent::Failed { message_id, status },
+          Ok(_) => continue,
+          Err(broadcast::RecvError::Lagged(_)) => continue,
+          Err(broadcast::RecvError::Closed) => break,
+        };
+
+        if tx.send(evt).await.is_err() {
+          break;
+        }
+      }
+    });
+
+    rx
+  }
+
+  /// Searches the server side archive of this account's channel and
+  /// directed messages, oldest first. Must be called from within a Tokio
+  /// runtime.
+  pub fn archive(&self, query: ArchiveQuery) -> impl Stream<Item = Result<ArchivedMessage, VivoxError>> {
+    archive::query(self.client.clone(), self.clone(), Scope::Account(self.handle.clone()), query)
+  }
+
+  /// Levels and mutes as last set through this account.
+  pub fn audio_settings(&self) -> AudioSettings {
+    *self.audio.lock().unwrap()
+  }
+
+  pub fn set_mic_volume(&self, volume: i32) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    let pending = match check_volume(volume) {
+      Ok(()) => unsafe {
+        let mut req: *mut vx_req_connector_set_local_mic_volume = mem::zeroed();
+
+        vx_req_connector_set_local_mic_volume_create(&mut req);
+
+        (*req).connector_handle = strdup(&self.connector_handle);
+        (*req).account_handle = strdup(&self.handle);
+        (*req).volume = volume;
+
+        Ok(self.client.issue(&mut (*req).base))
+      },
+      Err(e) => Err(e),
+    };
+
+    self.update_audio(pending, move |audio| audio.mic_volume = volume)
+  }
+
+  pub fn set_speaker_volume(&self, volume: i32) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    let pending = match check_volume(volume) {
+      Ok(()) => unsafe {
+        let mut req: *mut vx_req_connector_set_local_speaker_volume = mem::zeroed();
+
+        vx_req_connector_set_local_speaker_volume_create(&mut req);
+
+        (*req).connector_handle = strdup(&self.connector_handle);
+        (*req).account_handle = strdup(&self.handle);
+        (*req).volume = volume;
+
+        Ok(self.client.issue(&mut (*req).base))
+      },
+      Err(e) => Err(e),
+    };
+
+    self.update_audio(pending, move |audio| audio.speaker_volume = volume)
+  }
+
+  /// Stops sending microphone audio to every session of the account.
+  pub fn mute_mic(&self, muted: bool) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    let pending = unsafe {
+      let mut req: *mut vx_req_connector_mute_local_mic = mem::zeroed();
+
+      vx_req_connector_mute_local_mic_create(&mut req);
+
+      (*req).connector_handle = strdup(&self.connector_handle);
+      (*req).account_handle = strdup(&self.handle);
+      (*req).mute_level = muted as c_int;
+
+      Ok(self.client.issue(&mut (*req).base))
+    };
+
+    self.update_audio(pending, move |audio| audio.mic_muted = muted)
+  }
+
+  /// Silences playback of every session of the account.
+  pub fn mute_speaker(&self, muted: bool) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    let pending = unsafe {
+      let mut req: *mut vx_req_connector_mute_local_speaker = mem::zeroed();
+
+      vx_req_connector_mute_local_speaker_create(&mut req);
+
+      (*req).connector_handle = strdup(&self.connector_handle);
+      (*req).account_handle = strdup(&self.handle);
+      (*req).mute_level = muted as c_int;
+
+      Ok(self.client.issue(&mut (*req).base))
+    };
+
+    self.update_audio(pending, move |audio| audio.speaker_muted = muted)
+  }
+
+  /// Applies saved settings, stopping at the first request that fails.
+  pub fn restore_audio_settings(
+    &self,
+    settings: AudioSettings,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    let mic_volume = self.set_mic_volume(settings.mic_volume);
+    let speaker_volume = self.set_speaker_volume(settings.speaker_volume);
+    let mic_muted = self.mute_mic(settings.mic_muted);
+    let speaker_muted = self.mute_speaker(settings.speaker_muted);
+
+    async move {
+      mic_volume.await?;
+      speaker_volume.await?;
+      mic_muted.await?;
+      speaker_muted.await
+    }
+  }
+
+  /// Presence, buddies and block rules of this account.
+  pub fn buddy_list(&self) -> BuddyList {
+    BuddyList::new(self.client.clone(), self.handle.clone(), self.buddies.clone())
+  }
+
+  /// Device selection tagged with this account's handle.
+  pub fn audio_devices(&self) -> AudioDevices {
+    AudioDevices::new(self.client.clone(), Some(self.handle.clone()))
+  }
+
+  /// Creates an empty session group. Sessions are added with
+  /// `SessionGroup::join`.
+  pub fn create_sessiongroup(&self) -> SessionGroup {
+    SessionGroup::new(self.client.clone(), self.clone())
+  }
+
+  /// Removes a user from a channel this account moderates.
+  ///
+  /// Moderation tokens have to come from the game server, which holds the
+  /// signing key. This one is a `kick` token from this account's URI for
+  /// the channel with the participant as subject, as minted by
+  /// `TokenGenerator::generate_with_subject`.
+  pub fn kick_user(
+    &self,
+    channel_uri: &str,
+    participant_uri: &str,
+    access_token: &str,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    use std::mem;
+
+    unsafe {
+      let mut req: *mut vx_req_channel_kick_user = mem::zeroed();
+
+      vx_req_channel_kick_user_create(&mut req);
+
+      (*req).account_handle = strdup(&self.handle);
+      (*req).channel_uri = strdup(channel_uri);
+      (*req).participant_uri = strdup(participant_uri);
+      (*req).access_token = strdup(access_token);
+
+      self.client.issue(&mut (*req).base)
+    }
+  }
+
+  /// Mutes or unmutes a user for everyone in a channel this account
+  /// moderates. Takes a server minted `mute` token with the participant as
+  /// subject, like `kick_user`.
+  pub fn mute_user(
+    &self,
+    channel_uri: &str,
+    participant_uri: &str,
+    muted: bool,
+    access_token: &str,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    use std::mem;
+
+    unsafe {
+      let mut req: *mut vx_req_channel_mute_user = mem::zeroed();
+
+      vx_req_channel_mute_user_create(&mut req);
+
+      (*req).account_handle = strdup(&self.handle);
+      (*req).channel_uri = strdup(channel_uri);
+      (*req).participant_uri = strdup(participant_uri);
+      (*req).set_muted = muted as c_int;
+      (*req).access_token = strdup(access_token);
+
+      self.client.issue(&mut (*req).base)
+    }
+  }
+
+  /// Mutes or unmutes everyone in a channel this account moderates,
+  /// except for the moderators themselves. Takes a server minted `mute`
+  /// token without a subject.
+  pub fn mute_all_users(
+    &self,
+    channel_uri: &str,
+    muted: bool,
+    access_token: &str,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    use std::mem;
+
+    unsafe {
+      let mut req: *mut vx_req_channel_mute_all_users = mem::zeroed();
+
+      vx_req_channel_mute_all_users_create(&mut req);
+
+      (*req).account_handle = strdup(&self.handle);
+      (*req).channel_uri = strdup(channel_uri);
+      (*req).set_muted = muted as c_int;
+      (*req).access_token = strdup(access_token);
+
+      self.client.issue(&mut (*req).base)
+    }
+  }
+
+  /// Records a setting once the SDK has accepted it.
+  fn update_audio<P, F>(
+    &self,
+    pending: Result<P, VivoxError>,
+    f: F,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  where
+    P: Future<Output = Result<(), VivoxError>>,
+    F: FnOnce(&mut AudioSettings),
+  {
+    let audio = self.audio.clone();
+
+    async move {
+      pending?.await?;
+      f(&mut audio.lock().unwrap());
+
+      Ok(())
+    }
+  }
+
+  pub fn logout(self) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    let pending = unsafe {
+      let mut req: *mut vx_req_account_logout = mem::zeroed();
+
+      vx_req_account_logout_create(&mut req);
+
+      (*req).account_handle = strdup(&self.handle);
+
+      self.client.issue(&mut (*req).base)
+    };
+
+    async move {
+      pending.await?;
+
+      self.client.unregister_account(&self.handle);
+
+      Ok(())
+    }
+  }
+}
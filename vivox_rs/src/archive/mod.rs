@@ -0,0 +1,222 @@
+use crate::account::Account;
+use crate::client::{Payload, Response, VivoxClient};
+use crate::enums::VivoxError;
+use crate::events::Event;
+use crate::helpers::*;
+use crate::uri::UserUri;
+
+use futures::Stream;
+use serde::{Serialize, Deserialize};
+use std::future::Future;
+use std::os::raw::c_uint;
+use tokio::sync::{broadcast, mpsc};
+use vivox_rs_sys::*;
+
+const STREAM_CAPACITY: usize = 64;
+
+/// Largest page the server hands out per request.
+const MAX_PAGE_SIZE: u32 = 50;
+
+/// Filters for a message archive query. Every filter is optional.
+///
+/// Times are ISO 8601 strings such as `2020-08-01T18:00:00Z`, the same
+/// format `ArchivedMessage::timestamp` uses.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArchiveQuery {
+  pub start: Option<String>,
+  pub end: Option<String>,
+  /// Only messages containing this text
+  pub text: Option<String>,
+  /// Only messages sent by this user
+  pub participant: Option<UserUri>,
+  /// Only messages of this channel, account archives only
+  pub channel_uri: Option<String>,
+  /// Continue after the message with this id, as taken from the last
+  /// `ArchivedMessage::id` a previous query returned
+  pub after: Option<String>,
+  /// Messages fetched per request, from 1 to 50
+  pub page_size: u32,
+  /// Stop after this many messages in total
+  pub limit: Option<usize>,
+}
+
+impl Default for ArchiveQuery {
+  fn default() -> Self {
+    Self {
+      start: None,
+      end: None,
+      text: None,
+      participant: None,
+      channel_uri: None,
+      after: None,
+      page_size: 10,
+      limit: None,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedMessage {
+  /// Server assigned id, usable as `ArchiveQuery::after`
+  pub id: String,
+  pub timestamp: String,
+  /// Channel the message was posted to, empty for directed messages
+  pub channel_uri: String,
+  pub sender_uri: String,
+  pub body: String,
+  pub language: Option<String>,
+  /// Whether the local user received rather than sent the message
+  pub is_inbound: bool,
+}
+
+pub(crate) enum Scope {
+  /// Session handle and channel URI
+  Session(String, String),
+  Account(String),
+}
+
+/// Runs a query page by page, following the cursor until the server
+/// returns a short page or the limit is reached.
+pub(crate) fn query(
+  client: VivoxClient,
+  account: Account,
+  scope: Scope,
+  query: ArchiveQuery,
+) -> impl Stream<Item = Result<ArchivedMessage, VivoxError>>
+{
+  let (mut tx, rx) = mpsc::channel(STREAM_CAPACITY);
+
+  tokio::spawn(async move {
+    if query.page_size == 0 || query.page_size > MAX_PAGE_SIZE {
+      let _ = tx.send(Err(VivoxError::INVALID_ARGUMENT)).await;
+
+      return;
+    }
+
+    if query.limit == Some(0) {
+      return;
+    }
+
+    let mut after = query.after.clone();
+    let mut total = 0;
+
+    loop {
+      // Subscribe before issuing so no result can slip past
+      let mut events = account.events();
+      let pending = issue_page(&client, &scope, &query, after.as_deref());
+
+      let query_id = match pending.await {
+        Ok(Payload::RequestId(id)) => id,
+        Ok(_) => {
+          let _ = tx.send(Err(VivoxError::FAILED)).await;
+
+          return;
+        },
+        Err(e) => {
+          let _ = tx.send(Err(e)).await;
+
+          return;
+        },
+      };
+
+      let mut count = 0;
+      let status = loop {
+        match events.recv().await {
+          Ok(Event::SessionArchiveMessage { query_id: id, mut message, .. })
+          | Ok(Event::AccountArchiveMessage { query_id: id, mut message, .. }) if id == query_id => {
+            // Session results don't repeat the channel they belong to
+            if let Scope::Session(_, uri) = &scope {
+              message.channel_uri = uri.clone();
+            }
+
+            after = Some(message.id.clone());
+            count += 1;
+            total += 1;
+
+            if tx.send(Ok(message)).await.is_err() {
+              return;
+            }
+
+            if Some(total) == query.limit {
+              return;
+            }
+          },
+          Ok(Event::SessionArchiveQueryEnd { query_id: id, status, .. })
+          | Ok(Event::AccountArchiveQueryEnd { query_id: id, status, .. }) if id == query_id => break status,
+          Ok(_) => {},
+          // Results were dropped, the page can't be trusted
+          Err(broadcast::RecvError::Lagged(_)) => break VivoxError::FAILED,
+          Err(broadcast::RecvError::Closed) => break VivoxError::REQUEST_CANCELLED,
+        }
+      };
+
+      if status != VivoxError::SUCCESS {
+        let _ = tx.send(Err(status)).await;
+
+        return;
+      }
+
+      if count < query.page_size {
+        return;
+      }
+    }
+  });
+
+  rx
+}
+
+fn issue_page(
+  client: &VivoxClient,
+  scope: &Scope,
+  query: &ArchiveQuery,
+  after: Option<&str>,
+) -> impl Future<Output = Response>
+{
+  use std::mem;
+
+  let participant = query.participant.as_ref().map(|uri| uri.to_string());
+
+  unsafe {
+    match scope {
+      Scope::Session(handle, _) => {
+        let mut req: *mut vx_req_session_archive_query = mem::zeroed();
+
+        vx_req_session_archive_query_create(&mut req);
+
+        (*req).session_handle = strdup(handle);
+        (*req).max = query.page_size as c_uint;
+        (*req).time_start = opt_strdup(query.start.as_deref());
+        (*req).time_end = opt_strdup(query.end.as_deref());
+        (*req).search_text = opt_strdup(query.text.as_deref());
+        (*req).participant_uri = opt_strdup(participant.as_deref());
+        (*req).after_id = opt_strdup(after);
+
+        client.issue_for(&mut (*req).base)
+      },
+      Scope::Account(handle) => {
+        let mut req: *mut vx_req_account_archive_query = mem::zeroed();
+
+        vx_req_account_archive_query_create(&mut req);
+
+        (*req).account_handle = strdup(handle);
+        (*req).max = query.page_size as c_uint;
+        (*req).time_start = opt_strdup(query.start.as_deref());
+        (*req).time_end = opt_strdup(query.end.as_deref());
+        (*req).search_text = opt_strdup(query.text.as_deref());
+        (*req).participant_uri = opt_strdup(participant.as_deref());
+        (*req).channel_uri = opt_strdup(query.channel_uri.as_deref());
+        (*req).after_id = opt_strdup(after);
+
+        client.issue_for(&mut (*req).base)
+      },
+    }
+  }
+}
+
+/// Unset filters stay null so the SDK ignores them.
+fn opt_strdup(input: Option<&str>) -> *mut std::os::raw::c_char {
+  match input {
+    Some(input) => strdup(input),
+    None => std::ptr::null_mut(),
+  }
+}
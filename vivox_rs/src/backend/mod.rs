@@ -0,0 +1,252 @@
+//! The layer between `VivoxClient` and the SDK.
+//!
+//! `VivoxClient` never calls into the SDK for requests or messages itself.
+//! It hands requests to a `VivoxBackend` and polls it for decoded
+//! responses and events, so tests can put a recording, fault-injecting or
+//! simulated backend in place of `FfiBackend`.
+//!
+//! Request structs and the strings in them are still allocated through
+//! `vivox_rs_sys`. A backend that never passes a request on to the SDK
+//! frees it by dropping the `Request`.
+
+use crate::client::{InitError, Payload, Response};
+use crate::devices;
+use crate::enums::VivoxError;
+use crate::events::Event;
+use crate::helpers::*;
+use crate::raw::{RawEvent, RawRequest};
+use crate::version::{self, SdkVersion};
+
+use std::mem::transmute;
+use vivox_rs_sys::*;
+
+/// What `VivoxClient` needs from an SDK.
+pub trait VivoxBackend: Send + Sync {
+  /// Called once from `VivoxClient::with_backend`, before any request.
+  fn initialize(&self) -> Result<(), InitError>;
+
+  /// Called from the poll thread once the client was shut down.
+  fn uninitialize(&self);
+
+  /// Takes a request tagged with its cookie. An error fails the request
+  /// right away, otherwise its response is expected from `poll`.
+  fn issue(&self, req: Request) -> Result<(), VivoxError>;
+
+  /// Next pending message, or `None` once there is nothing left to deliver
+  /// for now. The client drains this every 100ms.
+  fn poll(&self) -> Option<Message>;
+
+  /// Version of the SDK behind the backend, if there is one.
+  fn sdk_version(&self) -> Option<SdkVersion> {
+    None
+  }
+
+  /// Has the service end a session on its own with `status`, for
+  /// simulated faults. Returns false when the backend can't, which leaves
+  /// it to `Faults` to make up the events.
+  fn drop_session(&self, _session_handle: &str, _status: VivoxError) -> bool {
+    false
+  }
+
+  /// Same as `drop_session` for an account losing its connection.
+  fn drop_account(&self, _account_handle: &str, _status: VivoxError) -> bool {
+    false
+  }
+}
+
+/// A decoded message from the SDK.
+#[derive(Clone, Debug)]
+pub enum Message {
+  /// Result of the request issued with `cookie`
+  Response { cookie: String, response: Response },
+  /// A decoded event, along with the SDK struct it was copied from when
+  /// it came from the SDK
+  Event(Event, Option<RawEvent>),
+}
+
+/// A request owned by the backend it was issued to.
+///
+/// Dropping it frees the request struct.
+pub struct Request {
+  ptr: *mut vx_req_base_t,
+}
+
+// The struct is fully built before it is handed over and nothing else
+// keeps a pointer to it
+unsafe impl Send for Request {}
+
+impl Request {
+  /// # Safety
+  /// `ptr` must come from a `vx_req_*_create` function and must not be
+  /// used by the caller afterwards.
+  pub(crate) unsafe fn from_raw(ptr: *mut vx_req_base_t) -> Self {
+    Self { ptr }
+  }
+
+  pub fn cookie(&self) -> String {
+    unsafe { to_owned_string((*self.ptr).cookie) }
+  }
+
+  pub fn kind(&self) -> vx_request_type {
+    unsafe { (*self.ptr).type_ }
+  }
+
+  /// Copies the fields of the request, see `RawRequest`.
+  pub fn raw(&self) -> RawRequest {
+    unsafe { RawRequest::capture(self.ptr) }
+  }
+
+  pub fn as_ptr(&self) -> *const vx_req_base_t {
+    self.ptr
+  }
+
+  /// Gives up ownership, typically to pass the request to
+  /// `vx_issue_request`, which frees it along with its response.
+  pub fn into_raw(self) -> *mut vx_req_base_t {
+    let ptr = self.ptr;
+
+    std::mem::forget(self);
+
+    ptr
+  }
+}
+
+impl Drop for Request {
+  fn drop(&mut self) {
+    unsafe {
+      vx_destroy_message(&mut (*self.ptr).message);
+    }
+  }
+}
+
+/// The SDK linked through `vivox_rs_sys`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FfiBackend;
+
+impl VivoxBackend for FfiBackend {
+  fn initialize(&self) -> Result<(), InitError> {
+    crate::init()
+  }
+
+  fn uninitialize(&self) {
+    unsafe { vx_uninitialize(); }
+  }
+
+  fn issue(&self, req: Request) -> Result<(), VivoxError> {
+    match unsafe { vx_issue_request(req.into_raw()) } {
+      0 => Ok(()),
+      status => Err(VivoxError::from_code(status)),
+    }
+  }
+
+  fn poll(&self) -> Option<Message> {
+    use std::mem;
+
+    unsafe {
+      let mut m: *mut vx_message_base_t = mem::zeroed();
+
+      loop {
+        let status = vx_get_message(&mut m);
+
+        if status == VX_GET_MESSAGE_AVAILABLE as i32 {
+          let msg = decode(m);
+
+          vx_destroy_message(m);
+
+          // Messages the wrapper does not know about are skipped
+          if msg.is_some() {
+            return msg;
+          }
+        }
+        else if status == VX_GET_MESSAGE_NO_MESSAGE as i32 {
+          return None;
+        }
+        else {
+          // Retrying right away would spin, the next poll tries again
+          log::error!("vx_get_message() failed with {}", status);
+
+          return None;
+        }
+      }
+    }
+  }
+
+  fn sdk_version(&self) -> Option<SdkVersion> {
+    version::sdk_version().ok()
+  }
+
+  #[cfg(feature = "mock")]
+  fn drop_session(&self, session_handle: &str, status: VivoxError) -> bool {
+    vivox_rs_sys::mock::server::drop_session(session_handle, status.code() as u32)
+  }
+
+  #[cfg(feature = "mock")]
+  fn drop_account(&self, account_handle: &str, status: VivoxError) -> bool {
+    vivox_rs_sys::mock::server::drop_account(account_handle, status.code() as u32)
+  }
+}
+
+/// Copies what the wrapper needs out of a message before the SDK frees it.
+///
+/// # Safety
+/// `msg` must be a live message returned by `vx_get_message`.
+pub(crate) unsafe fn decode(msg: *mut vx_message_base_t) -> Option<Message> {
+  match (*msg).type_ {
+    vx_message_type_msg_response => {
+      let resp = transmute::<*mut vx_message_base_t, *mut vx_resp_base_t>(msg);
+
+      if (*resp).request.is_null() {
+        return None;
+      }
+
+      let response = match (*resp).return_code {
+        1 => Err(VivoxError::from_code((*resp).status_code)),
+        _ => Ok(payload(resp)),
+      };
+
+      Some(Message::Response {
+        cookie: to_owned_string((*(*resp).request).cookie),
+        response,
+      })
+    },
+    vx_message_type_msg_event => {
+      let evt = transmute::<*mut vx_message_base_t, *mut vx_evt_base_t>(msg);
+
+      let event = Event::decode(evt)?;
+
+      Some(Message::Event(event, RawEvent::capture(evt)))
+    },
+    _ => None,
+  }
+}
+
+unsafe fn payload(resp: *mut vx_resp_base_t) -> Payload {
+  match (*resp).type_ {
+    vx_response_type_resp_aux_get_capture_devices => {
+      let resp = transmute::<*mut vx_resp_base_t, *mut vx_resp_aux_get_capture_devices>(resp);
+
+      Payload::Devices(devices::decode_capture_devices(resp))
+    },
+    vx_response_type_resp_aux_get_render_devices => {
+      let resp = transmute::<*mut vx_resp_base_t, *mut vx_resp_aux_get_render_devices>(resp);
+
+      Payload::Devices(devices::decode_render_devices(resp))
+    },
+    vx_response_type_resp_account_send_message => {
+      let resp = transmute::<*mut vx_resp_base_t, *mut vx_resp_account_send_message>(resp);
+
+      Payload::RequestId(to_owned_string((*resp).request_id))
+    },
+    vx_response_type_resp_session_archive_query => {
+      let resp = transmute::<*mut vx_resp_base_t, *mut vx_resp_session_archive_query>(resp);
+
+      Payload::RequestId(to_owned_string((*resp).query_id))
+    },
+    vx_response_type_resp_account_archive_query => {
+      let resp = transmute::<*mut vx_resp_base_t, *mut vx_resp_account_archive_query>(resp);
+
+      Payload::RequestId(to_owned_string((*resp).query_id))
+    },
+    _ => Payload::None,
+  }
+}
@@ -0,0 +1,454 @@
+use crate::backend::{FfiBackend, Message, Request, VivoxBackend};
+use crate::connector::Connector;
+use crate::devices::{AudioDevices, DeviceList};
+use crate::enums::VivoxError;
+use crate::events::{Event, Route};
+use crate::helpers::*;
+use crate::version::{IncompatibleSdk, SdkVersion};
+
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use tokio::sync::{broadcast, oneshot};
+use vivox_rs_sys::*;
+
+const EVENT_CAPACITY: usize = 256;
+
+pub type Response = Result<Payload, VivoxError>;
+
+/// Data decoded from a response before the SDK frees it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Payload {
+  None,
+  Devices(DeviceList),
+  /// Id the SDK assigned to a request, echoed by later events about it
+  RequestId(String),
+}
+
+/// Why a `VivoxClient` could not be created.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InitError {
+  /// The SDK refused to initialize
+  Sdk(VivoxError),
+  /// The library does not fit the bindings, it was left alone
+  Incompatible(IncompatibleSdk),
+}
+
+impl From<VivoxError> for InitError {
+  fn from(status: VivoxError) -> Self {
+    InitError::Sdk(status)
+  }
+}
+
+impl From<IncompatibleSdk> for InitError {
+  fn from(incompatible: IncompatibleSdk) -> Self {
+    InitError::Incompatible(incompatible)
+  }
+}
+
+impl fmt::Display for InitError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      InitError::Sdk(status) => write!(f, "The Vivox SDK failed to initialize: {}", status),
+      InitError::Incompatible(incompatible) => incompatible.fmt(f),
+    }
+  }
+}
+
+impl Error for InitError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      InitError::Sdk(status) => Some(status),
+      InitError::Incompatible(incompatible) => Some(incompatible),
+    }
+  }
+}
+
+/// Number of live SDK objects owned by a `VivoxClient`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ResourceUsage {
+  pub connectors: usize,
+  pub accounts: usize,
+  pub sessiongroups: usize,
+  pub sessions: usize,
+}
+
+#[derive(Default)]
+struct Routes {
+  connectors: HashSet<String>,
+  accounts: HashMap<String, broadcast::Sender<Event>>,
+  // Logged in account handle to connector handle
+  logins: HashMap<String, String>,
+  // Session handle to session group handle
+  sessions: HashMap<String, String>,
+  // Session group handle to owning account handle
+  sessiongroups: HashMap<String, String>,
+}
+
+impl Routes {
+  fn owner(&self, route: Route<'_>) -> Option<&broadcast::Sender<Event>> {
+    let sessiongroup = match route {
+      Route::Account(handle) => return self.accounts.get(handle),
+      Route::Session(handle) => self.sessions.get(handle)?,
+      Route::SessionGroup(handle) => handle,
+      Route::Global => return None,
+    };
+
+    self.accounts.get(self.sessiongroups.get(sessiongroup)?)
+  }
+}
+
+struct Registry {
+  backend: Box<dyn VivoxBackend>,
+  pending: Mutex<HashMap<String, oneshot::Sender<Response>>>,
+  routes: Mutex<Routes>,
+  usage: Mutex<ResourceUsage>,
+  events: broadcast::Sender<Event>,
+  running: AtomicBool,
+  poller: Mutex<Option<JoinHandle<()>>>,
+  next_id: AtomicUsize,
+  sdk_version: Option<SdkVersion>,
+}
+
+/// Owns the SDK instance and the thread polling it for messages.
+///
+/// Responses are matched to their request through the request cookie and
+/// events are forwarded to the account owning the handle they mention.
+/// Cloning is cheap, every clone talks to the same SDK instance.
+#[derive(Clone)]
+pub struct VivoxClient {
+  inner: Arc<Registry>,
+}
+
+impl VivoxClient {
+  /// Fails without touching the SDK when its version does not fit the
+  /// bindings, see `version`.
+  pub fn new() -> Result<Self, InitError> {
+    Self::with_backend(FfiBackend)
+  }
+
+  /// Runs the client on top of another SDK implementation.
+  pub fn with_backend<B: VivoxBackend + 'static>(backend: B) -> Result<Self, InitError> {
+    use std::time::Duration;
+
+    backend.initialize()?;
+
+    let sdk_version = backend.sdk_version();
+    let (events, _) = broadcast::channel(EVENT_CAPACITY);
+    let client = Self {
+      inner: Arc::new(Registry {
+        backend: Box::new(backend),
+        pending: Mutex::new(HashMap::new()),
+        routes: Mutex::new(Routes::default()),
+        usage: Mutex::new(ResourceUsage::default()),
+        events,
+        running: AtomicBool::new(true),
+        poller: Mutex::new(None),
+        next_id: AtomicUsize::new(1),
+        sdk_version,
+      }),
+    };
+
+    let poller = client.clone();
+    let handle = thread::spawn(move || {
+      while poller.inner.running.load(Ordering::Acquire) {
+        poller.poll_loop();
+        thread::sleep(Duration::from_millis(100));
+      }
+
+      poller.inner.backend.uninitialize();
+    });
+
+    *client.inner.poller.lock().unwrap() = Some(handle);
+
+    Ok(client)
+  }
+
+  /// Creates a new connector with its own handle.
+  ///
+  /// Fails with `MAX_CONNECTOR_LIMIT_EXCEEDED` once the SDK refuses any
+  /// more connectors in this process.
+  pub fn create_connector(
+    &self,
+    acct_mgmt_server: &str,
+  ) -> impl Future<Output = Result<Connector, VivoxError>>
+  {
+    use std::mem;
+
+    let handle = self.next_handle("c");
+    let pending = unsafe {
+      let mut req: *mut vx_req_connector_create = mem::zeroed();
+
+      // Creates default Connector Create request struct
+      vx_req_connector_create_create(&mut req);
+
+      (*req).connector_handle = strdup(&handle);
+      (*req).acct_mgmt_server = strdup(acct_mgmt_server);
+
+      self.issue(&mut (*req).base)
+    };
+    let client = self.clone();
+
+    async move {
+      pending.await?;
+      client.register_connector(&handle);
+
+      Ok(Connector::new(client, handle))
+    }
+  }
+
+  /// Subscribes to every event the SDK raises, regardless of account.
+  pub fn events(&self) -> broadcast::Receiver<Event> {
+    self.inner.events.subscribe()
+  }
+
+  /// Device selection shared by every account in the process.
+  pub fn audio_devices(&self) -> AudioDevices {
+    AudioDevices::new(self.clone(), None)
+  }
+
+  pub fn usage(&self) -> ResourceUsage {
+    *self.inner.usage.lock().unwrap()
+  }
+
+  /// Version of the SDK library, checked against the bindings when the
+  /// client was created. `None` for backends without one, like replays.
+  pub fn sdk_version(&self) -> Option<SdkVersion> {
+    self.inner.sdk_version
+  }
+
+  /// Stops the poll thread and uninitializes the SDK, returning once both
+  /// are done. Requests still waiting for a response fail with
+  /// `REQUEST_CANCELLED`.
+  pub fn shutdown(&self) {
+    self.inner.running.store(false, Ordering::Release);
+
+    let poller = self.inner.poller.lock().unwrap().take();
+
+    if let Some(poller) = poller {
+      // The poll thread can't wait for itself
+      if poller.thread().id() != thread::current().id() {
+        let _ = poller.join();
+      }
+    }
+
+    self.inner.pending.lock().unwrap().clear();
+  }
+
+  /// Tags the request with a fresh cookie and hands it to the backend.
+  ///
+  /// The returned future only holds the response channel, so callers can
+  /// drop their raw request pointer before awaiting it.
+  pub(crate) fn issue(
+    &self,
+    req: *mut vx_req_base_t,
+  ) -> impl Future<Output = Result<(), VivoxError>> + Send + 'static
+  {
+    let pending = self.issue_for(req);
+
+    async move {
+      pending.await.map(|_| ())
+    }
+  }
+
+  /// Like `issue`, but resolves to whatever the response carries.
+  pub(crate) fn issue_for(
+    &self,
+    req: *mut vx_req_base_t,
+  ) -> impl Future<Output = Response> + Send + 'static
+  {
+    let cookie = self.next_handle("req");
+    let (tx, rx) = oneshot::channel();
+
+    self.inner.pending.lock().unwrap().insert(cookie.clone(), tx);
+
+    let req = unsafe {
+      (*req).cookie = strdup(&cookie);
+
+      Request::from_raw(req)
+    };
+
+    if let Err(status) = self.inner.backend.issue(req) {
+      if let Some(tx) = self.inner.pending.lock().unwrap().remove(&cookie) {
+        let _ = tx.send(Err(status));
+      }
+    }
+
+    async move {
+      rx.await.unwrap_or(Err(VivoxError::REQUEST_CANCELLED))
+    }
+  }
+
+  /// Allocates a handle such as `c1` or `sg12`, also used for cookies.
+  ///
+  /// The SDK rejects a second connector, account or session using a handle
+  /// that is still alive, so handles are never reused. Nothing outlives the
+  /// client, and counting per client makes a replayed client mint the same
+  /// handles as the recorded one.
+  pub(crate) fn next_handle(&self, prefix: &str) -> String {
+    format!("{}{}", prefix, self.inner.next_id.fetch_add(1, Ordering::Relaxed))
+  }
+
+  fn register_connector(&self, connector_handle: &str) {
+    let mut routes = self.inner.routes.lock().unwrap();
+
+    if routes.connectors.insert(connector_handle.to_string()) {
+      self.inner.usage.lock().unwrap().connectors += 1;
+    }
+  }
+
+  /// Forgets a shut down connector along with the accounts logged in
+  /// through it. Only the first call for a handle counts.
+  pub(crate) fn unregister_connector(&self, connector_handle: &str) {
+    let accounts: Vec<String> = {
+      let mut routes = self.inner.routes.lock().unwrap();
+
+      if !routes.connectors.remove(connector_handle) {
+        return;
+      }
+
+      self.inner.usage.lock().unwrap().connectors -= 1;
+
+      routes.logins
+        .iter()
+        .filter(|(_, connector)| *connector == connector_handle)
+        .map(|(account, _)| account.clone())
+        .collect()
+    };
+
+    for account in accounts {
+      self.unregister_account(&account);
+    }
+  }
+
+  /// Creates the event channel for an account. Events for the account, its
+  /// session groups and its sessions are delivered here.
+  pub(crate) fn register_account(&self, account_handle: &str) -> broadcast::Sender<Event> {
+    let (tx, _) = broadcast::channel(EVENT_CAPACITY);
+
+    self.inner.routes.lock().unwrap()
+      .accounts
+      .insert(account_handle.to_string(), tx.clone());
+
+    tx
+  }
+
+  /// Counts an account whose login went through.
+  pub(crate) fn add_login(&self, connector_handle: &str, account_handle: &str) {
+    let mut routes = self.inner.routes.lock().unwrap();
+    let previous = routes.logins.insert(account_handle.to_string(), connector_handle.to_string());
+
+    if previous.is_none() {
+      self.inner.usage.lock().unwrap().accounts += 1;
+    }
+  }
+
+  /// Drops the routes of an account and of its session groups and
+  /// sessions. Calling it again for the same handle does nothing.
+  pub(crate) fn unregister_account(&self, account_handle: &str) {
+    let mut routes = self.inner.routes.lock().unwrap();
+    let mut usage = self.inner.usage.lock().unwrap();
+    let groups: Vec<String> = routes.sessiongroups
+      .iter()
+      .filter(|(_, owner)| *owner == account_handle)
+      .map(|(group, _)| group.clone())
+      .collect();
+
+    routes.accounts.remove(account_handle);
+
+    if routes.logins.remove(account_handle).is_some() {
+      usage.accounts -= 1;
+    }
+
+    for group in groups {
+      routes.sessiongroups.remove(&group);
+      usage.sessiongroups -= 1;
+
+      let before = routes.sessions.len();
+      routes.sessions.retain(|_, owner| *owner != group);
+      usage.sessions -= before - routes.sessions.len();
+    }
+  }
+
+  pub(crate) fn register_session(
+    &self,
+    account_handle: &str,
+    sessiongroup_handle: &str,
+    session_handle: &str,
+  ) {
+    let mut routes = self.inner.routes.lock().unwrap();
+    let mut usage = self.inner.usage.lock().unwrap();
+
+    if !routes.sessiongroups.contains_key(sessiongroup_handle) {
+      routes.sessiongroups.insert(sessiongroup_handle.to_string(), account_handle.to_string());
+      usage.sessiongroups += 1;
+    }
+
+    if !routes.sessions.contains_key(session_handle) {
+      routes.sessions.insert(session_handle.to_string(), sessiongroup_handle.to_string());
+      usage.sessions += 1;
+    }
+  }
+
+  pub(crate) fn unregister_session(&self, sessiongroup_handle: &str, session_handle: &str) {
+    let mut routes = self.inner.routes.lock().unwrap();
+    let mut usage = self.inner.usage.lock().unwrap();
+
+    if routes.sessions.remove(session_handle).is_some() {
+      usage.sessions -= 1;
+    }
+
+    // A group without sessions is torn down by the SDK as well
+    let group_in_use = routes.sessions.values().any(|group| group == sessiongroup_handle);
+
+    if !group_in_use && routes.sessiongroups.remove(sessiongroup_handle).is_some() {
+      usage.sessiongroups -= 1;
+    }
+  }
+
+  fn poll_loop(&self) {
+    while let Some(msg) = self.inner.backend.poll() {
+      match msg {
+        Message::Response { cookie, response } => {
+          if let Some(tx) = self.inner.pending.lock().unwrap().remove(&cookie) {
+            // The caller may have stopped waiting, which is fine
+            let _ = tx.send(response);
+          }
+        },
+        Message::Event(evt, _) => self.dispatch(evt),
+      }
+    }
+  }
+
+  fn dispatch(&self, evt: Event) {
+    let owner = self.inner.routes.lock().unwrap()
+      .owner(evt.route())
+      .cloned();
+
+    if let Some(tx) = owner {
+      let _ = tx.send(evt.clone());
+    }
+
+    // Removal events are the last ones mentioning a handle
+    match &evt {
+      Event::SessionRemoved { sessiongroup_handle, session_handle, .. } => {
+        self.unregister_session(sessiongroup_handle, session_handle);
+      },
+      Event::SessionGroupRemoved { sessiongroup_handle } => {
+        let mut routes = self.inner.routes.lock().unwrap();
+
+        if routes.sessiongroups.remove(sessiongroup_handle).is_some() {
+          self.inner.usage.lock().unwrap().sessiongroups -= 1;
+        }
+      },
+      _ => {},
+    }
+
+    let _ = self.inner.events.send(evt);
+  }
+}
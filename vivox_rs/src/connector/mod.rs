@@ -0,0 +1,124 @@
+use crate::account::{Account, AnonymousLogin};
+use crate::client::VivoxClient;
+use crate::enums::{LoginState, VivoxError};
+use crate::events::Event;
+use crate::helpers::*;
+
+use std::future::Future;
+use vivox_rs_sys::*;
+
+/// A connection to a Vivox account management server.
+///
+/// Any number of accounts can be logged in through one connector, and any
+/// number of connectors can exist in the same process.
+#[derive(Clone)]
+pub struct Connector {
+  client: VivoxClient,
+  handle: String,
+}
+
+impl Connector {
+  pub(crate) fn new(client: VivoxClient, handle: String) -> Self {
+    Self {
+      client,
+      handle,
+    }
+  }
+
+  pub fn handle(&self) -> &str {
+    &self.handle
+  }
+
+  /// Prepares a login request for `acct_name` with this connector and a
+  /// freshly allocated account handle already filled in.
+  pub fn anonymous_login(&self, acct_name: &str) -> AnonymousLogin {
+    let mut req = AnonymousLogin::new();
+
+    req
+      .connector_handle(&self.handle)
+      .account_handle(&self.client.next_handle("a"))
+      .acct_name(acct_name);
+
+    req
+  }
+
+  /// Issues a login request and resolves once the account is logged in.
+  ///
+  /// Fails with `MAX_LOGINS_PER_USER_EXCEEDED` when the same user is
+  /// already logged in too many times.
+  pub fn login(
+    &self,
+    req: &mut AnonymousLogin,
+  ) -> impl Future<Output = Result<Account, VivoxError>>
+  {
+    let account_handle = req.get_account_handle();
+    let acct_name = req.get_acct_name();
+    let uri = req.get_uri();
+
+    // Subscribe before issuing so the state change can't be missed
+    let events = self.client.register_account(&account_handle);
+    let mut rx = events.subscribe();
+    let pending = self.client.issue(req.base());
+    let connector = self.clone();
+
+    async move {
+      let result = async {
+        pending.await?;
+
+        loop {
+          match rx.recv().await {
+            Ok(Event::LoginStateChanged { state: LoginState::LoggedIn, .. }) => return Ok(()),
+            Ok(Event::LoginStateChanged { state: LoginState::Error, status, .. }) => return Err(status),
+            Ok(Event::LoginStateChanged { state: LoginState::LoggedOut, status, .. }) => return Err(status),
+            Ok(_) => {},
+            Err(_) => return Err(VivoxError::REQUEST_CANCELLED),
+          }
+        }
+      }.await;
+
+      match result {
+        Ok(()) => {
+          connector.client.add_login(&connector.handle, &account_handle);
+
+          Ok(Account::new(
+            connector.client,
+            connector.handle,
+            account_handle,
+            acct_name,
+            uri,
+            events,
+            rx,
+          ))
+        },
+        Err(e) => {
+          connector.client.unregister_account(&account_handle);
+
+          Err(e)
+        },
+      }
+    }
+  }
+
+  /// Shuts the connector down. Accounts logged in through it are logged
+  /// out by the SDK, and their session groups and sessions go with them.
+  pub fn shutdown(self) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    let pending = unsafe {
+      let mut req: *mut vx_req_connector_initiate_shutdown = mem::zeroed();
+
+      vx_req_connector_initiate_shutdown_create(&mut req);
+
+      (*req).connector_handle = strdup(&self.handle);
+
+      self.client.issue(&mut (*req).base)
+    };
+
+    async move {
+      pending.await?;
+      self.client.unregister_connector(&self.handle);
+
+      Ok(())
+    }
+  }
+}
@@ -0,0 +1,297 @@
+use crate::client::{Payload, VivoxClient};
+use crate::enums::VivoxError;
+use crate::events::Event;
+use crate::helpers::*;
+
+use serde::{Serialize, Deserialize};
+use std::future::Future;
+use tokio::sync::{broadcast, mpsc};
+use vivox_rs_sys::*;
+
+const CHANGE_CAPACITY: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeviceType {
+  /// A physical device picked by its id
+  Specific,
+  /// Follows whatever the operating system uses by default
+  DefaultSystem,
+  /// Discards captured audio or plays nothing
+  Null,
+  /// Follows the operating system's default communication device
+  DefaultCommunication,
+}
+
+impl DeviceType {
+  pub(crate) fn from_raw(raw: vx_device_type) -> Self {
+    match raw {
+      vx_device_type_vx_device_type_default_system => DeviceType::DefaultSystem,
+      vx_device_type_vx_device_type_null => DeviceType::Null,
+      vx_device_type_vx_device_type_default_communication => DeviceType::DefaultCommunication,
+      _ => DeviceType::Specific,
+    }
+  }
+}
+
+/// A capture or render device known to the SDK.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AudioDevice {
+  /// Specifier passed back to `set_capture_device` or `set_render_device`
+  pub id: String,
+  pub display_name: String,
+  pub device_type: DeviceType,
+  /// Whether this is the operating system's default device
+  pub is_default: bool,
+}
+
+/// Devices of one direction along with the one in use.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceList {
+  pub devices: Vec<AudioDevice>,
+  /// The device that was selected, possibly one of the default entries
+  pub current: Option<AudioDevice>,
+  /// The physical device actually opened for the current selection
+  pub effective: Option<AudioDevice>,
+}
+
+/// A device being plugged in, unplugged or swapped by the system.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeviceChange {
+  Added(AudioDevice),
+  Removed(AudioDevice),
+  /// The capture device in use changed, for example after a default switch
+  CaptureChanged(AudioDevice),
+  /// The capture device in use went away
+  CaptureRemoved(AudioDevice),
+  RenderChanged(AudioDevice),
+  RenderRemoved(AudioDevice),
+}
+
+/// Lists and selects the microphones and speakers used by the SDK.
+///
+/// Device selection is process wide. When created from an `Account` the
+/// requests are tagged with its handle, which the SDK uses to pick the
+/// right audio context when several accounts are logged in.
+#[derive(Clone)]
+pub struct AudioDevices {
+  client: VivoxClient,
+  account_handle: Option<String>,
+}
+
+impl AudioDevices {
+  pub(crate) fn new(client: VivoxClient, account_handle: Option<String>) -> Self {
+    Self {
+      client,
+      account_handle,
+    }
+  }
+
+  /// Fails with `NO_CAPTURE_DEVICES_FOUND` when no microphone is present.
+  pub fn capture_devices(&self) -> impl Future<Output = Result<DeviceList, VivoxError>> {
+    use std::mem;
+
+    let pending = unsafe {
+      let mut req: *mut vx_req_aux_get_capture_devices = mem::zeroed();
+
+      vx_req_aux_get_capture_devices_create(&mut req);
+
+      if let Some(handle) = &self.account_handle {
+        (*req).account_handle = strdup(handle);
+      }
+
+      self.client.issue_for(&mut (*req).base)
+    };
+
+    async move {
+      match pending.await? {
+        Payload::Devices(list) => Ok(list),
+        _ => Err(VivoxError::FAILED),
+      }
+    }
+  }
+
+  /// Fails with `NO_RENDER_DEVICES_FOUND` when no speaker is present.
+  pub fn render_devices(&self) -> impl Future<Output = Result<DeviceList, VivoxError>> {
+    use std::mem;
+
+    let pending = unsafe {
+      let mut req: *mut vx_req_aux_get_render_devices = mem::zeroed();
+
+      vx_req_aux_get_render_devices_create(&mut req);
+
+      if let Some(handle) = &self.account_handle {
+        (*req).account_handle = strdup(handle);
+      }
+
+      self.client.issue_for(&mut (*req).base)
+    };
+
+    async move {
+      match pending.await? {
+        Payload::Devices(list) => Ok(list),
+        _ => Err(VivoxError::FAILED),
+      }
+    }
+  }
+
+  /// Selects the microphone by its `AudioDevice::id`.
+  ///
+  /// Fails with `INVALID_CAPTURE_DEVICE_SPECIFIER` for an unknown id and
+  /// with `CAPTURE_DEVICE_IN_USE` or `UNABLE_TO_OPEN_CAPTURE_DEVICE` when
+  /// another application holds the device exclusively.
+  pub fn set_capture_device(&self, id: &str) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    unsafe {
+      let mut req: *mut vx_req_aux_set_capture_device = mem::zeroed();
+
+      vx_req_aux_set_capture_device_create(&mut req);
+
+      (*req).capture_device_specifier = strdup(id);
+
+      if let Some(handle) = &self.account_handle {
+        (*req).account_handle = strdup(handle);
+      }
+
+      self.client.issue(&mut (*req).base)
+    }
+  }
+
+  /// Selects the speaker by its `AudioDevice::id`.
+  ///
+  /// Fails with `RENDER_DEVICE_DOES_NOT_EXIST` for an unknown id and with
+  /// `RENDER_DEVICE_IN_USE` when the device cannot be opened.
+  pub fn set_render_device(&self, id: &str) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    unsafe {
+      let mut req: *mut vx_req_aux_set_render_device = mem::zeroed();
+
+      vx_req_aux_set_render_device_create(&mut req);
+
+      (*req).render_device_specifier = strdup(id);
+
+      if let Some(handle) = &self.account_handle {
+        (*req).account_handle = strdup(handle);
+      }
+
+      self.client.issue(&mut (*req).base)
+    }
+  }
+
+  /// Devices being plugged in and out from now on. Must be called from
+  /// within a Tokio runtime.
+  pub fn changes(&self) -> mpsc::Receiver<DeviceChange> {
+    let mut events = self.client.events();
+    let (mut tx, rx) = mpsc::channel(CHANGE_CAPACITY);
+
+    tokio::spawn(async move {
+      loop {
+        match events.recv().await {
+          Ok(Event::AudioDeviceHotSwap { change }) => {
+            if tx.send(change).await.is_err() {
+              break;
+            }
+          },
+          Ok(_) => {},
+          Err(broadcast::RecvError::Lagged(_)) => {},
+          Err(broadcast::RecvError::Closed) => break,
+        }
+      }
+    });
+
+    rx
+  }
+}
+
+/// Copies a device, flagging it as the default if it matches `default`.
+unsafe fn decode_device(device: *const vx_device_t, default: *const vx_device_t) -> Option<AudioDevice> {
+  if device.is_null() {
+    return None;
+  }
+
+  let id = to_owned_string((*device).device);
+  let is_default = !default.is_null() && to_owned_string((*default).device) == id;
+
+  Some(AudioDevice {
+    id,
+    display_name: to_owned_string((*device).display_name),
+    device_type: DeviceType::from_raw((*device).device_type),
+    is_default,
+  })
+}
+
+unsafe fn decode_list(
+  devices: *mut *mut vx_device_t,
+  count: i32,
+  current: *const vx_device_t,
+  effective: *const vx_device_t,
+  default: *const vx_device_t,
+) -> DeviceList {
+  let devices = match devices.is_null() {
+    true => Vec::new(),
+    false => (0..count.max(0) as usize)
+      .filter_map(|i| decode_device(*devices.add(i), default))
+      .collect(),
+  };
+
+  DeviceList {
+    devices,
+    current: decode_device(current, default),
+    effective: decode_device(effective, default),
+  }
+}
+
+pub(crate) fn decode_capture_devices(resp: *mut vx_resp_aux_get_capture_devices) -> DeviceList {
+  unsafe {
+    decode_list(
+      (*resp).capture_devices,
+      (*resp).count,
+      (*resp).current_capture_device,
+      (*resp).effective_capture_device,
+      (*resp).default_capture_device,
+    )
+  }
+}
+
+pub(crate) fn decode_render_devices(resp: *mut vx_resp_aux_get_render_devices) -> DeviceList {
+  unsafe {
+    decode_list(
+      (*resp).render_devices,
+      (*resp).count,
+      (*resp).current_render_device,
+      (*resp).effective_render_device,
+      (*resp).default_render_device,
+    )
+  }
+}
+
+/// Decodes a hot swap event. Returns `None` when it carries no device,
+/// which the SDK does when hot swapping is disabled.
+pub(crate) fn decode_hot_swap(evt: *mut vx_evt_audio_device_hot_swap) -> Option<DeviceChange> {
+  unsafe {
+    let device = decode_device((*evt).relevant_device, std::ptr::null())?;
+
+    match (*evt).event_type {
+      vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_audio_device_added => {
+        Some(DeviceChange::Added(device))
+      },
+      vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_audio_device_removed => {
+        Some(DeviceChange::Removed(device))
+      },
+      vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_active_capture_device_changed => {
+        Some(DeviceChange::CaptureChanged(device))
+      },
+      vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_active_capture_device_removed => {
+        Some(DeviceChange::CaptureRemoved(device))
+      },
+      vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_active_render_device_changed => {
+        Some(DeviceChange::RenderChanged(device))
+      },
+      vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_active_render_device_removed => {
+        Some(DeviceChange::RenderRemoved(device))
+      },
+      _ => None,
+    }
+  }
+}
@@ -0,0 +1,430 @@
+use crate::archive::ArchivedMessage;
+use crate::devices::{self, DeviceChange};
+use crate::enums::{LoginState, MediaState, RemovalReason, TextState, VivoxError};
+use crate::helpers::*;
+use crate::messages::{self, ChannelMessage, UserMessage};
+use crate::presence::{Presence, PresenceStatus, SubscriptionRequest};
+use crate::uri::UserUri;
+
+use serde::{Serialize, Deserialize};
+use std::mem::transmute;
+use std::time::{Duration, SystemTime};
+use vivox_rs_sys::*;
+
+/// Owned copy of an SDK event.
+///
+/// Events are decoded on the poll thread before the SDK frees the message,
+/// so they can be cloned and handed to any number of subscribers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Event {
+  LoginStateChanged {
+    account_handle: String,
+    state: LoginState,
+    status: VivoxError,
+  },
+  MediaStreamUpdated {
+    sessiongroup_handle: String,
+    session_handle: String,
+    state: MediaState,
+    status: VivoxError,
+    incoming: bool,
+  },
+  TextStreamUpdated {
+    sessiongroup_handle: String,
+    session_handle: String,
+    state: TextState,
+    status: VivoxError,
+    incoming: bool,
+  },
+  ParticipantAdded {
+    sessiongroup_handle: String,
+    session_handle: String,
+    participant_uri: String,
+    display_name: String,
+    is_current_user: bool,
+  },
+  ParticipantUpdated {
+    sessiongroup_handle: String,
+    session_handle: String,
+    participant_uri: String,
+    is_speaking: bool,
+    energy: f64,
+    volume: i32,
+    is_muted_for_me: bool,
+    is_moderator_muted: bool,
+  },
+  ParticipantRemoved {
+    sessiongroup_handle: String,
+    session_handle: String,
+    participant_uri: String,
+    reason: RemovalReason,
+  },
+  TypingChanged {
+    session_handle: String,
+    participant_uri: String,
+    is_typing: bool,
+  },
+  SessionRemoved {
+    sessiongroup_handle: String,
+    session_handle: String,
+    uri: String,
+  },
+  SessionGroupRemoved {
+    sessiongroup_handle: String,
+  },
+  AudioDeviceHotSwap {
+    change: DeviceChange,
+  },
+  MessageReceived {
+    sessiongroup_handle: String,
+    message: ChannelMessage,
+  },
+  UserMessageReceived {
+    account_handle: String,
+    message: UserMessage,
+  },
+  UserMessageFailed {
+    account_handle: String,
+    message_id: String,
+    status: VivoxError,
+  },
+  SessionArchiveMessage {
+    session_handle: String,
+    query_id: String,
+    message: ArchivedMessage,
+  },
+  SessionArchiveQueryEnd {
+    session_handle: String,
+    query_id: String,
+    status: VivoxError,
+  },
+  AccountArchiveMessage {
+    account_handle: String,
+    query_id: String,
+    message: ArchivedMessage,
+  },
+  AccountArchiveQueryEnd {
+    account_handle: String,
+    query_id: String,
+    status: VivoxError,
+  },
+  /// A buddy was added or renamed
+  BuddyChanged {
+    account_handle: String,
+    uri: String,
+    display_name: String,
+  },
+  BuddyRemoved {
+    account_handle: String,
+    uri: String,
+  },
+  BuddyPresence {
+    account_handle: String,
+    uri: String,
+    presence: Presence,
+  },
+  SubscriptionRequested {
+    account_handle: String,
+    request: SubscriptionRequest,
+  },
+}
+
+/// The handle an event should be delivered to.
+pub(crate) enum Route<'a> {
+  Account(&'a str),
+  Session(&'a str),
+  SessionGroup(&'a str),
+  /// Not tied to any account, only sent to `VivoxClient::events`
+  Global,
+}
+
+impl Event {
+  pub(crate) fn route(&self) -> Route<'_> {
+    match self {
+      Event::LoginStateChanged { account_handle, .. } => Route::Account(account_handle),
+      Event::MediaStreamUpdated { session_handle, .. } => Route::Session(session_handle),
+      Event::TextStreamUpdated { session_handle, .. } => Route::Session(session_handle),
+      Event::ParticipantAdded { session_handle, .. } => Route::Session(session_handle),
+      Event::ParticipantUpdated { session_handle, .. } => Route::Session(session_handle),
+      Event::ParticipantRemoved { session_handle, .. } => Route::Session(session_handle),
+      Event::TypingChanged { session_handle, .. } => Route::Session(session_handle),
+      Event::SessionRemoved { session_handle, .. } => Route::Session(session_handle),
+      Event::SessionGroupRemoved { sessiongroup_handle } => Route::SessionGroup(sessiongroup_handle),
+      Event::AudioDeviceHotSwap { .. } => Route::Global,
+      Event::MessageReceived { message, .. } => Route::Session(&message.session_handle),
+      Event::UserMessageReceived { account_handle, .. } => Route::Account(account_handle),
+      Event::UserMessageFailed { account_handle, .. } => Route::Account(account_handle),
+      Event::SessionArchiveMessage { session_handle, .. } => Route::Session(session_handle),
+      Event::SessionArchiveQueryEnd { session_handle, .. } => Route::Session(session_handle),
+      Event::AccountArchiveMessage { account_handle, .. } => Route::Account(account_handle),
+      Event::AccountArchiveQueryEnd { account_handle, .. } => Route::Account(account_handle),
+      Event::BuddyChanged { account_handle, .. } => Route::Account(account_handle),
+      Event::BuddyRemoved { account_handle, .. } => Route::Account(account_handle),
+      Event::BuddyPresence { account_handle, .. } => Route::Account(account_handle),
+      Event::SubscriptionRequested { account_handle, .. } => Route::Account(account_handle),
+    }
+  }
+
+  /// Decodes a raw SDK event. Returns `None` for event types the wrapper
+  /// does not handle yet.
+  pub(crate) fn decode(evt: *mut vx_evt_base_t) -> Option<Self> {
+    unsafe {
+      // When the SDK raised the event, not when it was polled
+      let received_at = SystemTime::UNIX_EPOCH + Duration::from_millis((*evt).message.create_time_ms);
+
+      match (*evt).type_ {
+        vx_event_type_evt_account_login_state_change => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_account_login_state_change>(evt);
+
+          Some(Event::LoginStateChanged {
+            account_handle: to_owned_string((*evt).account_handle),
+            state: LoginState::from_raw((*evt).state),
+            status: VivoxError::from_code((*evt).status_code),
+          })
+        },
+        vx_event_type_evt_media_stream_updated => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_media_stream_updated>(evt);
+
+          Some(Event::MediaStreamUpdated {
+            sessiongroup_handle: to_owned_string((*evt).sessiongroup_handle),
+            session_handle: to_owned_string((*evt).session_handle),
+            state: MediaState::from_raw((*evt).state),
+            status: VivoxError::from_code((*evt).status_code),
+            incoming: (*evt).incoming != 0,
+          })
+        },
+        vx_event_type_evt_text_stream_updated => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_text_stream_updated>(evt);
+
+          Some(Event::TextStreamUpdated {
+            sessiongroup_handle: to_owned_string((*evt).sessiongroup_handle),
+            session_handle: to_owned_string((*evt).session_handle),
+            state: TextState::from_raw((*evt).state),
+            status: VivoxError::from_code((*evt).status_code),
+            incoming: (*evt).incoming != 0,
+          })
+        },
+        vx_event_type_evt_participant_added => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_participant_added>(evt);
+
+          Some(Event::ParticipantAdded {
+            sessiongroup_handle: to_owned_string((*evt).sessiongroup_handle),
+            session_handle: to_owned_string((*evt).session_handle),
+            participant_uri: to_owned_string((*evt).participant_uri),
+            display_name: to_owned_string((*evt).displayname),
+            is_current_user: (*evt).is_current_user != 0,
+          })
+        },
+        vx_event_type_evt_participant_updated => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_participant_updated>(evt);
+
+          Some(Event::ParticipantUpdated {
+            sessiongroup_handle: to_owned_string((*evt).sessiongroup_handle),
+            session_handle: to_owned_string((*evt).session_handle),
+            participant_uri: to_owned_string((*evt).participant_uri),
+            is_speaking: (*evt).is_speaking != 0,
+            energy: (*evt).energy,
+            volume: (*evt).volume,
+            is_muted_for_me: (*evt).is_muted_for_me != 0,
+            is_moderator_muted: (*evt).is_moderator_muted != 0,
+          })
+        },
+        vx_event_type_evt_participant_removed => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_participant_removed>(evt);
+
+          Some(Event::ParticipantRemoved {
+            sessiongroup_handle: to_owned_string((*evt).sessiongroup_handle),
+            session_handle: to_owned_string((*evt).session_handle),
+            participant_uri: to_owned_string((*evt).participant_uri),
+            reason: RemovalReason::from_raw((*evt).reason),
+          })
+        },
+        vx_event_type_evt_session_notification => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_session_notification>(evt);
+          let is_typing = match (*evt).notification_type {
+            vx_notification_type_notification_typing => true,
+            vx_notification_type_notification_not_typing => false,
+            // Hand raising is not surfaced yet
+            _ => return None,
+          };
+
+          Some(Event::TypingChanged {
+            session_handle: to_owned_string((*evt).session_handle),
+            participant_uri: to_owned_string((*evt).participant_uri),
+            is_typing,
+          })
+        },
+        vx_event_type_evt_session_removed => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_session_removed>(evt);
+
+          Some(Event::SessionRemoved {
+            sessiongroup_handle: to_owned_string((*evt).sessiongroup_handle),
+            session_handle: to_owned_string((*evt).session_handle),
+            uri: to_owned_string((*evt).uri),
+          })
+        },
+        vx_event_type_evt_sessiongroup_removed => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_sessiongroup_removed>(evt);
+
+          Some(Event::SessionGroupRemoved {
+            sessiongroup_handle: to_owned_string((*evt).sessiongroup_handle),
+          })
+        },
+        vx_event_type_evt_message => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_message>(evt);
+
+          Some(Event::MessageReceived {
+            sessiongroup_handle: to_owned_string((*evt).sessiongroup_handle),
+            message: ChannelMessage {
+              session_handle: to_owned_string((*evt).session_handle),
+              sender_uri: to_owned_string((*evt).participant_uri),
+              sender_display_name: to_owned_string((*evt).participant_displayname),
+              body: to_owned_string((*evt).message_body),
+              language: messages::non_empty(to_owned_string((*evt).language)),
+              custom_metadata: messages::custom_metadata(
+                to_owned_string((*evt).application_stanza_namespace),
+                to_owned_string((*evt).application_stanza_body),
+              ),
+              received_at,
+            },
+          })
+        },
+        vx_event_type_evt_user_to_user_message => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_user_to_user_message>(evt);
+
+          Some(Event::UserMessageReceived {
+            account_handle: to_owned_string((*evt).account_handle),
+            message: UserMessage {
+              sender: UserUri::parse(&to_owned_string((*evt).from_uri)).ok(),
+              sender_uri: to_owned_string((*evt).from_uri),
+              sender_display_name: to_owned_string((*evt).from_displayname),
+              body: to_owned_string((*evt).message_body),
+              language: messages::non_empty(to_owned_string((*evt).language)),
+              custom_metadata: messages::custom_metadata(
+                to_owned_string((*evt).application_stanza_namespace),
+                to_owned_string((*evt).application_stanza_body),
+              ),
+              received_at,
+            },
+          })
+        },
+        vx_event_type_evt_account_send_message_failed => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_account_send_message_failed>(evt);
+
+          Some(Event::UserMessageFailed {
+            account_handle: to_owned_string((*evt).account_handle),
+            message_id: to_owned_string((*evt).request_id),
+            status: VivoxError::from_code((*evt).status_code),
+          })
+        },
+        vx_event_type_evt_session_archive_message => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_session_archive_message>(evt);
+
+          Some(Event::SessionArchiveMessage {
+            session_handle: to_owned_string((*evt).session_handle),
+            query_id: to_owned_string((*evt).query_id),
+            message: ArchivedMessage {
+              id: to_owned_string((*evt).message_id),
+              timestamp: to_owned_string((*evt).time_stamp),
+              channel_uri: String::new(),
+              sender_uri: to_owned_string((*evt).participant_uri),
+              body: to_owned_string((*evt).message_body),
+              language: messages::non_empty(to_owned_string((*evt).language)),
+              is_inbound: true,
+            },
+          })
+        },
+        vx_event_type_evt_session_archive_query_end => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_session_archive_query_end>(evt);
+
+          Some(Event::SessionArchiveQueryEnd {
+            session_handle: to_owned_string((*evt).session_handle),
+            query_id: to_owned_string((*evt).query_id),
+            status: query_status((*evt).return_code, (*evt).status_code),
+          })
+        },
+        vx_event_type_evt_account_archive_message => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_account_archive_message>(evt);
+
+          Some(Event::AccountArchiveMessage {
+            account_handle: to_owned_string((*evt).account_handle),
+            query_id: to_owned_string((*evt).query_id),
+            message: ArchivedMessage {
+              id: to_owned_string((*evt).message_id),
+              timestamp: to_owned_string((*evt).time_stamp),
+              channel_uri: to_owned_string((*evt).channel_uri),
+              sender_uri: to_owned_string((*evt).participant_uri),
+              body: to_owned_string((*evt).message_body),
+              language: messages::non_empty(to_owned_string((*evt).language)),
+              is_inbound: (*evt).is_inbound != 0,
+            },
+          })
+        },
+        vx_event_type_evt_account_archive_query_end => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_account_archive_query_end>(evt);
+
+          Some(Event::AccountArchiveQueryEnd {
+            account_handle: to_owned_string((*evt).account_handle),
+            query_id: to_owned_string((*evt).query_id),
+            status: query_status((*evt).return_code, (*evt).status_code),
+          })
+        },
+        vx_event_type_evt_buddy_changed => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_buddy_changed>(evt);
+          let account_handle = to_owned_string((*evt).account_handle);
+          let uri = to_owned_string((*evt).buddy_uri);
+
+          match (*evt).change_type {
+            vx_change_type_t_change_type_delete => Some(Event::BuddyRemoved { account_handle, uri }),
+            _ => Some(Event::BuddyChanged {
+              account_handle,
+              uri,
+              display_name: to_owned_string((*evt).display_name),
+            }),
+          }
+        },
+        vx_event_type_evt_buddy_presence => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_buddy_presence>(evt);
+
+          Some(Event::BuddyPresence {
+            account_handle: to_owned_string((*evt).account_handle),
+            uri: to_owned_string((*evt).buddy_uri),
+            presence: Presence {
+              status: PresenceStatus::from_raw((*evt).presence),
+              message: to_owned_string((*evt).custom_message),
+            },
+          })
+        },
+        vx_event_type_evt_subscription => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_subscription>(evt);
+
+          Some(Event::SubscriptionRequested {
+            account_handle: to_owned_string((*evt).account_handle),
+            request: SubscriptionRequest {
+              from: UserUri::parse(&to_owned_string((*evt).buddy_uri)).ok(),
+              from_uri: to_owned_string((*evt).buddy_uri),
+              display_name: to_owned_string((*evt).displayname),
+              message: to_owned_string((*evt).message),
+              subscription_handle: to_owned_string((*evt).subscription_handle),
+            },
+          })
+        },
+        vx_event_type_evt_audio_device_hot_swap => {
+          let evt = transmute::<*mut vx_evt_base_t, *mut vx_evt_audio_device_hot_swap>(evt);
+
+          Some(Event::AudioDeviceHotSwap {
+            change: devices::decode_hot_swap(evt)?,
+          })
+        },
+        _ => None,
+      }
+    }
+  }
+}
+
+fn query_status(return_code: i32, status_code: i32) -> VivoxError {
+  match return_code {
+    1 => VivoxError::from_code(status_code),
+    _ => VivoxError::SUCCESS,
+  }
+}
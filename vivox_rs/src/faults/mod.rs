@@ -0,0 +1,355 @@
+//! Scripted faults for testing how a game copes with lost connections,
+//! dropped calls and failing requests.
+//!
+//! `Faults` wraps the backend a `VivoxClient` runs on. Tests script it at
+//! any point of a scenario and then look at what the client did about it,
+//! both through the client's own API and through the requests and events
+//! logged here.
+//!
+//! Dropped calls and connections are passed on to the wrapped backend when
+//! it can simulate them, as `FfiBackend` does over the mock SDK, so the
+//! service behind it forgets the session or account as well. Otherwise the
+//! events the SDK would send are made up here.
+
+use crate::backend::{Message, Request, VivoxBackend};
+use crate::client::InitError;
+use crate::enums::{LoginState, MediaState, TextState, VivoxError};
+use crate::events::Event;
+use crate::version::SdkVersion;
+use crate::helpers::*;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use vivox_rs_sys::*;
+
+/// Shared script of a `FaultyBackend`. Clones script the same backend.
+#[derive(Clone, Default)]
+pub struct Faults {
+  state: Arc<Mutex<Script>>,
+}
+
+#[derive(Default)]
+struct Script {
+  /// Answers for the next requests of a type, used in order
+  rules: VecDeque<(vx_request_type, Rule)>,
+  /// Messages delivered ahead of the wrapped backend's
+  injected: VecDeque<Message>,
+  /// Outages applied on the next poll, where the wrapped backend is at hand
+  outages: VecDeque<Outage>,
+  issued: Vec<vx_request_type>,
+  delivered: Vec<Event>,
+  /// Handles of the accounts currently logged in
+  accounts: Vec<String>,
+  /// Sessions being joined and their handle, by request cookie
+  joining: HashMap<String, (String, Joined)>,
+  /// Sessions joined, by session handle
+  sessions: BTreeMap<String, Joined>,
+}
+
+enum Rule {
+  Fail(VivoxError),
+  Ignore,
+}
+
+enum Outage {
+  Session(String, VivoxError),
+  Connection(VivoxError),
+}
+
+#[derive(Clone)]
+struct Joined {
+  account_handle: String,
+  sessiongroup_handle: String,
+  uri: String,
+}
+
+/// Passes everything on to `inner` unless `Faults` says otherwise.
+pub struct FaultyBackend<B> {
+  inner: B,
+  faults: Faults,
+}
+
+impl Faults {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn wrap<B: VivoxBackend>(&self, inner: B) -> FaultyBackend<B> {
+    FaultyBackend {
+      inner,
+      faults: self.clone(),
+    }
+  }
+
+  /// Fails the next request of this type with `status` instead of issuing
+  /// it. Rules queue up and are used in order.
+  pub fn fail_next(&self, request_type: vx_request_type, status: VivoxError) {
+    self.state.lock().unwrap().rules.push_back((request_type, Rule::Fail(status)));
+  }
+
+  /// Swallows the next request of this type, it is never answered.
+  pub fn ignore_next(&self, request_type: vx_request_type) {
+    self.state.lock().unwrap().rules.push_back((request_type, Rule::Ignore));
+  }
+
+  /// Delivers an arbitrary event on the next poll.
+  pub fn inject(&self, event: Event) {
+    self.state.lock().unwrap().injected.push_back(Message::Event(event, None));
+  }
+
+  /// The call ends without being asked to, e.g. with
+  /// `CALL_TERMINATED_BY_SERVER` or `CALL_TERMINATED_NO_RTP_RXED`.
+  /// Returns false for sessions not joined through this backend.
+  pub fn terminate_session(&self, session_handle: &str, status: VivoxError) -> bool {
+    let mut state = self.state.lock().unwrap();
+
+    if !state.sessions.contains_key(session_handle) {
+      return false;
+    }
+
+    state.outages.push_back(Outage::Session(session_handle.to_string(), status));
+
+    true
+  }
+
+  /// Every account loses its connection, e.g. with `NETWORK_DOWN` or
+  /// `POWER_STATE_CHANGE`. Their sessions end and they are logged out.
+  ///
+  /// Nothing logs them in again, that is up to the game.
+  pub fn lose_connection(&self, status: VivoxError) {
+    self.state.lock().unwrap().outages.push_back(Outage::Connection(status));
+  }
+
+  /// Types of the requests the client issued, oldest first, including the
+  /// ones failed or swallowed here.
+  pub fn issued(&self) -> Vec<vx_request_type> {
+    self.state.lock().unwrap().issued.clone()
+  }
+
+  /// Events delivered to the client, oldest first.
+  pub fn delivered(&self) -> Vec<Event> {
+    self.state.lock().unwrap().delivered.clone()
+  }
+
+  /// Forgets the logged requests and events, typically right before a
+  /// fault to only see the reaction to it.
+  pub fn clear_log(&self) {
+    let mut state = self.state.lock().unwrap();
+
+    state.issued.clear();
+    state.delivered.clear();
+  }
+}
+
+impl Script {
+  /// Queues what the SDK reports when a call drops.
+  fn end_session(&mut self, session_handle: &str, session: &Joined, status: VivoxError) {
+    let sessiongroup_handle = &session.sessiongroup_handle;
+    let group_emptied = !self.sessions
+      .iter()
+      .any(|(handle, other)| handle != session_handle && other.sessiongroup_handle == *sessiongroup_handle);
+
+    let mut events = vec![
+      Event::MediaStreamUpdated {
+        sessiongroup_handle: sessiongroup_handle.clone(),
+        session_handle: session_handle.to_string(),
+        state: MediaState::Disconnected,
+        status,
+        incoming: false,
+      },
+      Event::TextStreamUpdated {
+        sessiongroup_handle: sessiongroup_handle.clone(),
+        session_handle: session_handle.to_string(),
+        state: TextState::Disconnected,
+        status,
+        incoming: false,
+      },
+      Event::SessionRemoved {
+        sessiongroup_handle: sessiongroup_handle.clone(),
+        session_handle: session_handle.to_string(),
+        uri: session.uri.clone(),
+      },
+    ];
+
+    if group_emptied {
+      events.push(Event::SessionGroupRemoved {
+        sessiongroup_handle: sessiongroup_handle.clone(),
+      });
+    }
+
+    self.injected.extend(events.into_iter().map(|evt| Message::Event(evt, None)));
+  }
+
+  fn observe(&mut self, msg: &Message) {
+    match msg {
+      Message::Response { cookie, response } => {
+        if let Some((handle, session)) = self.joining.remove(cookie) {
+          if response.is_ok() {
+            self.sessions.insert(handle, session);
+          }
+        }
+      },
+      Message::Event(evt, _) => {
+        match evt {
+          Event::LoginStateChanged { account_handle, state: LoginState::LoggedIn, .. } => {
+            self.accounts.push(account_handle.clone());
+          },
+          Event::LoginStateChanged { account_handle, state: LoginState::LoggedOut, .. } => {
+            self.accounts.retain(|handle| handle != account_handle);
+            self.sessions.retain(|_, session| session.account_handle != *account_handle);
+          },
+          Event::SessionRemoved { session_handle, .. } => {
+            self.sessions.remove(session_handle);
+          },
+          _ => {},
+        }
+
+        self.delivered.push(evt.clone());
+      },
+    }
+  }
+}
+
+impl<B: VivoxBackend> VivoxBackend for FaultyBackend<B> {
+  fn initialize(&self) -> Result<(), InitError> {
+    self.inner.initialize()
+  }
+
+  fn uninitialize(&self) {
+    self.inner.uninitialize()
+  }
+
+  fn issue(&self, req: Request) -> Result<(), VivoxError> {
+    let kind = req.kind();
+    let cookie = req.cookie();
+
+    let rule = {
+      let mut state = self.faults.state.lock().unwrap();
+
+      state.issued.push(kind);
+
+      if kind == vx_request_type_req_sessiongroup_add_session {
+        state.joining.insert(cookie.clone(), unsafe { joining(&req) });
+      }
+
+      match state.rules.iter().position(|(request_type, _)| *request_type == kind) {
+        Some(i) => state.rules.remove(i).map(|(_, rule)| rule),
+        None => None,
+      }
+    };
+
+    match rule {
+      Some(Rule::Fail(status)) => {
+        self.faults.state.lock().unwrap().injected.push_back(Message::Response {
+          cookie,
+          response: Err(status),
+        });
+
+        Ok(())
+      },
+      Some(Rule::Ignore) => Ok(()),
+      None => self.inner.issue(req),
+    }
+  }
+
+  fn poll(&self) -> Option<Message> {
+    self.apply_outages();
+
+    let injected = self.faults.state.lock().unwrap().injected.pop_front();
+    let msg = injected.or_else(|| self.inner.poll())?;
+
+    self.faults.state.lock().unwrap().observe(&msg);
+
+    Some(msg)
+  }
+
+  fn sdk_version(&self) -> Option<SdkVersion> {
+    self.inner.sdk_version()
+  }
+
+  fn drop_session(&self, session_handle: &str, status: VivoxError) -> bool {
+    self.inner.drop_session(session_handle, status)
+  }
+
+  fn drop_account(&self, account_handle: &str, status: VivoxError) -> bool {
+    self.inner.drop_account(account_handle, status)
+  }
+}
+
+impl<B: VivoxBackend> FaultyBackend<B> {
+  fn apply_outages(&self) {
+    let outages: Vec<Outage> = self.faults.state.lock().unwrap().outages.drain(..).collect();
+
+    for outage in outages {
+      match outage {
+        Outage::Session(handle, status) => self.end_session(&handle, status),
+        Outage::Connection(status) => self.end_connection(status),
+      }
+    }
+  }
+
+  fn end_session(&self, session_handle: &str, status: VivoxError) {
+    // Gone already when a second fault hits it before its removal events
+    // are delivered
+    let session = match self.faults.state.lock().unwrap().sessions.get(session_handle).cloned() {
+      Some(session) => session,
+      None => return,
+    };
+
+    let dropped = self.inner.drop_session(session_handle, status);
+    let mut state = self.faults.state.lock().unwrap();
+
+    if !dropped {
+      state.end_session(session_handle, &session, status);
+    }
+
+    state.sessions.remove(session_handle);
+  }
+
+  fn end_connection(&self, status: VivoxError) {
+    let (accounts, sessions) = {
+      let state = self.faults.state.lock().unwrap();
+
+      (state.accounts.clone(), state.sessions.clone())
+    };
+
+    let dropped: Vec<String> = accounts
+      .iter()
+      .filter(|handle| self.inner.drop_account(handle, status))
+      .cloned()
+      .collect();
+
+    for (handle, session) in sessions {
+      if dropped.contains(&session.account_handle) {
+        self.faults.state.lock().unwrap().sessions.remove(&handle);
+      }
+      else {
+        self.end_session(&handle, status);
+      }
+    }
+
+    let mut state = self.faults.state.lock().unwrap();
+
+    for account_handle in accounts.into_iter().filter(|handle| !dropped.contains(handle)) {
+      state.injected.push_back(Message::Event(Event::LoginStateChanged {
+        account_handle,
+        state: LoginState::LoggedOut,
+        status,
+      }, None));
+    }
+  }
+}
+
+/// Reads the session an add session request is about to join.
+unsafe fn joining(req: &Request) -> (String, Joined) {
+  let req = req.as_ptr() as *const vx_req_sessiongroup_add_session;
+
+  (
+    to_owned_string((*req).session_handle),
+    Joined {
+      account_handle: to_owned_string((*req).account_handle),
+      sessiongroup_handle: to_owned_string((*req).sessiongroup_handle),
+      uri: to_owned_string((*req).uri),
+    },
+  )
+}
@@ -1,7 +1,12 @@
-use std::ffi::CString;
+use crate::enums::VivoxError;
+
+use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
+use std::time::SystemTime;
 use vivox_rs_sys::*;
 
+pub(crate) use vivox_rs_common::uri::is_valid_non_alphanumeric;
+
 pub(crate) fn strdup(input: &str) -> *mut c_char {
   unsafe {
     vx_strdup(CString::new(input)
@@ -11,6 +16,32 @@ pub(crate) fn strdup(input: &str) -> *mut c_char {
   }
 }
 
-pub(crate) fn is_valid_non_alphanumeric(x: &char) -> bool {
-  "-_.!~*'()&=+$,;?/".chars().any(|y| y == *x)
+/// Copies an SDK owned string. Null pointers become an empty string.
+pub(crate) fn to_owned_string(input: *const c_char) -> String {
+  if input.is_null() {
+    return String::new();
+  }
+
+  unsafe { CStr::from_ptr(input).to_string_lossy().into_owned() }
+}
+
+/// Expiry for access tokens minted right before issuing a request.
+pub(crate) fn token_expiry() -> u64 {
+  SystemTime::now()
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .expect("Back from the future")
+    .as_secs() + 120
+}
+
+/// SDK volumes run from 0 to 100, with 50 leaving the level unchanged.
+pub(crate) fn check_volume(volume: i32) -> Result<(), VivoxError> {
+  match volume {
+    0..=100 => Ok(()),
+    _ => Err(VivoxError::INVALID_ARGUMENT),
+  }
+}
+
+/// The SDK's explanation of a status code.
+pub(crate) fn error_string(code: i32) -> String {
+  to_owned_string(unsafe { vx_get_error_string(code) })
 }
@@ -1,17 +1,560 @@
-use std::io::{self, Read};
+//! Interactive shell for trying the wrapper out against the Vivox dev
+//! servers. Events are printed as they arrive, between prompts.
+//!
+//! Access tokens are signed with the key of the dev issuer, read from
+//! `VIVOX_TOKEN_KEY` since it must not be part of the source.
 
-use vivox_rs::hello_vivox;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, ExternalPrinter, Helper};
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, mpsc};
+use vivox_rs::account::Account;
+use vivox_rs::client::VivoxClient;
+use vivox_rs::connector::Connector;
+use vivox_rs::devices::DeviceList;
+use vivox_rs::enums::VivoxError;
+use vivox_rs::events::Event;
+use vivox_rs::session::Session;
+use vivox_rs::sessiongroup::{SessionGroup, Transmit};
+use vivox_rs::tokengen::TokenGenerator;
+use vivox_rs::uri::{is_valid_non_alphanumeric, UserUri};
+
+const SERVER: &str = "https://vdx5.www.vivox.com/api2";
+const ISSUER: &str = "gmclvivox-gmvivox-w-dev";
+const DOMAIN: &str = "vdx5.vivox.com";
+
+/// Name, arguments and description of every command.
+const COMMANDS: &[(&str, &str, &str)] = &[
+  ("connect", "[server]", "create a connector, to the dev server by default"),
+  ("login", "<user>", "log in anonymously, connecting first if needed"),
+  ("join", "<channel>", "join a channel by name or sip: URI"),
+  ("leave", "[session]", "leave a session, the current one by default"),
+  ("say", "<text>", "post to the current session"),
+  ("mute", "<user>", "mute or unmute someone for yourself"),
+  ("devices", "", "list capture and render devices"),
+  ("tx", "<session|all|none>", "pick where the microphone is transmitted"),
+  ("roster", "", "list who is in the current session"),
+  ("status", "", "show the SDK, account and sessions"),
+  ("help", "", "show this list"),
+  ("quit", "", "shut down and exit"),
+];
+
+const TOKEN_KEY: &str = "VIVOX_TOKEN_KEY";
+
+static TOKENS: TokenGenerator = TokenGenerator::init();
+
+type Outcome = Result<(), Box<dyn Error>>;
+
+/// Sessions the shell joined, shared with tab completion.
+#[derive(Default)]
+struct Joined {
+  group: Option<SessionGroup>,
+  /// Where `say`, `mute` and `roster` go, the last session joined
+  current: Option<Session>,
+}
+
+struct Shell {
+  client: VivoxClient,
+  connector: Option<Connector>,
+  account: Option<Account>,
+  joined: Arc<Mutex<Joined>>,
+  /// Signing key of the dev issuer, if one was given
+  key: Option<String>,
+}
+
+impl Shell {
+  async fn run(&mut self, line: &str) -> Outcome {
+    let (command, arg) = match line.find(' ') {
+      Some(i) => (&line[..i], line[i + 1..].trim()),
+      None => (line, ""),
+    };
+
+    match (command, arg) {
+      ("connect", "") => self.connect(SERVER).await,
+      ("connect", server) => self.connect(server).await,
+      ("login", user) if !user.is_empty() => self.login(user).await,
+      ("join", channel) if !channel.is_empty() => self.join(channel).await,
+      ("leave", handle) => self.leave(handle).await,
+      ("say", text) if !text.is_empty() => self.current()?.send_message(text, None, None).await.map_err(Into::into),
+      ("mute", user) if !user.is_empty() => self.mute(user).await,
+      ("devices", "") => self.devices().await,
+      ("tx", target) if !target.is_empty() => self.transmit(target).await,
+      ("roster", "") => self.roster(),
+      ("status", "") => self.status(),
+      ("help", _) => {
+        for (name, args, description) in COMMANDS {
+          println!("  {:<28} {}", format!("{} {}", name, args), description);
+        }
+
+        Ok(())
+      },
+      _ => match COMMANDS.iter().find(|(name, _, _)| *name == command) {
+        Some((name, args, _)) => Err(format!("usage: {} {}", name, args).into()),
+        None => Err(format!("unknown command {}, try help", command).into()),
+      },
+    }
+  }
+
+  async fn connect(&mut self, server: &str) -> Outcome {
+    if let Some(connector) = &self.connector {
+      return Err(format!("already connected as {}", connector.handle()).into());
+    }
+
+    let connector = self.client.create_connector(server).await?;
+    println!("Connected to {} as {}", server, connector.handle());
+
+    self.connector = Some(connector);
+
+    Ok(())
+  }
+
+  async fn login(&mut self, user: &str) -> Outcome {
+    if let Some(account) = &self.account {
+      return Err(format!("already logged in as {}", account.uri()).into());
+    }
+
+    if self.connector.is_none() {
+      self.connect(SERVER).await?;
+    }
+
+    let name = format!(".{}.{}.", ISSUER, user);
+    let token = self.token("login", &UserUri::new(&name, DOMAIN).to_string(), None)?;
+    let connector = self.connector.as_ref().unwrap();
+    let account = connector.login(
+      connector
+        .anonymous_login(&name)
+        .displayname(user)
+        .signed_access_token(DOMAIN, &token)
+    ).await?;
+    println!("Logged in as {}", account.uri());
+
+    self.account = Some(account);
+
+    Ok(())
+  }
+
+  async fn join(&mut self, channel: &str) -> Outcome {
+    let account = self.account.as_ref().ok_or("not logged in, use login first")?;
+    let uri = channel_uri(channel)?;
+    let token = self.token("join", account.uri(), Some(uri.clone()))?;
+
+    // The SDK removes a group along with its last session
+    let group = match &self.joined.lock().unwrap().group {
+      Some(group) if !group.sessions().is_empty() => group.clone(),
+      _ => account.create_sessiongroup(),
+    };
+    let session = group.join(
+      group
+        .add_session()
+        .uri(&uri)?
+        .connect_audio(1)
+        .connect_text(1)
+        .signed_access_token(&token)
+    ).await?;
+    println!("Joined {} as {}", uri, session.handle());
+
+    let mut joined = self.joined.lock().unwrap();
+    joined.group = Some(group);
+    joined.current = Some(session);
+
+    Ok(())
+  }
+
+  /// Signs a token for `action` by `from`, valid for two minutes.
+  fn token(&self, action: &str, from: &str, to: Option<String>) -> Result<String, Box<dyn Error>> {
+    let key = self.key
+      .as_ref()
+      .ok_or_else(|| format!("set {} to the signing key of {} to get access tokens", TOKEN_KEY, ISSUER))?;
+    let expiry = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)? + Duration::from_secs(120);
+
+    Ok(TOKENS.generate(key, ISSUER, expiry.as_secs(), action, from, to))
+  }
+
+  async fn leave(&mut self, handle: &str) -> Outcome {
+    let session = self.session(handle)?;
+    let group = self.joined.lock().unwrap().group.clone().ok_or("not in a channel")?;
+
+    group.remove_session(&session).await?;
+    println!("Left {}", session.uri());
+
+    let mut joined = self.joined.lock().unwrap();
+
+    if joined.current.as_ref().map(Session::handle) == Some(session.handle()) {
+      joined.current = group.sessions()
+        .into_iter()
+        .find(|other| other.handle() != session.handle());
+    }
+
+    Ok(())
+  }
+
+  async fn mute(&self, user: &str) -> Outcome {
+    let session = self.current()?;
+    let participant = session.roster()
+      .into_iter()
+      .find(|participant| participant.uri == user || user_name(&participant.uri) == user)
+      .ok_or_else(|| format!("{} is not in {}", user, session.uri()))?;
+    let mute = !participant.is_muted_for_me;
+
+    session.set_participant_mute_for_me(&participant.uri, mute).await?;
+    println!("{} {}", if mute { "Muted" } else { "Unmuted" }, participant.display_name);
+
+    Ok(())
+  }
+
+  async fn devices(&self) -> Outcome {
+    let devices = self.client.audio_devices();
+
+    print_devices("Capture", &devices.capture_devices().await?);
+    print_devices("Render", &devices.render_devices().await?);
+
+    Ok(())
+  }
+
+  async fn transmit(&self, target: &str) -> Outcome {
+    let group = self.joined.lock().unwrap().group.clone().ok_or("not in a channel")?;
+    let target = match target {
+      "all" => Transmit::All,
+      "none" => Transmit::None,
+      handle => Transmit::Session(self.session(handle)?.handle().to_string()),
+    };
+
+    group.transmit_to(target.clone()).await?;
+    println!("Transmitting to {}", describe_target(&target));
+
+    Ok(())
+  }
+
+  fn roster(&self) -> Outcome {
+    let session = self.current()?;
+
+    for participant in session.roster() {
+      let mut flags = Vec::new();
+
+      if participant.is_current_user {
+        flags.push("you");
+      }
+      if participant.is_speaking {
+        flags.push("speaking");
+      }
+      if participant.is_muted_for_me {
+        flags.push("muted");
+      }
+      if participant.is_moderator_muted {
+        flags.push("moderator muted");
+      }
+
+      println!("  {:<20} {} {}", participant.display_name, participant.uri, flags.join(", "));
+    }
+
+    Ok(())
+  }
+
+  fn status(&self) -> Outcome {
+    match self.client.sdk_version() {
+      Some(version) => println!("SDK        {}", version),
+      None => println!("SDK        unknown"),
+    }
+
+    println!("Connector  {}", self.connector.as_ref().map(Connector::handle).unwrap_or("none"));
+    println!("Account    {}", self.account.as_ref().map(Account::uri).unwrap_or("none"));
+
+    let joined = self.joined.lock().unwrap();
+
+    if let Some(group) = &joined.group {
+      let mut sessions = group.sessions();
+      sessions.sort_by(|a, b| a.handle().cmp(b.handle()));
+
+      println!("Transmit   {}", describe_target(&group.transmitting()));
+
+      for session in sessions {
+        let state = *session.state().borrow();
+        let current = joined.current.as_ref().map(Session::handle) == Some(session.handle());
+
+        println!(
+          "  {} {:<6} {} audio {:?}, text {:?}",
+          if current { '*' } else { ' ' },
+          session.handle(),
+          session.uri(),
+          state.media,
+          state.text,
+        );
+      }
+    }
+
+    println!("Usage      {:?}", self.client.usage());
+
+    Ok(())
+  }
+
+  fn current(&self) -> Result<Session, Box<dyn Error>> {
+    self.joined.lock().unwrap().current.clone().ok_or_else(|| "not in a channel, use join first".into())
+  }
+
+  /// The session with `handle`, or the current one without.
+  fn session(&self, handle: &str) -> Result<Session, Box<dyn Error>> {
+    if handle.is_empty() {
+      return self.current();
+    }
+
+    self.joined.lock().unwrap()
+      .group
+      .as_ref()
+      .and_then(|group| group.session(handle))
+      .ok_or_else(|| format!("no session {}, see status", handle).into())
+  }
+}
+
+/// Completes command names, then users and sessions where they are
+/// expected.
+struct Completions {
+  joined: Arc<Mutex<Joined>>,
+}
+
+impl Completer for Completions {
+  type Candidate = String;
+
+  fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+    let line = &line[..pos];
+    let start = line.rfind(' ').map(|i| i + 1).unwrap_or(0);
+    let joined = self.joined.lock().unwrap();
+    let sessions = || -> Vec<String> {
+      joined.group
+        .iter()
+        .flat_map(|group| group.sessions())
+        .map(|session| session.handle().to_string())
+        .collect()
+    };
+
+    let candidates = match line[..start].split_whitespace().collect::<Vec<_>>().as_slice() {
+      [] => COMMANDS.iter().map(|(name, _, _)| name.to_string()).collect(),
+      ["mute"] => joined.current
+        .iter()
+        .flat_map(Session::roster)
+        .map(|participant| user_name(&participant.uri).to_string())
+        .collect(),
+      ["leave"] => sessions(),
+      ["tx"] => {
+        let mut targets = sessions();
+        targets.extend(vec!["all".to_string(), "none".to_string()]);
+        targets
+      },
+      _ => Vec::new(),
+    };
+
+    Ok((start, candidates.into_iter().filter(|c| c.starts_with(&line[start..])).collect()))
+  }
+}
+
+impl Hinter for Completions {
+  type Hint = String;
+}
+
+impl Highlighter for Completions {}
+
+impl Validator for Completions {}
+
+impl Helper for Completions {}
+
+/// Users logged in through `login` go by the name they were given there.
+fn user_name(uri: &str) -> &str {
+  uri
+    .strip_prefix(format!("sip:.{}.", ISSUER).as_str())
+    .and_then(|rest| rest.find(".@").map(|end| &rest[..end]))
+    .unwrap_or(uri)
+}
+
+/// Names become group channels of the dev issuer.
+fn channel_uri(channel: &str) -> Result<String, Box<dyn Error>> {
+  let uri = match channel.starts_with("sip:") {
+    true => channel.to_string(),
+    false => format!("sip:confctl-g-{}.{}@{}", ISSUER, channel, DOMAIN),
+  };
+
+  let valid = match uri["sip:".len()..].find('@') {
+    Some(end) => {
+      let name = &uri["sip:".len()..][..end];
+
+      !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || is_valid_non_alphanumeric(&c))
+    },
+    None => false,
+  };
+
+  match valid {
+    true => Ok(uri),
+    false => Err(format!("invalid channel {}", channel).into()),
+  }
+}
+
+fn describe_target(target: &Transmit) -> &str {
+  match target {
+    Transmit::All => "every session",
+    Transmit::None => "no session",
+    Transmit::Session(handle) => handle,
+  }
+}
+
+fn print_devices(direction: &str, list: &DeviceList) {
+  println!("{} devices:", direction);
+
+  for device in &list.devices {
+    let current = list.current.as_ref().map(|current| current.id == device.id).unwrap_or(false);
+
+    println!("  {} {} ({})", if current { '*' } else { ' ' }, device.display_name, device.id);
+  }
+}
+
+fn status_suffix(status: VivoxError) -> String {
+  match status {
+    VivoxError::SUCCESS => String::new(),
+    status => format!(" ({})", status),
+  }
+}
+
+/// One line per event worth showing. Participant updates only show when
+/// someone starts or stops speaking, tracked in `speaking`.
+fn describe(evt: &Event, speaking: &mut HashMap<(String, String), bool>) -> Option<String> {
+  let line = match evt {
+    Event::LoginStateChanged { account_handle, state, status } => {
+      format!("[{}] login {:?}{}", account_handle, state, status_suffix(*status))
+    },
+    Event::MediaStreamUpdated { session_handle, state, status, .. } => {
+      format!("[{}] audio {:?}{}", session_handle, state, status_suffix(*status))
+    },
+    Event::TextStreamUpdated { session_handle, state, status, .. } => {
+      format!("[{}] text {:?}{}", session_handle, state, status_suffix(*status))
+    },
+    Event::ParticipantAdded { session_handle, participant_uri, display_name, .. } => {
+      format!("[{}] {} joined as {}", session_handle, participant_uri, display_name)
+    },
+    Event::ParticipantUpdated { session_handle, participant_uri, is_speaking, .. } => {
+      let key = (session_handle.clone(), participant_uri.clone());
+
+      if speaking.insert(key, *is_speaking).unwrap_or(false) == *is_speaking {
+        return None;
+      }
+
+      let change = if *is_speaking { "started" } else { "stopped" };
+      format!("[{}] {} {} speaking", session_handle, participant_uri, change)
+    },
+    Event::ParticipantRemoved { session_handle, participant_uri, reason, .. } => {
+      speaking.remove(&(session_handle.clone(), participant_uri.clone()));
+
+      format!("[{}] {} left ({:?})", session_handle, participant_uri, reason)
+    },
+    Event::MessageReceived { message, .. } => {
+      format!("[{}] {}: {}", message.session_handle, message.sender_display_name, message.body)
+    },
+    Event::UserMessageReceived { account_handle, message } => {
+      format!("[{}] {} whispers: {}", account_handle, message.sender_uri, message.body)
+    },
+    Event::SessionRemoved { session_handle, uri, .. } => {
+      format!("[{}] session to {} ended", session_handle, uri)
+    },
+    Event::AudioDeviceHotSwap { change } => format!("[devices] {:?}", change),
+    evt => format!("{:?}", evt),
+  };
+
+  Some(line)
+}
+
+/// Runs the prompt on its own thread, waiting for each command to finish
+/// before showing it again.
+fn read_lines(
+  mut editor: Editor<Completions>,
+  lines: mpsc::UnboundedSender<String>,
+  done: std_mpsc::Receiver<()>,
+) {
+  loop {
+    match editor.readline("vivox> ") {
+      Ok(line) => {
+        let line = line.trim();
+
+        if line.is_empty() {
+          continue;
+        }
+
+        editor.add_history_entry(line);
+
+        if lines.send(line.to_string()).is_err() || done.recv().is_err() {
+          break;
+        }
+      },
+      // Ctrl-C only clears the line, Ctrl-D exits
+      Err(ReadlineError::Interrupted) => continue,
+      Err(_) => break,
+    }
+  }
+}
 
 #[tokio::main]
-async fn main() -> io::Result<()> {
-  let mut buffer = String::new();
-  let stdin = io::stdin();
-  let mut handle = stdin.lock();
+async fn main() -> Result<(), Box<dyn Error>> {
+  let client = VivoxClient::new()?;
+  let joined = Arc::new(Mutex::new(Joined::default()));
+
+  let mut editor = Editor::<Completions>::new()?;
+  editor.set_helper(Some(Completions { joined: joined.clone() }));
 
+  // Without a terminal there is no prompt to keep intact
+  let mut print: Box<dyn FnMut(String) + Send> = match editor.create_external_printer() {
+    Ok(mut printer) => Box::new(move |line| { let _ = printer.print(line + "\n"); }),
+    Err(_) => Box::new(|line| println!("{}", line)),
+  };
+
+  let mut events = client.events();
   tokio::spawn(async move {
-    hello_vivox();
+    let mut speaking = HashMap::new();
+
+    loop {
+      match events.recv().await {
+        Ok(evt) => {
+          if let Some(line) = describe(&evt, &mut speaking) {
+            print(line);
+          }
+        },
+        Err(broadcast::RecvError::Lagged(_)) => continue,
+        Err(broadcast::RecvError::Closed) => break,
+      }
+    }
   });
 
-  handle.read_to_string(&mut buffer)?;
+  let (lines_tx, mut lines) = mpsc::unbounded_channel();
+  let (done, done_rx) = std_mpsc::channel();
+  thread::spawn(move || read_lines(editor, lines_tx, done_rx));
+
+  let mut shell = Shell {
+    client: client.clone(),
+    connector: None,
+    account: None,
+    joined,
+    key: env::var(TOKEN_KEY).ok(),
+  };
+
+  println!("Type help for a list of commands, tab completes them");
+
+  while let Some(line) = lines.recv().await {
+    if line == "quit" {
+      break;
+    }
+
+    if let Err(e) = shell.run(&line).await {
+      println!("error: {}", e);
+    }
+
+    let _ = done.send(());
+  }
+
+  // Returns once the poll thread uninitialized the SDK
+  client.shutdown();
+
   Ok(())
 }
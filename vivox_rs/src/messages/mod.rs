@@ -0,0 +1,131 @@
+use crate::enums::VivoxError;
+use crate::uri::UserUri;
+
+use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+/// Messages kept per session by default.
+pub(crate) const HISTORY_CAPACITY: usize = 100;
+
+/// Application defined data sent along with a message, invisible to
+/// players but delivered to every client.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CustomMetadata {
+  pub namespace: String,
+  pub body: String,
+}
+
+/// A text message posted to a channel.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChannelMessage {
+  pub session_handle: String,
+  pub sender_uri: String,
+  pub sender_display_name: String,
+  pub body: String,
+  /// Language tag such as `en` when the sender set one
+  pub language: Option<String>,
+  pub custom_metadata: Option<CustomMetadata>,
+  pub received_at: SystemTime,
+}
+
+/// A private message sent directly to the local account.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserMessage {
+  /// `None` when the sender address isn't a user URI
+  pub sender: Option<UserUri>,
+  /// Sender address as the SDK reported it
+  pub sender_uri: String,
+  pub sender_display_name: String,
+  pub body: String,
+  pub language: Option<String>,
+  pub custom_metadata: Option<CustomMetadata>,
+  pub received_at: SystemTime,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum UserMessageEvent {
+  Received(UserMessage),
+  /// A message sent with `Account::send_user_message` could not be
+  /// delivered, e.g. because the recipient logged out
+  Failed {
+    message_id: String,
+    status: VivoxError,
+  },
+}
+
+/// The most recent messages of a session, oldest first.
+pub(crate) struct MessageHistory {
+  capacity: usize,
+  messages: VecDeque<ChannelMessage>,
+}
+
+impl MessageHistory {
+  pub(crate) fn new(capacity: usize) -> Self {
+    Self {
+      capacity,
+      messages: VecDeque::with_capacity(capacity),
+    }
+  }
+
+  pub(crate) fn push(&mut self, message: ChannelMessage) {
+    if self.capacity == 0 {
+      return;
+    }
+
+    while self.messages.len() >= self.capacity {
+      self.messages.pop_front();
+    }
+
+    self.messages.push_back(message);
+  }
+
+  pub(crate) fn snapshot(&self) -> Vec<ChannelMessage> {
+    self.messages.iter().cloned().collect()
+  }
+}
+
+/// Reads the optional application stanza of a message.
+pub(crate) fn custom_metadata(namespace: String, body: String) -> Option<CustomMetadata> {
+  match namespace.is_empty() {
+    true => None,
+    false => Some(CustomMetadata { namespace, body }),
+  }
+}
+
+/// Empty strings from the SDK mean the field was not set.
+pub(crate) fn non_empty(input: String) -> Option<String> {
+  match input.is_empty() {
+    true => None,
+    false => Some(input),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn message(body: &str) -> ChannelMessage {
+    ChannelMessage {
+      session_handle: "s1".to_string(),
+      sender_uri: "sip:a@x".to_string(),
+      sender_display_name: "a".to_string(),
+      body: body.to_string(),
+      language: None,
+      custom_metadata: None,
+      received_at: SystemTime::UNIX_EPOCH,
+    }
+  }
+
+  #[test]
+  fn history_drops_oldest_messages() {
+    let mut history = MessageHistory::new(2);
+
+    history.push(message("one"));
+    history.push(message("two"));
+    history.push(message("three"));
+
+    let bodies: Vec<String> = history.snapshot().into_iter().map(|m| m.body).collect();
+    assert_eq!(bodies, vec!["two", "three"]);
+  }
+}
@@ -0,0 +1,156 @@
+use crate::enums::RemovalReason;
+use crate::events::Event;
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+const CHANGE_CAPACITY: usize = 256;
+
+const DEFAULT_VOLUME: i32 = 50;
+
+/// Someone present in a session, including the local user.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Participant {
+  pub uri: String,
+  pub display_name: String,
+  pub is_current_user: bool,
+  pub is_speaking: bool,
+  /// Voice energy between 0.0 and 1.0
+  pub energy: f64,
+  /// Local playback volume between 0 and 100, 50 leaves it unchanged
+  pub volume: i32,
+  /// Muted locally by this user, nobody else is affected
+  pub is_muted_for_me: bool,
+  /// Muted for everyone by a channel moderator
+  pub is_moderator_muted: bool,
+  pub is_typing: bool,
+}
+
+impl Participant {
+  fn new(uri: &str, display_name: &str, is_current_user: bool) -> Self {
+    Self {
+      uri: uri.to_string(),
+      display_name: display_name.to_string(),
+      is_current_user,
+      is_speaking: false,
+      energy: 0.0,
+      volume: DEFAULT_VOLUME,
+      is_muted_for_me: false,
+      is_moderator_muted: false,
+      is_typing: false,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RosterChange {
+  Added(Participant),
+  /// The participant after the update
+  Updated(Participant),
+  Removed(Participant, RemovalReason),
+}
+
+/// Participants of one session, keyed by URI.
+pub(crate) struct Roster {
+  participants: Mutex<BTreeMap<String, Participant>>,
+  changes: broadcast::Sender<RosterChange>,
+}
+
+impl Roster {
+  pub(crate) fn new() -> Self {
+    let (changes, _) = broadcast::channel(CHANGE_CAPACITY);
+
+    Self {
+      participants: Mutex::new(BTreeMap::new()),
+      changes,
+    }
+  }
+
+  pub(crate) fn snapshot(&self) -> Vec<Participant> {
+    self.participants.lock().unwrap()
+      .values()
+      .cloned()
+      .collect()
+  }
+
+  pub(crate) fn get(&self, uri: &str) -> Option<Participant> {
+    self.participants.lock().unwrap().get(uri).cloned()
+  }
+
+  pub(crate) fn subscribe(&self) -> broadcast::Receiver<RosterChange> {
+    self.changes.subscribe()
+  }
+
+  /// Updates a participant in place and reports the change if anything
+  /// actually differs.
+  pub(crate) fn modify<F: FnOnce(&mut Participant)>(&self, uri: &str, f: F) {
+    let mut participants = self.participants.lock().unwrap();
+
+    if let Some(participant) = participants.get_mut(uri) {
+      let before = participant.clone();
+
+      f(participant);
+
+      if *participant != before {
+        let _ = self.changes.send(RosterChange::Updated(participant.clone()));
+      }
+    }
+  }
+
+  /// Applies a participant event that is already known to belong to the
+  /// session owning this roster.
+  pub(crate) fn apply(&self, evt: &Event) {
+    match evt {
+      Event::ParticipantAdded { participant_uri, display_name, is_current_user, .. } => {
+        let participant = Participant::new(participant_uri, display_name, *is_current_user);
+
+        self.participants.lock().unwrap().insert(participant_uri.clone(), participant.clone());
+        let _ = self.changes.send(RosterChange::Added(participant));
+      },
+      Event::ParticipantUpdated {
+        participant_uri,
+        is_speaking,
+        energy,
+        volume,
+        is_muted_for_me,
+        is_moderator_muted,
+        ..
+      } => {
+        self.modify(participant_uri, |participant| {
+          participant.is_speaking = *is_speaking;
+          participant.energy = *energy;
+          participant.volume = *volume;
+          participant.is_muted_for_me = *is_muted_for_me;
+          participant.is_moderator_muted = *is_moderator_muted;
+        });
+      },
+      Event::TypingChanged { participant_uri, is_typing, .. } => {
+        self.modify(participant_uri, |participant| participant.is_typing = *is_typing);
+      },
+      Event::ParticipantRemoved { participant_uri, reason, .. } => {
+        let removed = self.participants.lock().unwrap().remove(participant_uri);
+
+        if let Some(participant) = removed {
+          let _ = self.changes.send(RosterChange::Removed(participant, *reason));
+        }
+      },
+      _ => {},
+    }
+  }
+
+  /// Empties the roster once the session is gone.
+  pub(crate) fn clear(&self) {
+    let removed: Vec<Participant> = {
+      let mut participants = self.participants.lock().unwrap();
+      let removed = participants.values().cloned().collect();
+
+      participants.clear();
+      removed
+    };
+
+    for participant in removed {
+      let _ = self.changes.send(RosterChange::Removed(participant, RemovalReason::Left));
+    }
+  }
+}
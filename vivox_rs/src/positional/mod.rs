@@ -0,0 +1,181 @@
+use std::time::{Duration, Instant};
+
+/// Shortest gap between two position updates of the same session.
+pub(crate) const POSITION_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A point or direction in the SDK's right handed coordinate system,
+/// where -Z points forward and +Y points up.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vector3 {
+  pub x: f64,
+  pub y: f64,
+  pub z: f64,
+}
+
+impl Vector3 {
+  pub fn new(x: f64, y: f64, z: f64) -> Self {
+    Self { x, y, z }
+  }
+
+  pub(crate) fn cross(&self, other: &Vector3) -> Vector3 {
+    Vector3 {
+      x: self.y * other.z - self.z * other.y,
+      y: self.z * other.x - self.x * other.z,
+      z: self.x * other.y - self.y * other.x,
+    }
+  }
+
+  pub(crate) fn to_array(self) -> [f64; 3] {
+    [self.x, self.y, self.z]
+  }
+}
+
+/// Where someone is facing. The left vector the SDK wants as well is
+/// derived from these two.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Orientation {
+  pub at: Vector3,
+  pub up: Vector3,
+}
+
+impl Orientation {
+  pub(crate) fn left(&self) -> Vector3 {
+    self.up.cross(&self.at)
+  }
+}
+
+impl Default for Orientation {
+  fn default() -> Self {
+    Self {
+      at: Vector3::new(0.0, 0.0, -1.0),
+      up: Vector3::new(0.0, 1.0, 0.0),
+    }
+  }
+}
+
+/// Placement of the local speaker's mouth or listener's ears.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Position3d {
+  pub position: Vector3,
+  pub velocity: Vector3,
+  pub orientation: Orientation,
+}
+
+pub(crate) enum Schedule {
+  /// Same as the last requested position, nothing to send
+  Unchanged,
+  /// Send at the given time unless a newer generation shows up first
+  SendAt(Instant, u64),
+}
+
+/// Coalesces position updates so the SDK gets at most one per interval
+/// and only ever the latest one.
+pub(crate) struct PositionThrottle {
+  interval: Duration,
+  latest: Option<(Position3d, Position3d)>,
+  generation: u64,
+  last_sent_at: Option<Instant>,
+}
+
+impl PositionThrottle {
+  pub(crate) fn new(interval: Duration) -> Self {
+    Self {
+      interval,
+      latest: None,
+      generation: 0,
+      last_sent_at: None,
+    }
+  }
+
+  pub(crate) fn schedule(&mut self, speaker: Position3d, listener: Position3d, now: Instant) -> Schedule {
+    if self.latest == Some((speaker, listener)) {
+      return Schedule::Unchanged;
+    }
+
+    self.latest = Some((speaker, listener));
+    self.generation += 1;
+
+    let at = match self.last_sent_at {
+      Some(last) if last + self.interval > now => last + self.interval,
+      _ => now,
+    };
+
+    Schedule::SendAt(at, self.generation)
+  }
+
+  /// Claims the send slot. Returns `false` when a newer position replaced
+  /// this one while it was waiting.
+  pub(crate) fn take(&mut self, generation: u64, now: Instant) -> bool {
+    if generation != self.generation {
+      return false;
+    }
+
+    self.last_sent_at = Some(now);
+    true
+  }
+
+  /// Forgets a position the SDK rejected so it is sent again next time.
+  pub(crate) fn failed(&mut self, generation: u64) {
+    if generation == self.generation {
+      self.latest = None;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn at(x: f64) -> Position3d {
+    Position3d {
+      position: Vector3::new(x, 0.0, 0.0),
+      ..Position3d::default()
+    }
+  }
+
+  #[test]
+  fn default_orientation_faces_forward() {
+    assert_eq!(Orientation::default().left(), Vector3::new(-1.0, 0.0, 0.0));
+  }
+
+  #[test]
+  fn unchanged_positions_are_skipped() {
+    let t0 = Instant::now();
+    let mut throttle = PositionThrottle::new(Duration::from_millis(100));
+
+    match throttle.schedule(at(1.0), at(0.0), t0) {
+      Schedule::SendAt(when, generation) => {
+        assert_eq!(when, t0);
+        assert!(throttle.take(generation, t0));
+      },
+      Schedule::Unchanged => panic!("first position must be sent"),
+    }
+
+    assert!(matches!(throttle.schedule(at(1.0), at(0.0), t0), Schedule::Unchanged));
+  }
+
+  #[test]
+  fn updates_are_rate_limited_and_coalesced() {
+    let t0 = Instant::now();
+    let mut throttle = PositionThrottle::new(Duration::from_millis(100));
+
+    if let Schedule::SendAt(_, generation) = throttle.schedule(at(1.0), at(0.0), t0) {
+      assert!(throttle.take(generation, t0));
+    }
+
+    let first = match throttle.schedule(at(2.0), at(0.0), t0 + Duration::from_millis(10)) {
+      Schedule::SendAt(when, generation) => {
+        assert_eq!(when, t0 + Duration::from_millis(100));
+        generation
+      },
+      Schedule::Unchanged => panic!("moved"),
+    };
+    let second = match throttle.schedule(at(3.0), at(0.0), t0 + Duration::from_millis(20)) {
+      Schedule::SendAt(_, generation) => generation,
+      Schedule::Unchanged => panic!("moved"),
+    };
+
+    assert!(!throttle.take(first, t0 + Duration::from_millis(100)));
+    assert!(throttle.take(second, t0 + Duration::from_millis(100)));
+  }
+}
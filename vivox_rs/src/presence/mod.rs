@@ -0,0 +1,416 @@
+use crate::client::VivoxClient;
+use crate::enums::VivoxError;
+use crate::events::Event;
+use crate::helpers::*;
+use crate::uri::UserUri;
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::os::raw::c_int;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use vivox_rs_sys::*;
+
+const CHANGE_CAPACITY: usize = 64;
+
+/// How the SDK answers subscription requests from other users, set at
+/// login with `AnonymousLogin::buddy_management_mode`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BuddyManagementMode {
+  /// Everyone may see the account's presence
+  AutoAccept,
+  /// Accepts and adds the requester to the buddy list
+  AutoAdd,
+  Block,
+  Hide,
+  /// Requests are surfaced as `BuddyEvent::SubscriptionRequested`
+  Application,
+}
+
+impl BuddyManagementMode {
+  pub(crate) fn to_raw(self) -> vx_buddy_management_mode {
+    match self {
+      BuddyManagementMode::AutoAccept => vx_buddy_management_mode_mode_auto_accept,
+      BuddyManagementMode::AutoAdd => vx_buddy_management_mode_mode_auto_add,
+      BuddyManagementMode::Block => vx_buddy_management_mode_mode_block,
+      BuddyManagementMode::Hide => vx_buddy_management_mode_mode_hide,
+      BuddyManagementMode::Application => vx_buddy_management_mode_mode_application,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PresenceStatus {
+  Offline,
+  /// The buddy has not accepted the subscription yet
+  Pending,
+  Online,
+  Busy,
+  BeRightBack,
+  Away,
+  OnThePhone,
+  OutToLunch,
+  Chat,
+  /// Only meaningful together with the custom message
+  Custom,
+}
+
+impl PresenceStatus {
+  pub(crate) fn from_raw(raw: vx_buddy_presence_state) -> Self {
+    match raw {
+      vx_buddy_presence_state_buddy_presence_pending => PresenceStatus::Pending,
+      vx_buddy_presence_state_buddy_presence_online => PresenceStatus::Online,
+      vx_buddy_presence_state_buddy_presence_online_slc => PresenceStatus::Online,
+      vx_buddy_presence_state_buddy_presence_busy => PresenceStatus::Busy,
+      vx_buddy_presence_state_buddy_presence_brb => PresenceStatus::BeRightBack,
+      vx_buddy_presence_state_buddy_presence_away => PresenceStatus::Away,
+      vx_buddy_presence_state_buddy_presence_onthephone => PresenceStatus::OnThePhone,
+      vx_buddy_presence_state_buddy_presence_outtolunch => PresenceStatus::OutToLunch,
+      vx_buddy_presence_state_buddy_presence_chat => PresenceStatus::Chat,
+      vx_buddy_presence_state_buddy_presence_custom => PresenceStatus::Custom,
+      _ => PresenceStatus::Offline,
+    }
+  }
+
+  pub(crate) fn to_raw(self) -> vx_buddy_presence_state {
+    match self {
+      PresenceStatus::Offline => vx_buddy_presence_state_buddy_presence_offline,
+      PresenceStatus::Pending => vx_buddy_presence_state_buddy_presence_pending,
+      PresenceStatus::Online => vx_buddy_presence_state_buddy_presence_online,
+      PresenceStatus::Busy => vx_buddy_presence_state_buddy_presence_busy,
+      PresenceStatus::BeRightBack => vx_buddy_presence_state_buddy_presence_brb,
+      PresenceStatus::Away => vx_buddy_presence_state_buddy_presence_away,
+      PresenceStatus::OnThePhone => vx_buddy_presence_state_buddy_presence_onthephone,
+      PresenceStatus::OutToLunch => vx_buddy_presence_state_buddy_presence_outtolunch,
+      PresenceStatus::Chat => vx_buddy_presence_state_buddy_presence_chat,
+      PresenceStatus::Custom => vx_buddy_presence_state_buddy_presence_custom,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Presence {
+  pub status: PresenceStatus,
+  pub message: String,
+}
+
+impl Default for Presence {
+  fn default() -> Self {
+    Self {
+      status: PresenceStatus::Offline,
+      message: String::new(),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Buddy {
+  /// `None` when the buddy address isn't a user URI
+  pub user: Option<UserUri>,
+  /// Buddy address as the SDK reported it
+  pub uri: String,
+  pub display_name: String,
+  pub presence: Presence,
+}
+
+impl Buddy {
+  pub fn is_online(&self) -> bool {
+    !matches!(self.presence.status, PresenceStatus::Offline | PresenceStatus::Pending)
+  }
+}
+
+/// Another user asking to see this account's presence.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SubscriptionRequest {
+  /// `None` when the requester address isn't a user URI
+  pub from: Option<UserUri>,
+  /// Requester address as the SDK reported it
+  pub from_uri: String,
+  pub display_name: String,
+  pub message: String,
+  pub(crate) subscription_handle: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubscriptionReply {
+  Allow,
+  /// Refuses and stops further requests from the same user
+  Block,
+  /// Refuses while appearing offline to the user
+  Hide,
+}
+
+impl SubscriptionReply {
+  fn to_raw(self) -> vx_rule_type {
+    match self {
+      SubscriptionReply::Allow => vx_rule_type_rule_allow,
+      SubscriptionReply::Block => vx_rule_type_rule_block,
+      SubscriptionReply::Hide => vx_rule_type_rule_hide,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuddyEvent {
+  Added(Buddy),
+  /// The buddy after the change, usually a presence update
+  Updated(Buddy),
+  Removed(Buddy),
+  SubscriptionRequested(SubscriptionRequest),
+}
+
+/// Buddies of one account, kept current from buddy and presence events.
+pub(crate) struct BuddyState {
+  buddies: Mutex<BTreeMap<String, Buddy>>,
+  blocked: Mutex<BTreeSet<String>>,
+  changes: broadcast::Sender<BuddyEvent>,
+}
+
+impl BuddyState {
+  pub(crate) fn new() -> Self {
+    let (changes, _) = broadcast::channel(CHANGE_CAPACITY);
+
+    Self {
+      buddies: Mutex::new(BTreeMap::new()),
+      blocked: Mutex::new(BTreeSet::new()),
+      changes,
+    }
+  }
+
+  fn apply(&self, evt: Event) {
+    let change = match evt {
+      Event::BuddyChanged { uri, display_name, .. } => {
+        let mut buddies = self.buddies.lock().unwrap();
+
+        match buddies.get_mut(&uri) {
+          Some(buddy) => {
+            buddy.display_name = display_name;
+            BuddyEvent::Updated(buddy.clone())
+          },
+          None => {
+            let buddy = Buddy {
+              user: UserUri::parse(&uri).ok(),
+              uri: uri.clone(),
+              display_name,
+              presence: Presence::default(),
+            };
+
+            buddies.insert(uri, buddy.clone());
+            BuddyEvent::Added(buddy)
+          },
+        }
+      },
+      Event::BuddyRemoved { uri, .. } => match self.buddies.lock().unwrap().remove(&uri) {
+        Some(buddy) => BuddyEvent::Removed(buddy),
+        None => return,
+      },
+      Event::BuddyPresence { uri, presence, .. } => match self.buddies.lock().unwrap().get_mut(&uri) {
+        Some(buddy) if buddy.presence != presence => {
+          buddy.presence = presence;
+          BuddyEvent::Updated(buddy.clone())
+        },
+        _ => return,
+      },
+      Event::SubscriptionRequested { request, .. } => BuddyEvent::SubscriptionRequested(request),
+      _ => return,
+    };
+
+    let _ = self.changes.send(change);
+  }
+}
+
+/// Consumes an account's events until it is gone.
+pub(crate) async fn track(state: Arc<BuddyState>, mut rx: broadcast::Receiver<Event>) {
+  loop {
+    match rx.recv().await {
+      Ok(evt) => state.apply(evt),
+      Err(broadcast::RecvError::Lagged(_)) => {},
+      Err(broadcast::RecvError::Closed) => break,
+    }
+  }
+}
+
+/// Presence and buddies of a logged in account.
+///
+/// Requires the login to enable social features with
+/// `AnonymousLogin::enable_social`.
+#[derive(Clone)]
+pub struct BuddyList {
+  client: VivoxClient,
+  account_handle: String,
+  state: Arc<BuddyState>,
+}
+
+impl BuddyList {
+  pub(crate) fn new(client: VivoxClient, account_handle: String, state: Arc<BuddyState>) -> Self {
+    Self {
+      client,
+      account_handle,
+      state,
+    }
+  }
+
+  /// Every buddy, ordered by URI.
+  pub fn buddies(&self) -> Vec<Buddy> {
+    self.state.buddies.lock().unwrap().values().cloned().collect()
+  }
+
+  pub fn buddy(&self, uri: &UserUri) -> Option<Buddy> {
+    self.state.buddies.lock().unwrap().get(&uri.to_string()).cloned()
+  }
+
+  /// Subscribes to buddy list changes and subscription requests.
+  pub fn events(&self) -> broadcast::Receiver<BuddyEvent> {
+    self.state.changes.subscribe()
+  }
+
+  /// Block rules created through this list since login.
+  pub fn block_rules(&self) -> Vec<String> {
+    self.state.blocked.lock().unwrap().iter().cloned().collect()
+  }
+
+  /// Publishes the account's own presence to everyone subscribed to it.
+  pub fn set_presence(
+    &self,
+    status: PresenceStatus,
+    message: &str,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    use std::mem;
+
+    unsafe {
+      let mut req: *mut vx_req_account_set_presence = mem::zeroed();
+
+      vx_req_account_set_presence_create(&mut req);
+
+      (*req).account_handle = strdup(&self.account_handle);
+      (*req).presence = status.to_raw();
+      (*req).custom_message = strdup(message);
+
+      self.client.issue(&mut (*req).base)
+    }
+  }
+
+  /// Adds or renames a buddy, asking for permission to see their presence.
+  pub fn add(&self, uri: &UserUri, display_name: &str) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    unsafe {
+      let mut req: *mut vx_req_account_buddy_set = mem::zeroed();
+
+      vx_req_account_buddy_set_create(&mut req);
+
+      (*req).account_handle = strdup(&self.account_handle);
+      (*req).buddy_uri = strdup(&uri.to_string());
+      (*req).display_name = strdup(display_name);
+
+      self.client.issue(&mut (*req).base)
+    }
+  }
+
+  pub fn remove(&self, uri: &UserUri) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    unsafe {
+      let mut req: *mut vx_req_account_buddy_delete = mem::zeroed();
+
+      vx_req_account_buddy_delete_create(&mut req);
+
+      (*req).account_handle = strdup(&self.account_handle);
+      (*req).buddy_uri = strdup(&uri.to_string());
+
+      self.client.issue(&mut (*req).base)
+    }
+  }
+
+  /// Answers a `BuddyEvent::SubscriptionRequested`. With `remember` the
+  /// answer becomes a rule applied to future requests of the same user,
+  /// blocking ones show up in `block_rules`.
+  pub fn reply(
+    &self,
+    request: &SubscriptionRequest,
+    reply: SubscriptionReply,
+    remember: bool,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    use std::mem;
+
+    let pending = unsafe {
+      let mut req: *mut vx_req_account_send_subscription_reply = mem::zeroed();
+
+      vx_req_account_send_subscription_reply_create(&mut req);
+
+      (*req).account_handle = strdup(&self.account_handle);
+      (*req).buddy_uri = strdup(&request.from_uri);
+      (*req).subscription_handle = strdup(&request.subscription_handle);
+      (*req).rule_type = reply.to_raw();
+      (*req).auto_accept = remember as c_int;
+
+      self.client.issue(&mut (*req).base)
+    };
+    let state = self.state.clone();
+    let mask = request.from_uri.clone();
+
+    async move {
+      pending.await?;
+
+      if remember && reply == SubscriptionReply::Block {
+        state.blocked.lock().unwrap().insert(mask);
+      }
+
+      Ok(())
+    }
+  }
+
+  /// Blocks everyone matching `mask`, a user URI that may contain `*`
+  /// wildcards. With `presence_only` they can still message the account.
+  pub fn block(&self, mask: &str, presence_only: bool) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    let pending = unsafe {
+      let mut req: *mut vx_req_account_create_block_rule = mem::zeroed();
+
+      vx_req_account_create_block_rule_create(&mut req);
+
+      (*req).account_handle = strdup(&self.account_handle);
+      (*req).block_mask = strdup(mask);
+      (*req).presence_only = presence_only as c_int;
+
+      self.client.issue(&mut (*req).base)
+    };
+    let state = self.state.clone();
+    let mask = mask.to_string();
+
+    async move {
+      pending.await?;
+      state.blocked.lock().unwrap().insert(mask);
+
+      Ok(())
+    }
+  }
+
+  pub fn unblock(&self, mask: &str) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    let pending = unsafe {
+      let mut req: *mut vx_req_account_delete_block_rule = mem::zeroed();
+
+      vx_req_account_delete_block_rule_create(&mut req);
+
+      (*req).account_handle = strdup(&self.account_handle);
+      (*req).block_mask = strdup(mask);
+
+      self.client.issue(&mut (*req).base)
+    };
+    let state = self.state.clone();
+    let mask = mask.to_string();
+
+    async move {
+      pending.await?;
+      state.blocked.lock().unwrap().remove(&mask);
+
+      Ok(())
+    }
+  }
+}
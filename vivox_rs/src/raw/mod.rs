@@ -0,0 +1,509 @@
+//! Requests and events copied field by field out of the SDK structs.
+//!
+//! Names are those of the SDK's type constants without their prefix, such
+//! as `req_sessiongroup_add_session` or `evt_media_stream_updated`, so they
+//! stay the same when the enum values are renumbered between SDK versions.
+//! Fields keep their SDK names as well.
+
+use crate::events::Event;
+use crate::helpers::to_owned_string;
+
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use vivox_rs_sys::*;
+
+const REQUEST_PREFIX: &str = "vx_request_type_";
+const EVENT_PREFIX: &str = "vx_event_type_";
+
+/// A request as the wrapper built it.
+///
+/// Access tokens are left out, they are secrets and expire anyway.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RawRequest {
+  pub request_type: String,
+  pub fields: Map<String, Value>,
+}
+
+impl RawRequest {
+  /// # Safety
+  /// `req` must point to a fully built request.
+  pub(crate) unsafe fn capture(req: *const vx_req_base_t) -> Self {
+    capture_request(req)
+  }
+}
+
+/// An event as the SDK delivered it, including the fields the wrapper
+/// does not look at yet.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RawEvent {
+  pub event_type: String,
+  pub create_time_ms: u64,
+  pub fields: Map<String, Value>,
+}
+
+impl RawEvent {
+  /// Returns `None` for events the wrapper does not know about.
+  ///
+  /// # Safety
+  /// `evt` must be a live event returned by `vx_get_message`.
+  pub(crate) unsafe fn capture(evt: *const vx_evt_base_t) -> Option<Self> {
+    let (event_type, fields) = capture_event(evt)?;
+
+    Some(Self {
+      event_type: event_type.to_string(),
+      create_time_ms: (*evt).message.create_time_ms,
+      fields,
+    })
+  }
+
+  /// Rebuilds the SDK struct and decodes it the way a polled event is.
+  ///
+  /// Returns `None` for unknown event types and missing or mistyped fields.
+  pub fn decode(&self) -> Option<Event> {
+    unsafe { rebuild_event(self) }
+  }
+}
+
+/// Keeps what rebuilt structs point to alive while they are decoded.
+#[derive(Default)]
+struct Arena {
+  strings: Vec<CString>,
+  // Boxed so the structs stay put as the vector grows
+  #[allow(clippy::vec_box)]
+  devices: Vec<Box<vx_device_t>>,
+}
+
+trait Field: Sized {
+  unsafe fn save(&self) -> Value;
+  fn load(value: &Value, arena: &mut Arena) -> Option<Self>;
+}
+
+impl Field for *mut c_char {
+  unsafe fn save(&self) -> Value {
+    if self.is_null() {
+      return Value::Null;
+    }
+
+    Value::String(to_owned_string(*self))
+  }
+
+  fn load(value: &Value, arena: &mut Arena) -> Option<Self> {
+    match value {
+      Value::Null => Some(std::ptr::null_mut()),
+      Value::String(s) => {
+        let s = CString::new(s.as_str()).ok()?;
+        // The buffer stays put when the CString is moved into the arena
+        let ptr = s.as_ptr() as *mut c_char;
+
+        arena.strings.push(s);
+
+        Some(ptr)
+      },
+      _ => None,
+    }
+  }
+}
+
+macro_rules! integer_field {
+  ($($ty:ty => $as:ident,)*) => {
+    $(
+      impl Field for $ty {
+        unsafe fn save(&self) -> Value {
+          Value::from(*self)
+        }
+
+        fn load(value: &Value, _: &mut Arena) -> Option<Self> {
+          <$ty>::try_from(value.$as()?).ok()
+        }
+      }
+    )*
+  };
+}
+
+integer_field! {
+  i32 => as_i64,
+  u32 => as_u64,
+  u64 => as_u64,
+}
+
+impl Field for f64 {
+  unsafe fn save(&self) -> Value {
+    Value::from(*self)
+  }
+
+  fn load(value: &Value, _: &mut Arena) -> Option<Self> {
+    value.as_f64()
+  }
+}
+
+impl Field for [f64; 3] {
+  unsafe fn save(&self) -> Value {
+    Value::from(self.to_vec())
+  }
+
+  fn load(value: &Value, _: &mut Arena) -> Option<Self> {
+    match value.as_array()?.as_slice() {
+      [x, y, z] => Some([x.as_f64()?, y.as_f64()?, z.as_f64()?]),
+      _ => None,
+    }
+  }
+}
+
+impl Field for *mut vx_device_t {
+  unsafe fn save(&self) -> Value {
+    if self.is_null() {
+      return Value::Null;
+    }
+
+    let mut device = Map::new();
+
+    device.insert("device".to_string(), (**self).device.save());
+    device.insert("display_name".to_string(), (**self).display_name.save());
+    device.insert("device_type".to_string(), (**self).device_type.save());
+
+    Value::Object(device)
+  }
+
+  fn load(value: &Value, arena: &mut Arena) -> Option<Self> {
+    use std::mem;
+
+    if value.is_null() {
+      return Some(std::ptr::null_mut());
+    }
+
+    let mut device: Box<vx_device_t> = Box::new(unsafe { mem::zeroed() });
+
+    device.device = Field::load(value.get("device")?, arena)?;
+    device.display_name = Field::load(value.get("display_name")?, arena)?;
+    device.device_type = Field::load(value.get("device_type")?, arena)?;
+
+    let ptr = &mut *device as *mut vx_device_t;
+
+    arena.devices.push(device);
+
+    Some(ptr)
+  }
+}
+
+macro_rules! requests {
+  ($($kind:ident => $ty:ty { $($field:ident),* $(,)? })*) => {
+    unsafe fn capture_request(req: *const vx_req_base_t) -> RawRequest {
+      let kind = (*req).type_;
+      let mut fields = Map::new();
+
+      $(
+        if kind == $kind {
+          let req = req as *const $ty;
+
+          $(fields.insert(stringify!($field).to_string(), (*req).$field.save());)*
+
+          return RawRequest {
+            request_type: stringify!($kind)[REQUEST_PREFIX.len()..].to_string(),
+            fields,
+          };
+        }
+      )*
+
+      // Not issued by the wrapper, only the number is known
+      RawRequest {
+        request_type: format!("unknown_{}", kind),
+        fields,
+      }
+    }
+  };
+}
+
+macro_rules! events {
+  ($($kind:ident => $ty:ty { $($field:ident),* $(,)? })*) => {
+    unsafe fn capture_event(evt: *const vx_evt_base_t) -> Option<(&'static str, Map<String, Value>)> {
+      let kind = (*evt).type_;
+
+      $(
+        if kind == $kind {
+          let evt = evt as *const $ty;
+          let mut fields = Map::new();
+
+          $(fields.insert(stringify!($field).to_string(), (*evt).$field.save());)*
+
+          return Some((&stringify!($kind)[EVENT_PREFIX.len()..], fields));
+        }
+      )*
+
+      None
+    }
+
+    unsafe fn rebuild_event(raw: &RawEvent) -> Option<Event> {
+      use std::mem;
+
+      let mut arena = Arena::default();
+
+      $(
+        if raw.event_type == stringify!($kind)[EVENT_PREFIX.len()..] {
+          let mut evt: $ty = mem::zeroed();
+
+          evt.base.message.type_ = vx_message_type_msg_event;
+          evt.base.message.create_time_ms = raw.create_time_ms;
+          evt.base.type_ = $kind;
+          $(evt.$field = Field::load(raw.fields.get(stringify!($field))?, &mut arena)?;)*
+
+          return Event::decode(&mut evt.base);
+        }
+      )*
+
+      None
+    }
+  };
+}
+
+requests! {
+  vx_request_type_req_connector_create => vx_req_connector_create {
+    client_name, acct_mgmt_server, connector_handle, application, max_calls,
+  }
+  vx_request_type_req_connector_initiate_shutdown => vx_req_connector_initiate_shutdown {
+    connector_handle, client_name,
+  }
+  vx_request_type_req_account_anonymous_login => vx_req_account_anonymous_login {
+    connector_handle, acct_name, displayname, enable_buddies_and_presence,
+    buddy_management_mode, account_handle, languages,
+    participant_property_frequency, application_override, acct_mgmt_server,
+    application_token, autopost_crash_dumps, enable_presence_persistence,
+  }
+  vx_request_type_req_account_logout => vx_req_account_logout {
+    account_handle, logout_reason,
+  }
+  vx_request_type_req_sessiongroup_add_session => vx_req_sessiongroup_add_session {
+    sessiongroup_handle, session_handle, uri, name, password,
+    jitter_compensation, connect_audio, password_hash_algorithm, connect_text,
+    session_font_id, account_handle,
+  }
+  vx_request_type_req_sessiongroup_remove_session => vx_req_sessiongroup_remove_session {
+    sessiongroup_handle, session_handle,
+  }
+  vx_request_type_req_sessiongroup_terminate => vx_req_sessiongroup_terminate {
+    sessiongroup_handle,
+  }
+  vx_request_type_req_sessiongroup_set_tx_session => vx_req_sessiongroup_set_tx_session {
+    session_handle,
+  }
+  vx_request_type_req_sessiongroup_set_tx_all_sessions => vx_req_sessiongroup_set_tx_all_sessions {
+    sessiongroup_handle,
+  }
+  vx_request_type_req_sessiongroup_set_tx_no_session => vx_req_sessiongroup_set_tx_no_session {
+    sessiongroup_handle,
+  }
+  vx_request_type_req_session_media_connect => vx_req_session_media_connect {
+    sessiongroup_handle, session_handle, session_font_id, jitter_compensation,
+    media, capture_device_id, render_device_id,
+  }
+  vx_request_type_req_session_media_disconnect => vx_req_session_media_disconnect {
+    sessiongroup_handle, session_handle, media,
+  }
+  vx_request_type_req_session_text_connect => vx_req_session_text_connect {
+    sessiongroup_handle, session_handle,
+  }
+  vx_request_type_req_session_text_disconnect => vx_req_session_text_disconnect {
+    sessiongroup_handle, session_handle,
+  }
+  vx_request_type_req_session_set_participant_mute_for_me => vx_req_session_set_participant_mute_for_me {
+    session_handle, participant_uri, mute, scope,
+  }
+  vx_request_type_req_session_set_participant_volume_for_me => vx_req_session_set_participant_volume_for_me {
+    session_handle, participant_uri, volume,
+  }
+  vx_request_type_req_session_set_local_render_volume => vx_req_session_set_local_render_volume {
+    session_handle, volume,
+  }
+  vx_request_type_req_session_set_3d_position => vx_req_session_set_3d_position {
+    session_handle, speaker_position, speaker_velocity, speaker_at_orientation,
+    speaker_up_orientation, speaker_left_orientation, listener_position,
+    listener_velocity, listener_at_orientation, listener_up_orientation,
+    listener_left_orientation, type_,
+  }
+  vx_request_type_req_session_send_message => vx_req_session_send_message {
+    session_handle, message_header, message_body, application_stanza_namespace,
+    application_stanza_body, language,
+  }
+  vx_request_type_req_session_archive_query => vx_req_session_archive_query {
+    session_handle, time_start, time_end, search_text, participant_uri, max,
+    after_id, before_id, first_message_index,
+  }
+  vx_request_type_req_channel_kick_user => vx_req_channel_kick_user {
+    account_handle, channel_uri, participant_uri,
+  }
+  vx_request_type_req_channel_mute_user => vx_req_channel_mute_user {
+    account_handle, channel_uri, participant_uri, set_muted, scope,
+  }
+  vx_request_type_req_channel_mute_all_users => vx_req_channel_mute_all_users {
+    account_handle, channel_uri, set_muted, scope,
+  }
+  vx_request_type_req_connector_mute_local_mic => vx_req_connector_mute_local_mic {
+    connector_handle, mute_level, account_handle,
+  }
+  vx_request_type_req_connector_mute_local_speaker => vx_req_connector_mute_local_speaker {
+    connector_handle, mute_level, account_handle,
+  }
+  vx_request_type_req_connector_set_local_mic_volume => vx_req_connector_set_local_mic_volume {
+    connector_handle, volume, account_handle,
+  }
+  vx_request_type_req_connector_set_local_speaker_volume => vx_req_connector_set_local_speaker_volume {
+    connector_handle, volume, account_handle,
+  }
+  vx_request_type_req_aux_get_capture_devices => vx_req_aux_get_capture_devices {
+    account_handle,
+  }
+  vx_request_type_req_aux_get_render_devices => vx_req_aux_get_render_devices {
+    account_handle,
+  }
+  vx_request_type_req_aux_set_capture_device => vx_req_aux_set_capture_device {
+    capture_device_specifier, account_handle,
+  }
+  vx_request_type_req_aux_set_render_device => vx_req_aux_set_render_device {
+    render_device_specifier, account_handle,
+  }
+  vx_request_type_req_aux_set_vad_properties => vx_req_aux_set_vad_properties {
+    vad_hangover, vad_sensitivity, vad_noise_floor, vad_auto,
+  }
+  vx_request_type_req_account_send_message => vx_req_account_send_message {
+    account_handle, user_uri, message_header, message_body,
+    application_stanza_namespace, application_stanza_body, language,
+  }
+  vx_request_type_req_account_archive_query => vx_req_account_archive_query {
+    account_handle, max, time_start, time_end, search_text, after_id,
+    before_id, first_message_index, channel_uri, participant_uri,
+  }
+  vx_request_type_req_account_set_presence => vx_req_account_set_presence {
+    account_handle, presence, custom_message,
+  }
+  vx_request_type_req_account_buddy_set => vx_req_account_buddy_set {
+    account_handle, buddy_uri, display_name, buddy_data, group_id, message,
+  }
+  vx_request_type_req_account_buddy_delete => vx_req_account_buddy_delete {
+    account_handle, buddy_uri,
+  }
+  vx_request_type_req_account_send_subscription_reply => vx_req_account_send_subscription_reply {
+    account_handle, rule_type, auto_accept, buddy_uri, subscription_handle,
+  }
+  vx_request_type_req_account_create_block_rule => vx_req_account_create_block_rule {
+    account_handle, block_mask, presence_only,
+  }
+  vx_request_type_req_account_delete_block_rule => vx_req_account_delete_block_rule {
+    account_handle, block_mask,
+  }
+}
+
+events! {
+  vx_event_type_evt_account_login_state_change => vx_evt_account_login_state_change {
+    state, account_handle, status_code, status_string,
+  }
+  vx_event_type_evt_sessiongroup_removed => vx_evt_sessiongroup_removed {
+    sessiongroup_handle,
+  }
+  vx_event_type_evt_session_removed => vx_evt_session_removed {
+    sessiongroup_handle, session_handle, uri,
+  }
+  vx_event_type_evt_media_stream_updated => vx_evt_media_stream_updated {
+    sessiongroup_handle, session_handle, status_code, status_string, state,
+    incoming,
+  }
+  vx_event_type_evt_text_stream_updated => vx_evt_text_stream_updated {
+    sessiongroup_handle, session_handle, enabled, state, incoming,
+    status_code, status_string,
+  }
+  vx_event_type_evt_participant_added => vx_evt_participant_added {
+    sessiongroup_handle, session_handle, participant_uri, account_name,
+    displayname, participant_type, application, is_current_user,
+    encoded_uri_with_tag,
+  }
+  vx_event_type_evt_participant_removed => vx_evt_participant_removed {
+    sessiongroup_handle, session_handle, participant_uri, account_name,
+    reason, is_current_user, encoded_uri_with_tag,
+  }
+  vx_event_type_evt_participant_updated => vx_evt_participant_updated {
+    sessiongroup_handle, session_handle, participant_uri, is_moderator_muted,
+    is_speaking, volume, energy, active_media, is_muted_for_me,
+    is_moderator_text_muted, is_text_muted_for_me, type_, is_current_user,
+    encoded_uri_with_tag,
+  }
+  vx_event_type_evt_session_notification => vx_evt_session_notification {
+    session_handle, participant_uri, notification_type, encoded_uri_with_tag,
+  }
+  vx_event_type_evt_audio_device_hot_swap => vx_evt_audio_device_hot_swap {
+    event_type, relevant_device,
+  }
+  vx_event_type_evt_message => vx_evt_message {
+    sessiongroup_handle, session_handle, participant_uri, message_header,
+    message_body, participant_displayname, application_stanza_namespace,
+    application_stanza_body, language, encoded_uri_with_tag,
+  }
+  vx_event_type_evt_user_to_user_message => vx_evt_user_to_user_message {
+    account_handle, from_uri, message_header, message_body, from_displayname,
+    application_stanza_namespace, application_stanza_body, language,
+    encoded_uri_with_tag,
+  }
+  vx_event_type_evt_account_send_message_failed => vx_evt_account_send_message_failed {
+    account_handle, request_id, status_code, status_string,
+  }
+  vx_event_type_evt_session_archive_message => vx_evt_session_archive_message {
+    sessiongroup_handle, session_handle, query_id, time_stamp, message_body,
+    participant_uri, message_id, is_current_user, language,
+  }
+  vx_event_type_evt_session_archive_query_end => vx_evt_session_archive_query_end {
+    sessiongroup_handle, session_handle, query_id, return_code, status_code,
+    first_id, last_id, first_index, count,
+  }
+  vx_event_type_evt_account_archive_message => vx_evt_account_archive_message {
+    account_handle, query_id, time_stamp, channel_uri, message_body,
+    participant_uri, message_id, is_inbound, language,
+  }
+  vx_event_type_evt_account_archive_query_end => vx_evt_account_archive_query_end {
+    account_handle, query_id, return_code, status_code, first_id, last_id,
+    first_index, count,
+  }
+  vx_event_type_evt_buddy_changed => vx_evt_buddy_changed {
+    account_handle, change_type, buddy_uri, display_name, buddy_data, group_id,
+  }
+  vx_event_type_evt_buddy_presence => vx_evt_buddy_presence {
+    presence, account_handle, buddy_uri, custom_message, displayname,
+    application,
+  }
+  vx_event_type_evt_subscription => vx_evt_subscription {
+    account_handle, buddy_uri, subscription_handle, subscription_type,
+    displayname, application, message,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::enums::{LoginState, VivoxError};
+
+  #[test]
+  fn decodes_captured_events() {
+    use std::mem;
+
+    let mut arena = Arena::default();
+    let mut evt: vx_evt_account_login_state_change = unsafe { mem::zeroed() };
+
+    evt.base.message.type_ = vx_message_type_msg_event;
+    evt.base.type_ = vx_event_type_evt_account_login_state_change;
+    evt.account_handle = Field::load(&Value::from("a1"), &mut arena).unwrap();
+    evt.state = vx_login_state_change_state_login_state_logged_in;
+
+    let raw = unsafe { RawEvent::capture(&evt.base) }.unwrap();
+    let json = serde_json::to_string(&raw).unwrap();
+
+    assert_eq!(raw.event_type, "evt_account_login_state_change");
+    assert_eq!(serde_json::from_str::<RawEvent>(&json).unwrap(), raw);
+    match raw.decode() {
+      Some(Event::LoginStateChanged { account_handle, state, status }) => {
+        assert_eq!(account_handle, "a1");
+        assert_eq!(state, LoginState::LoggedIn);
+        assert_eq!(status, VivoxError::SUCCESS);
+      },
+      other => panic!("decoded {:?}", other),
+    }
+  }
+}
@@ -0,0 +1,276 @@
+//! Recording the traffic between a `VivoxClient` and the SDK, and playing
+//! it back without one.
+//!
+//! A recording is a JSON Lines file with one `Entry` per line, in the order
+//! the client saw them. Replaying it through a fresh client that makes the
+//! same calls reproduces the same state changes and events, since handles
+//! and cookies are counted per client.
+//!
+//! Requests and SDK events are kept as `raw` copies of their structs, so a
+//! recording still replays after the wrapper learns to decode more of an
+//! event, and a replayed request must match the recorded one field by field.
+
+use crate::backend::{Message, Request, VivoxBackend};
+use crate::client::{InitError, Response};
+use crate::enums::VivoxError;
+use crate::events::Event;
+use crate::raw::{RawEvent, RawRequest};
+use crate::version::SdkVersion;
+
+use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// One line of a recording.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Entry {
+  /// A request issued by the client
+  Request {
+    cookie: String,
+    #[serde(flatten)]
+    request: RawRequest,
+  },
+  Response { cookie: String, response: Response },
+  /// An event delivered by the SDK, decoded again on replay
+  RawEvent {
+    #[serde(flatten)]
+    event: RawEvent,
+  },
+  /// An event that did not come from the SDK, such as an injected fault
+  Event { event: Event },
+}
+
+impl From<Message> for Entry {
+  fn from(msg: Message) -> Self {
+    match msg {
+      Message::Response { cookie, response } => Entry::Response { cookie, response },
+      Message::Event(_, Some(event)) => Entry::RawEvent { event },
+      Message::Event(event, None) => Entry::Event { event },
+    }
+  }
+}
+
+/// Wraps a backend and writes everything passing through it to `out`.
+pub struct Recorder<B> {
+  inner: B,
+  out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl<B: VivoxBackend> Recorder<B> {
+  pub fn new<W: Write + Send + 'static>(inner: B, out: W) -> Self {
+    Self {
+      inner,
+      out: Mutex::new(Box::new(out)),
+    }
+  }
+
+  /// Records to a new file at `path`, replacing any existing one.
+  ///
+  /// Every entry is flushed as it is written, so the recording survives a
+  /// crash of the game.
+  pub fn create<P: AsRef<Path>>(inner: B, path: P) -> io::Result<Self> {
+    Ok(Self::new(inner, LineWriter::new(File::create(path)?)))
+  }
+
+  fn record(&self, entry: &Entry) {
+    let mut out = self.out.lock().unwrap();
+
+    // A broken recording must not take voice down with it
+    let _ = serde_json::to_writer(&mut *out, entry)
+      .map_err(io::Error::from)
+      .and_then(|_| out.write_all(b"\n"));
+  }
+}
+
+impl<B: VivoxBackend> VivoxBackend for Recorder<B> {
+  fn initialize(&self) -> Result<(), InitError> {
+    self.inner.initialize()
+  }
+
+  fn uninitialize(&self) {
+    self.inner.uninitialize();
+
+    let _ = self.out.lock().unwrap().flush();
+  }
+
+  fn issue(&self, req: Request) -> Result<(), VivoxError> {
+    let cookie = req.cookie();
+
+    self.record(&Entry::Request {
+      cookie: cookie.clone(),
+      request: req.raw(),
+    });
+
+    let result = self.inner.issue(req);
+
+    // Replays answer the request the way the client saw it fail
+    if let Err(status) = result {
+      self.record(&Entry::Response {
+        cookie,
+        response: Err(status),
+      });
+    }
+
+    result
+  }
+
+  fn poll(&self) -> Option<Message> {
+    let msg = self.inner.poll()?;
+
+    self.record(&Entry::from(msg.clone()));
+
+    Some(msg)
+  }
+
+  fn sdk_version(&self) -> Option<SdkVersion> {
+    self.inner.sdk_version()
+  }
+
+  fn drop_session(&self, session_handle: &str, status: VivoxError) -> bool {
+    self.inner.drop_session(session_handle, status)
+  }
+
+  fn drop_account(&self, account_handle: &str, status: VivoxError) -> bool {
+    self.inner.drop_account(account_handle, status)
+  }
+}
+
+/// Plays a recording back in place of the SDK.
+///
+/// Responses and events are delivered in their recorded order, each batch
+/// once the client issued the request recorded before it. A request that
+/// does not match the recording fails with `INVALID_ARGUMENT` and stops
+/// the playback, see `divergence`.
+///
+/// Clones share the playback, so a test can keep one to inspect it after
+/// handing the other to `VivoxClient::with_backend`.
+#[derive(Clone)]
+pub struct Replay {
+  state: Arc<Mutex<Playback>>,
+}
+
+struct Playback {
+  entries: VecDeque<Entry>,
+  divergence: Option<String>,
+}
+
+impl Replay {
+  pub fn new<I: IntoIterator<Item = Entry>>(entries: I) -> Self {
+    Self {
+      state: Arc::new(Mutex::new(Playback {
+        entries: entries.into_iter().collect(),
+        divergence: None,
+      })),
+    }
+  }
+
+  pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    Self::from_reader(BufReader::new(File::open(path)?))
+  }
+
+  /// Reads a recording, skipping blank lines.
+  pub fn from_reader<R: BufRead>(input: R) -> io::Result<Self> {
+    let mut entries = Vec::new();
+
+    for (n, line) in input.lines().enumerate() {
+      let line = line?;
+
+      if line.trim().is_empty() {
+        continue;
+      }
+
+      let entry = serde_json::from_str(&line).map_err(|e| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", n + 1, e))
+      })?;
+
+      entries.push(entry);
+    }
+
+    Ok(Self::new(entries))
+  }
+
+  /// Entries not played back yet.
+  pub fn remaining(&self) -> usize {
+    self.state.lock().unwrap().entries.len()
+  }
+
+  pub fn is_finished(&self) -> bool {
+    self.remaining() == 0
+  }
+
+  /// Describes the first request that did not match the recording.
+  pub fn divergence(&self) -> Option<String> {
+    self.state.lock().unwrap().divergence.clone()
+  }
+}
+
+impl VivoxBackend for Replay {
+  fn initialize(&self) -> Result<(), InitError> {
+    Ok(())
+  }
+
+  fn uninitialize(&self) {}
+
+  fn issue(&self, req: Request) -> Result<(), VivoxError> {
+    let mut state = self.state.lock().unwrap();
+    let cookie = req.cookie();
+    let request = req.raw();
+
+    if state.divergence.is_some() {
+      return Err(VivoxError::INVALID_ARGUMENT);
+    }
+
+    match state.entries.front() {
+      Some(Entry::Request { cookie: expected, request: recorded })
+        if *expected == cookie && *recorded == request =>
+      {
+        state.entries.pop_front();
+
+        Ok(())
+      },
+      next => {
+        state.divergence = Some(format!(
+          "issued {} as {:?}, the recording continues with {:?}",
+          cookie,
+          request,
+          next,
+        ));
+
+        Err(VivoxError::INVALID_ARGUMENT)
+      },
+    }
+  }
+
+  fn poll(&self) -> Option<Message> {
+    let mut state = self.state.lock().unwrap();
+
+    if state.divergence.is_some() {
+      return None;
+    }
+
+    loop {
+      match state.entries.pop_front()? {
+        Entry::Response { cookie, response } => {
+          return Some(Message::Response { cookie, response });
+        },
+        Entry::RawEvent { event } => {
+          // Skipped like the SDK events the wrapper does not know about
+          if let Some(decoded) = event.decode() {
+            return Some(Message::Event(decoded, Some(event)));
+          }
+        },
+        Entry::Event { event } => return Some(Message::Event(event, None)),
+        // Nothing more until the client issues it
+        req => {
+          state.entries.push_front(req);
+
+          return None;
+        },
+      }
+    }
+  }
+}
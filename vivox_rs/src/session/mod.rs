@@ -0,0 +1,599 @@
+use crate::account::Account;
+use crate::archive::{self, ArchiveQuery, ArchivedMessage, Scope};
+use crate::client::VivoxClient;
+use crate::enums::{MediaState, TextState, VivoxError};
+use crate::events::Event;
+use crate::helpers::*;
+use crate::messages::{ChannelMessage, CustomMetadata, MessageHistory, HISTORY_CAPACITY};
+use crate::participant::{Participant, Roster, RosterChange};
+use crate::positional::{Position3d, PositionThrottle, Schedule, POSITION_INTERVAL};
+use crate::speaking::{self, SpeakingConfig, SpeakingEvent};
+use crate::uri::{ChannelType, ChannelUri};
+
+use futures::Stream;
+use std::future::Future;
+use std::os::raw::c_int;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc, watch};
+use vivox_rs_sys::*;
+
+/// Audio and text connection state of a session.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StreamState {
+  pub media: MediaState,
+  pub text: TextState,
+  /// Reason for the last disconnect, `SUCCESS` when it was requested.
+  pub status: VivoxError,
+}
+
+/// Something that happened to the local user in a session.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionEvent {
+  /// A moderator kicked the local user out of the channel
+  Kicked,
+  /// The local user is banned from the channel
+  Banned,
+  /// The call ended for any other reason than a requested disconnect
+  Terminated(VivoxError),
+}
+
+/// A channel joined through a `SessionGroup`.
+///
+/// Audio and text can be connected and disconnected independently for as
+/// long as the session exists.
+#[derive(Clone)]
+pub struct Session {
+  client: VivoxClient,
+  account: Account,
+  sessiongroup_handle: String,
+  handle: String,
+  uri: String,
+  channel_type: Option<ChannelType>,
+  state: watch::Receiver<StreamState>,
+  roster: Arc<Roster>,
+  events: broadcast::Sender<SessionEvent>,
+  positions: Arc<Mutex<PositionThrottle>>,
+  history: Arc<Mutex<MessageHistory>>,
+  messages: broadcast::Sender<ChannelMessage>,
+}
+
+impl Session {
+  pub fn handle(&self) -> &str {
+    &self.handle
+  }
+
+  pub fn sessiongroup_handle(&self) -> &str {
+    &self.sessiongroup_handle
+  }
+
+  pub fn uri(&self) -> &str {
+    &self.uri
+  }
+
+  /// Kind of channel joined, `None` when the URI isn't a channel URI.
+  pub fn channel_type(&self) -> Option<ChannelType> {
+    self.channel_type
+  }
+
+  /// Current stream state. The receiver also resolves on every change.
+  pub fn state(&self) -> watch::Receiver<StreamState> {
+    self.state.clone()
+  }
+
+  /// Subscribes to kicks, bans and other unrequested disconnects.
+  pub fn events(&self) -> broadcast::Receiver<SessionEvent> {
+    self.events.subscribe()
+  }
+
+  /// Subscribes to text messages posted to the channel from now on.
+  pub fn messages(&self) -> broadcast::Receiver<ChannelMessage> {
+    self.messages.subscribe()
+  }
+
+  /// The last messages received in this session, oldest first.
+  pub fn history(&self) -> Vec<ChannelMessage> {
+    self.history.lock().unwrap().snapshot()
+  }
+
+  /// Searches the server side history of this channel, oldest first.
+  /// Text has to be connected. Must be called from within a Tokio runtime.
+  pub fn archive(&self, query: ArchiveQuery) -> impl Stream<Item = Result<ArchivedMessage, VivoxError>> {
+    let scope = Scope::Session(self.handle.clone(), self.uri.clone());
+
+    archive::query(self.client.clone(), self.account.clone(), scope, query)
+  }
+
+  /// Posts a text message to the channel. Text has to be connected,
+  /// otherwise it fails with `SESSION_DOES_NOT_HAVE_TEXT`.
+  ///
+  /// Fails with `SESSION_TEXT_DENIED` when the server refuses the message,
+  /// for example because the user is text muted, and with `TEXT_DISABLED`
+  /// when text is turned off for the application.
+  pub fn send_message(
+    &self,
+    body: &str,
+    language: Option<&str>,
+    custom_metadata: Option<&CustomMetadata>,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    use std::mem;
+
+    unsafe {
+      let mut req: *mut vx_req_session_send_message = mem::zeroed();
+
+      vx_req_session_send_message_create(&mut req);
+
+      (*req).session_handle = strdup(&self.handle);
+      (*req).message_body = strdup(body);
+
+      if let Some(language) = language {
+        (*req).language = strdup(language);
+      }
+
+      if let Some(metadata) = custom_metadata {
+        (*req).application_stanza_namespace = strdup(&metadata.namespace);
+        (*req).application_stanza_body = strdup(&metadata.body);
+      }
+
+      self.client.issue(&mut (*req).base)
+    }
+  }
+
+  /// Everyone currently in the session, ordered by URI.
+  pub fn roster(&self) -> Vec<Participant> {
+    self.roster.snapshot()
+  }
+
+  pub fn participant(&self, uri: &str) -> Option<Participant> {
+    self.roster.get(uri)
+  }
+
+  pub(crate) fn shared_roster(&self) -> Arc<Roster> {
+    self.roster.clone()
+  }
+
+  /// Subscribes to roster changes made after this call.
+  pub fn roster_changes(&self) -> broadcast::Receiver<RosterChange> {
+    self.roster.subscribe()
+  }
+
+  /// Debounced speaking transitions and energy levels of everyone in the
+  /// session. Must be called from within a Tokio runtime.
+  pub fn speaking(&self, config: SpeakingConfig) -> mpsc::Receiver<SpeakingEvent> {
+    speaking::spawn_filter(self.roster.clone(), config)
+  }
+
+  /// Kicks a participant out of this channel. Requires the account to
+  /// moderate the channel and a token as described on `Account::kick_user`.
+  pub fn kick(
+    &self,
+    participant_uri: &str,
+    access_token: &str,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    self.account.kick_user(&self.uri, participant_uri, access_token)
+  }
+
+  /// Mutes or unmutes a participant for everyone in this channel.
+  pub fn moderator_mute(
+    &self,
+    participant_uri: &str,
+    muted: bool,
+    access_token: &str,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    self.account.mute_user(&self.uri, participant_uri, muted, access_token)
+  }
+
+  /// Mutes or unmutes everyone in this channel except moderators.
+  pub fn moderator_mute_all(
+    &self,
+    muted: bool,
+    access_token: &str,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    self.account.mute_all_users(&self.uri, muted, access_token)
+  }
+
+  /// Mutes or unmutes a participant for the local user only.
+  pub fn set_participant_mute_for_me(
+    &self,
+    participant_uri: &str,
+    mute: bool,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    use std::mem;
+
+    let pending = unsafe {
+      let mut req: *mut vx_req_session_set_participant_mute_for_me = mem::zeroed();
+
+      vx_req_session_set_participant_mute_for_me_create(&mut req);
+
+      (*req).session_handle = strdup(&self.handle);
+      (*req).participant_uri = strdup(participant_uri);
+      (*req).mute = mute as c_int;
+
+      self.client.issue(&mut (*req).base)
+    };
+    let roster = self.roster.clone();
+    let participant_uri = participant_uri.to_string();
+
+    async move {
+      pending.await?;
+      roster.modify(&participant_uri, |participant| participant.is_muted_for_me = mute);
+
+      Ok(())
+    }
+  }
+
+  /// Sets how loud a participant plays back locally, from 0 to 100 where
+  /// 50 leaves the volume unchanged.
+  pub fn set_participant_volume_for_me(
+    &self,
+    participant_uri: &str,
+    volume: i32,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    use std::mem;
+
+    let pending = match check_volume(volume) {
+      Ok(()) => unsafe {
+        let mut req: *mut vx_req_session_set_participant_volume_for_me = mem::zeroed();
+
+        vx_req_session_set_participant_volume_for_me_create(&mut req);
+
+        (*req).session_handle = strdup(&self.handle);
+        (*req).participant_uri = strdup(participant_uri);
+        (*req).volume = volume;
+
+        Ok(self.client.issue(&mut (*req).base))
+      },
+      Err(e) => Err(e),
+    };
+    let roster = self.roster.clone();
+    let participant_uri = participant_uri.to_string();
+
+    async move {
+      pending?.await?;
+      roster.modify(&participant_uri, |participant| participant.volume = volume);
+
+      Ok(())
+    }
+  }
+
+  /// Sets the playback volume of the whole session, from 0 to 100 where 50
+  /// leaves the volume unchanged.
+  pub fn set_local_render_volume(&self, volume: i32) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    let pending = match check_volume(volume) {
+      Ok(()) => unsafe {
+        let mut req: *mut vx_req_session_set_local_render_volume = mem::zeroed();
+
+        vx_req_session_set_local_render_volume_create(&mut req);
+
+        (*req).session_handle = strdup(&self.handle);
+        (*req).volume = volume;
+
+        Ok(self.client.issue(&mut (*req).base))
+      },
+      Err(e) => Err(e),
+    };
+
+    async move {
+      pending?.await
+    }
+  }
+
+  /// Moves the local user in a positional channel.
+  ///
+  /// Meant to be called every frame: positions equal to the last one are
+  /// skipped, and updates arriving faster than the SDK accepts them are
+  /// delayed so only the most recent one is sent. The update goes out even
+  /// when the returned future is dropped. Fails with `SESSION_IS_NOT_3D` on
+  /// channels without positional audio. Must be called from within a Tokio
+  /// runtime.
+  pub fn set_3d_position(
+    &self,
+    speaker: Position3d,
+    listener: Position3d,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    use std::time::Instant;
+    use tokio::time::{delay_until, Instant as TokioInstant};
+
+    let schedule = match self.channel_type == Some(ChannelType::Positional) {
+      true => Ok(self.positions.lock().unwrap().schedule(speaker, listener, Instant::now())),
+      false => Err(VivoxError::SESSION_IS_NOT_3D),
+    };
+    let client = self.client.clone();
+    let handle = self.handle.clone();
+    let positions = self.positions.clone();
+
+    // The throttle already counts the position as sent, so the send must
+    // not depend on the caller polling
+    let send = schedule.map(|schedule| match schedule {
+      Schedule::Unchanged => None,
+      Schedule::SendAt(when, generation) => Some(tokio::spawn(async move {
+        delay_until(TokioInstant::from_std(when)).await;
+
+        if !positions.lock().unwrap().take(generation, Instant::now()) {
+          return Ok(());
+        }
+
+        let result = issue_3d_position(&client, &handle, &speaker, &listener).await;
+
+        if result.is_err() {
+          positions.lock().unwrap().failed(generation);
+        }
+
+        result
+      })),
+    });
+
+    async move {
+      match send? {
+        Some(send) => send.await.unwrap_or(Err(VivoxError::REQUEST_CANCELLED)),
+        None => Ok(()),
+      }
+    }
+  }
+
+  pub fn connect_audio(&self) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    unsafe {
+      let mut req: *mut vx_req_session_media_connect = mem::zeroed();
+
+      vx_req_session_media_connect_create(&mut req);
+
+      (*req).sessiongroup_handle = strdup(&self.sessiongroup_handle);
+      (*req).session_handle = strdup(&self.handle);
+
+      self.client.issue(&mut (*req).base)
+    }
+  }
+
+  /// Fails with `MEDIA_DISCONNECT_NOT_ALLOWED` when the SDK refuses to
+  /// drop audio in the session's current state.
+  pub fn disconnect_audio(&self) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    unsafe {
+      let mut req: *mut vx_req_session_media_disconnect = mem::zeroed();
+
+      vx_req_session_media_disconnect_create(&mut req);
+
+      (*req).sessiongroup_handle = strdup(&self.sessiongroup_handle);
+      (*req).session_handle = strdup(&self.handle);
+
+      self.client.issue(&mut (*req).base)
+    }
+  }
+
+  pub fn connect_text(&self) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    unsafe {
+      let mut req: *mut vx_req_session_text_connect = mem::zeroed();
+
+      vx_req_session_text_connect_create(&mut req);
+
+      (*req).sessiongroup_handle = strdup(&self.sessiongroup_handle);
+      (*req).session_handle = strdup(&self.handle);
+
+      self.client.issue(&mut (*req).base)
+    }
+  }
+
+  /// Fails with `TEXT_DISCONNECT_NOT_ALLOWED` when the SDK refuses to
+  /// drop text in the session's current state.
+  pub fn disconnect_text(&self) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    unsafe {
+      let mut req: *mut vx_req_session_text_disconnect = mem::zeroed();
+
+      vx_req_session_text_disconnect_create(&mut req);
+
+      (*req).sessiongroup_handle = strdup(&self.sessiongroup_handle);
+      (*req).session_handle = strdup(&self.handle);
+
+      self.client.issue(&mut (*req).base)
+    }
+  }
+}
+
+const EVENT_CAPACITY: usize = 16;
+
+const MESSAGE_CAPACITY: usize = 64;
+
+fn issue_3d_position(
+  client: &VivoxClient,
+  session_handle: &str,
+  speaker: &Position3d,
+  listener: &Position3d,
+) -> impl Future<Output = Result<(), VivoxError>>
+{
+  use std::mem;
+
+  unsafe {
+    let mut req: *mut vx_req_session_set_3d_position = mem::zeroed();
+
+    vx_req_session_set_3d_position_create(&mut req);
+
+    (*req).session_handle = strdup(session_handle);
+    (*req).type_ = req_disposition_type_t_req_disposition_replace_previous;
+
+    (*req).speaker_position = speaker.position.to_array();
+    (*req).speaker_velocity = speaker.velocity.to_array();
+    (*req).speaker_at_orientation = speaker.orientation.at.to_array();
+    (*req).speaker_up_orientation = speaker.orientation.up.to_array();
+    (*req).speaker_left_orientation = speaker.orientation.left().to_array();
+
+    (*req).listener_position = listener.position.to_array();
+    (*req).listener_velocity = listener.velocity.to_array();
+    (*req).listener_at_orientation = listener.orientation.at.to_array();
+    (*req).listener_up_orientation = listener.orientation.up.to_array();
+    (*req).listener_left_orientation = listener.orientation.left().to_array();
+
+    client.issue(&mut (*req).base)
+  }
+}
+
+/// Keeps a session's state up to date from the owning account's events.
+pub(crate) struct SessionTracker {
+  handle: String,
+  sessiongroup_handle: String,
+  current: StreamState,
+  state: watch::Sender<StreamState>,
+  roster: Arc<Roster>,
+  events: broadcast::Sender<SessionEvent>,
+  history: Arc<Mutex<MessageHistory>>,
+  messages: broadcast::Sender<ChannelMessage>,
+  // Media and text both report the same termination, only surface it once
+  terminated: bool,
+}
+
+impl SessionTracker {
+  pub(crate) fn new(
+    client: VivoxClient,
+    account: Account,
+    sessiongroup_handle: String,
+    handle: String,
+    uri: String,
+  ) -> (Session, Self) {
+    let current = StreamState {
+      media: MediaState::Disconnected,
+      text: TextState::Disconnected,
+      status: VivoxError::SUCCESS,
+    };
+    let (tx, rx) = watch::channel(current);
+    let roster = Arc::new(Roster::new());
+    let (events, _) = broadcast::channel(EVENT_CAPACITY);
+    let history = Arc::new(Mutex::new(MessageHistory::new(HISTORY_CAPACITY)));
+    let (messages, _) = broadcast::channel(MESSAGE_CAPACITY);
+
+    let session = Session {
+      client,
+      account,
+      sessiongroup_handle: sessiongroup_handle.clone(),
+      handle: handle.clone(),
+      channel_type: ChannelUri::parse(&uri).ok().map(|channel| channel.channel_type()),
+      uri,
+      state: rx,
+      roster: roster.clone(),
+      events: events.clone(),
+      positions: Arc::new(Mutex::new(PositionThrottle::new(POSITION_INTERVAL))),
+      history: history.clone(),
+      messages: messages.clone(),
+    };
+    let tracker = Self {
+      handle,
+      sessiongroup_handle,
+      current,
+      state: tx,
+      roster,
+      events,
+      history,
+      messages,
+      terminated: false,
+    };
+
+    (session, tracker)
+  }
+
+  /// Consumes events until the session is removed.
+  pub(crate) async fn run(mut self, mut rx: broadcast::Receiver<Event>) {
+    loop {
+      match rx.recv().await {
+        Ok(evt) => {
+          if !self.apply(&evt) {
+            break;
+          }
+        },
+        Err(broadcast::RecvError::Lagged(_)) => {},
+        Err(broadcast::RecvError::Closed) => break,
+      }
+    }
+  }
+
+  /// Applies an event. Returns `false` once the session is gone.
+  fn apply(&mut self, evt: &Event) -> bool {
+    match evt {
+      Event::MediaStreamUpdated { session_handle, state, status, .. } if *session_handle == self.handle => {
+        self.current.media = *state;
+        self.record_status(*state == MediaState::Disconnected, *status);
+      },
+      Event::TextStreamUpdated { session_handle, state, status, .. } if *session_handle == self.handle => {
+        self.current.text = *state;
+        self.record_status(*state == TextState::Disconnected, *status);
+      },
+      Event::ParticipantAdded { session_handle, .. }
+      | Event::ParticipantUpdated { session_handle, .. }
+      | Event::ParticipantRemoved { session_handle, .. }
+      | Event::TypingChanged { session_handle, .. } if *session_handle == self.handle => {
+        self.roster.apply(evt);
+
+        return true;
+      },
+      Event::MessageReceived { message, .. } if message.session_handle == self.handle => {
+        self.history.lock().unwrap().push(message.clone());
+        let _ = self.messages.send(message.clone());
+
+        return true;
+      },
+      Event::SessionRemoved { session_handle, .. } if *session_handle == self.handle => {
+        self.disconnected();
+
+        return false;
+      },
+      Event::SessionGroupRemoved { sessiongroup_handle } if *sessiongroup_handle == self.sessiongroup_handle => {
+        self.disconnected();
+
+        return false;
+      },
+      _ => return true,
+    }
+
+    let _ = self.state.broadcast(self.current);
+
+    true
+  }
+
+  /// Keeps the reason of the last disconnect, later updates on the way
+  /// back up report `SUCCESS` as well.
+  fn record_status(&mut self, disconnected: bool, status: VivoxError) {
+    if disconnected {
+      self.current.status = status;
+    }
+
+    self.check_termination(status);
+  }
+
+  fn check_termination(&mut self, status: VivoxError) {
+    let evt = match status {
+      VivoxError::SUCCESS => {
+        self.terminated = false;
+
+        return;
+      },
+      VivoxError::CALL_TERMINATED_KICK => SessionEvent::Kicked,
+      VivoxError::CALL_TERMINATED_BAN => SessionEvent::Banned,
+      other => SessionEvent::Terminated(other),
+    };
+
+    if !self.terminated {
+      self.terminated = true;
+      let _ = self.events.send(evt);
+    }
+  }
+
+  fn disconnected(&mut self) {
+    self.current.media = MediaState::Disconnected;
+    self.current.text = TextState::Disconnected;
+
+    let _ = self.state.broadcast(self.current);
+    self.roster.clear();
+  }
+}
@@ -1,18 +1,29 @@
+use crate::account::Account;
+use crate::client::VivoxClient;
+use crate::enums::VivoxError;
 use crate::helpers::*;
+use crate::session::{Session, SessionTracker};
+use crate::speaking::{self, Membership, SpeakingConfig, VoiceActivity};
 use crate::tokengen::TokenGenerator;
+use crate::transmission::TransmissionController;
+use crate::uri::UserUri;
 
+use std::collections::HashMap;
 use std::ffi::CStr;
+use std::future::Future;
 use std::os::raw::c_int;
-use std::time::SystemTime;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, watch};
 use vivox_rs_sys::*;
 
-pub struct AddSession<'u> {
+pub struct AddSession {
   req_ptr: *mut vx_req_sessiongroup_add_session,
-  uri: &'u str,
+  uri: String,
+  acct_name: String,
 }
 
 #[allow(dead_code)]
-impl<'a> AddSession<'a> {
+impl AddSession {
   pub fn new() -> Self {
     use std::mem;
 
@@ -21,7 +32,8 @@ impl<'a> AddSession<'a> {
     unsafe {
       new_req = Self {
         req_ptr: mem::zeroed(),
-        uri: "",
+        uri: String::new(),
+        acct_name: String::new(),
       };
 
       vx_req_sessiongroup_add_session_create(&mut new_req.req_ptr);
@@ -30,39 +42,47 @@ impl<'a> AddSession<'a> {
     new_req
   }
 
-  pub fn account_handle(&'a mut self, input: &str) -> &'a mut Self {
+  pub fn account_handle<'a>(&'a mut self, input: &str) -> &'a mut Self {
     unsafe { (*self.req_ptr).account_handle = strdup(input); }
 
     self
   }
 
+  /// Name the access token is issued for. Defaults to the account handle.
+  pub fn acct_name<'a>(&'a mut self, input: &str) -> &'a mut Self {
+    self.acct_name = input.to_string();
+
+    self
+  }
+
+  /// Signs a join token with `key`, see `AnonymousLogin::access_token`.
   pub fn access_token(
-    &'a mut self,
+    &mut self,
+    key: &str,
     issuer: &str,
     domain: &str,
     generator: &TokenGenerator,
-  ) -> &'a mut Self
+  ) -> &mut Self
   {
-    if self.uri == "" {
+    if self.uri.is_empty() {
       panic!("uri not set prior to requesting access token!");
     }
 
     unsafe {
       (*self.req_ptr).access_token = strdup(
         &generator.generate(
-          "get_your_own!",
+          key,
           issuer,
-          SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("Back from the future")
-            .as_secs() + 120,
+          token_expiry(),
           "join",
-          &format!(
-            "sip:{}@{}",
-            CStr::from_ptr((*self.req_ptr).account_handle).to_str().unwrap(),
+          &UserUri::new(
+            match self.acct_name.is_empty() {
+              true => CStr::from_ptr((*self.req_ptr).account_handle).to_str().unwrap(),
+              false => &self.acct_name,
+            },
             domain,
-          ),
-          Some(self.uri.to_string()),
+          ).to_string(),
+          Some(self.uri.clone()),
         )
       );
     }
@@ -70,41 +90,49 @@ impl<'a> AddSession<'a> {
     self
   }
 
-  pub fn connect_audio(&'a mut self, input: c_int) -> &'a mut Self {
+  /// Uses a token minted elsewhere, see
+  /// `AnonymousLogin::signed_access_token`.
+  pub fn signed_access_token(&mut self, token: &str) -> &mut Self {
+    unsafe { (*self.req_ptr).access_token = strdup(token); }
+
+    self
+  }
+
+  pub fn connect_audio<'a>(&'a mut self, input: c_int) -> &'a mut Self {
     unsafe { (*self.req_ptr).connect_audio = input; }
 
     self
   }
 
-  pub fn connect_text(&'a mut self, input: c_int) -> &'a mut Self {
+  pub fn connect_text<'a>(&'a mut self, input: c_int) -> &'a mut Self {
     unsafe { (*self.req_ptr).connect_text = input; }
 
     self
   }
 
-  pub fn issue(&'a mut self) {
+  pub fn issue<'a>(&'a mut self) {
     unsafe { vx_issue_request(&mut (*self.req_ptr).base); }
   }
 
-  pub fn jitter_compensation(&'a mut self, input: c_int) -> &'a mut Self {
+  pub fn jitter_compensation<'a>(&'a mut self, input: c_int) -> &'a mut Self {
     unsafe { (*self.req_ptr).jitter_compensation = input; }
 
     self
   }
 
-  pub fn name(&'a mut self, input: &str) -> &'a mut Self {
+  pub fn name<'a>(&'a mut self, input: &str) -> &'a mut Self {
     unsafe { (*self.req_ptr).name = strdup(input); }
 
     self
   }
 
-  pub fn password(&'a mut self, input: &str) -> &'a mut Self {
+  pub fn password<'a>(&'a mut self, input: &str) -> &'a mut Self {
     unsafe { (*self.req_ptr).password = strdup(input); }
 
     self
   }
 
-  pub fn password_hash_algorithm(
+  pub fn password_hash_algorithm<'a>(
     &'a mut self,
     input: vx_password_hash_algorithm_t
   ) -> &'a mut Self {
@@ -113,47 +141,335 @@ impl<'a> AddSession<'a> {
     self
   }
 
-  pub fn session_font_id(&'a mut self, input: c_int) -> &'a mut Self {
+  pub fn session_font_id<'a>(&'a mut self, input: c_int) -> &'a mut Self {
     unsafe { (*self.req_ptr).session_font_id = input; }
 
     self
   }
 
-  pub fn session_handle(&'a mut self, input: &str) -> &'a mut Self {
+  pub fn session_handle<'a>(&'a mut self, input: &str) -> &'a mut Self {
     unsafe { (*self.req_ptr).session_handle = strdup(input); }
 
     self
   }
 
-  pub fn sessiongroup_handle(&'a mut self, input: &str) -> &'a mut Self {
+  pub fn sessiongroup_handle<'a>(&'a mut self, input: &str) -> &'a mut Self {
     unsafe { (*self.req_ptr).sessiongroup_handle = strdup(input); }
 
     self
   }
 
-  pub fn uri(&'a mut self, input: &'a str) -> &'a mut Self {
+  /// Channel to join. Fails with `INVALID_ARGUMENT` when the channel name
+  /// has characters Vivox doesn't allow, and `CHANNEL_URI_TOO_LONG` when it
+  /// is longer than `MAX_CHANNEL_URI_LENGTH`.
+  pub fn uri(&mut self, input: &str) -> Result<&mut Self, VivoxError> {
     // Tests without "sip:" and "@vdx5..."
-    let start = input.find(':').unwrap_or(0) + 1;
-    let end = input.find('@').unwrap_or(1) - 1;
-    let sub = &input[start..end];
-
-    let test = sub
-      .chars()
-      .all(|c|
-        c.is_alphanumeric()
-        || is_valid_non_alphanumeric(&c)
-      )
-      && sub.len() <= MAX_CHANNEL_URI_LENGTH as usize;
+    let start = input.find(':').map(|colon| colon + 1).unwrap_or(0);
+    let end = input.find('@').unwrap_or(input.len());
+    let sub = input.get(start..end).unwrap_or("");
+
+    let valid = !sub.is_empty()
+      && sub
+        .chars()
+        .all(|c|
+          c.is_alphanumeric()
+          || is_valid_non_alphanumeric(&c)
+        );
+
+    if !valid {
+      return Err(VivoxError::INVALID_ARGUMENT);
+    }
 
-    if !test {
-      panic!("URI validation failed!");
+    if sub.len() > MAX_CHANNEL_URI_LENGTH as usize {
+      return Err(VivoxError::CHANNEL_URI_TOO_LONG);
     }
 
     // Store locally for access_token()
-    self.uri = input;
+    self.uri = input.to_string();
 
     unsafe { (*self.req_ptr).uri = strdup(input); }
 
-    self
+    Ok(self)
+  }
+
+  pub(crate) fn base(&mut self) -> *mut vx_req_base_t {
+    unsafe { &mut (*self.req_ptr).base }
+  }
+
+  pub(crate) fn get_uri(&self) -> String {
+    self.uri.clone()
+  }
+
+  /// Session group and session handles, in that order.
+  pub(crate) fn handles(&self) -> (String, String) {
+    unsafe {
+      (
+        to_owned_string((*self.req_ptr).sessiongroup_handle),
+        to_owned_string((*self.req_ptr).session_handle),
+      )
+    }
+  }
+}
+
+/// Where a session group sends the local microphone.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Transmit {
+  None,
+  All,
+  Session(String),
+}
+
+struct GroupState {
+  sessions: HashMap<String, Session>,
+  transmitting: Transmit,
+  // Voice activity maps following the sessions
+  followers: Vec<mpsc::UnboundedSender<Membership>>,
+}
+
+impl GroupState {
+  fn notify(&mut self, change: Membership) {
+    self.followers.retain(|tx| tx.send(change.clone()).is_ok());
+  }
+}
+
+/// A set of sessions joined together by one account.
+///
+/// Every session in the group plays back at the same time, but the local
+/// microphone is only transmitted to the sessions selected with
+/// `transmit_to`.
+#[derive(Clone)]
+pub struct SessionGroup {
+  client: VivoxClient,
+  account: Account,
+  handle: String,
+  state: Arc<Mutex<GroupState>>,
+}
+
+impl SessionGroup {
+  pub(crate) fn new(client: VivoxClient, account: Account) -> Self {
+    let handle = client.next_handle("sg");
+
+    Self {
+      client,
+      account,
+      handle,
+      state: Arc::new(Mutex::new(GroupState {
+        sessions: HashMap::new(),
+        // The SDK transmits to every session until told otherwise
+        transmitting: Transmit::All,
+        followers: Vec::new(),
+      })),
+    }
+  }
+
+  pub fn handle(&self) -> &str {
+    &self.handle
+  }
+
+  /// Sessions currently in the group.
+  pub fn sessions(&self) -> Vec<Session> {
+    self.state.lock().unwrap()
+      .sessions
+      .values()
+      .cloned()
+      .collect()
+  }
+
+  pub fn session(&self, session_handle: &str) -> Option<Session> {
+    self.state.lock().unwrap()
+      .sessions
+      .get(session_handle)
+      .cloned()
+  }
+
+  pub fn transmitting(&self) -> Transmit {
+    self.state.lock().unwrap().transmitting.clone()
+  }
+
+  /// Speaking state of everyone in the group's sessions, keyed by user URI.
+  ///
+  /// Follows sessions joined and removed later on. Must be called from
+  /// within a Tokio runtime.
+  pub fn voice_activity(&self, config: SpeakingConfig) -> watch::Receiver<HashMap<String, VoiceActivity>> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut state = self.state.lock().unwrap();
+
+    for session in state.sessions.values() {
+      let _ = tx.send(Membership::Joined(session.handle().to_string(), session.shared_roster()));
+    }
+
+    state.followers.push(tx);
+
+    speaking::spawn_merge(rx, config)
+  }
+
+  /// Prepares a join request for this group with a fresh session handle.
+  pub fn add_session(&self) -> AddSession {
+    let mut req = AddSession::new();
+
+    req
+      .account_handle(self.account.handle())
+      .acct_name(self.account.name())
+      .sessiongroup_handle(&self.handle)
+      .session_handle(&self.client.next_handle("s"));
+
+    req
+  }
+
+  /// Issues a join request and resolves with the joined session.
+  pub fn join(&self, req: &mut AddSession) -> impl Future<Output = Result<Session, VivoxError>> {
+    let (_, session_handle) = req.handles();
+    let uri = req.get_uri();
+
+    self.client.register_session(self.account.handle(), &self.handle, &session_handle);
+
+    // Subscribe before issuing so early stream updates are not missed
+    let rx = self.account.events();
+    let pending = self.client.issue(req.base());
+    let group = self.clone();
+
+    async move {
+      if let Err(e) = pending.await {
+        group.client.unregister_session(&group.handle, &session_handle);
+
+        return Err(e);
+      }
+
+      let (session, tracker) = SessionTracker::new(
+        group.client.clone(),
+        group.account.clone(),
+        group.handle.clone(),
+        session_handle.clone(),
+        uri,
+      );
+
+      {
+        let mut state = group.state.lock().unwrap();
+
+        state.sessions.insert(session_handle.clone(), session.clone());
+        state.notify(Membership::Joined(session_handle.clone(), session.shared_roster()));
+      }
+
+      // Forget the session once the SDK tears it down
+      tokio::spawn(async move {
+        tracker.run(rx).await;
+        group.forget(&session_handle);
+      });
+
+      Ok(session)
+    }
+  }
+
+  pub fn remove_session(&self, session: &Session) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    let pending = unsafe {
+      let mut req: *mut vx_req_sessiongroup_remove_session = mem::zeroed();
+
+      vx_req_sessiongroup_remove_session_create(&mut req);
+
+      (*req).sessiongroup_handle = strdup(&self.handle);
+      (*req).session_handle = strdup(session.handle());
+
+      self.client.issue(&mut (*req).base)
+    };
+
+    // The session is forgotten on the SessionRemoved event that follows
+    pending
+  }
+
+  /// Leaves every session in the group.
+  pub fn terminate(&self) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    let pending = unsafe {
+      let mut req: *mut vx_req_sessiongroup_terminate = mem::zeroed();
+
+      vx_req_sessiongroup_terminate_create(&mut req);
+
+      (*req).sessiongroup_handle = strdup(&self.handle);
+
+      self.client.issue(&mut (*req).base)
+    };
+
+    // And its sessions on the SessionGroupRemoved event
+    pending
+  }
+
+  /// Selects which sessions receive the local microphone.
+  pub fn transmit_to(&self, target: Transmit) -> impl Future<Output = Result<(), VivoxError>> {
+    use std::mem;
+
+    let pending = unsafe {
+      match &target {
+        Transmit::None => {
+          let mut req: *mut vx_req_sessiongroup_set_tx_no_session = mem::zeroed();
+
+          vx_req_sessiongroup_set_tx_no_session_create(&mut req);
+          (*req).sessiongroup_handle = strdup(&self.handle);
+
+          self.client.issue(&mut (*req).base)
+        },
+        Transmit::All => {
+          let mut req: *mut vx_req_sessiongroup_set_tx_all_sessions = mem::zeroed();
+
+          vx_req_sessiongroup_set_tx_all_sessions_create(&mut req);
+          (*req).sessiongroup_handle = strdup(&self.handle);
+
+          self.client.issue(&mut (*req).base)
+        },
+        Transmit::Session(session_handle) => {
+          let mut req: *mut vx_req_sessiongroup_set_tx_session = mem::zeroed();
+
+          vx_req_sessiongroup_set_tx_session_create(&mut req);
+          (*req).session_handle = strdup(session_handle);
+
+          self.client.issue(&mut (*req).base)
+        },
+      }
+    };
+    let state = self.state.clone();
+
+    async move {
+      pending.await?;
+      state.lock().unwrap().transmitting = target;
+
+      Ok(())
+    }
+  }
+
+  /// Creates a push-to-talk controller for this group. Nothing changes
+  /// until the first call to it, so call `set_mode` to apply the initial
+  /// mode.
+  pub fn transmission(&self) -> TransmissionController {
+    TransmissionController::new(self.client.clone(), self.account.clone(), self.clone())
+  }
+
+  /// Transmits to whichever of the two sessions is not transmitting yet,
+  /// e.g. to flip between a team channel and a proximity channel.
+  pub fn toggle_transmission(
+    &self,
+    first: &Session,
+    second: &Session,
+  ) -> impl Future<Output = Result<(), VivoxError>>
+  {
+    let target = match self.transmitting() {
+      Transmit::Session(ref current) if current == first.handle() => second,
+      _ => first,
+    };
+
+    self.transmit_to(Transmit::Session(target.handle().to_string()))
+  }
+
+  fn forget(&self, session_handle: &str) {
+    let mut state = self.state.lock().unwrap();
+
+    if state.sessions.remove(session_handle).is_some() {
+      state.notify(Membership::Left(session_handle.to_string()));
+    }
+
+    if state.transmitting == Transmit::Session(session_handle.to_string()) {
+      state.transmitting = Transmit::None;
+    }
   }
 }
@@ -0,0 +1,426 @@
+use crate::participant::{Participant, Roster, RosterChange};
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, watch};
+
+const STREAM_CAPACITY: usize = 64;
+
+/// Smoothing applied to the SDK's raw speaking flag.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpeakingConfig {
+  /// How long someone has to speak before `Started` is reported
+  pub debounce: Duration,
+  /// How long the indicator stays lit after they go quiet
+  pub hold: Duration,
+}
+
+impl Default for SpeakingConfig {
+  fn default() -> Self {
+    Self {
+      debounce: Duration::from_millis(100),
+      hold: Duration::from_millis(400),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpeakingEvent {
+  Started { uri: String },
+  Stopped { uri: String },
+  /// Raw voice energy between 0.0 and 1.0, reported on every change
+  Energy { uri: String, energy: f64 },
+}
+
+/// Merged speaking state of one user across every session of a group.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VoiceActivity {
+  pub is_speaking: bool,
+  /// Highest energy reported by any session
+  pub energy: f64,
+}
+
+/// A session entering or leaving a group followed by `spawn_merge`.
+#[derive(Clone)]
+pub(crate) enum Membership {
+  Joined(String, Arc<Roster>),
+  Left(String),
+}
+
+struct Speaker {
+  raw: bool,
+  reported: bool,
+  changed_at: Instant,
+  energy: f64,
+}
+
+/// Turns raw speaking flags into debounced start and stop transitions.
+///
+/// Time is passed in explicitly so the filter can be driven by a timer in
+/// production and by hand in tests.
+pub(crate) struct SpeakingFilter {
+  config: SpeakingConfig,
+  speakers: HashMap<String, Speaker>,
+}
+
+impl SpeakingFilter {
+  pub(crate) fn new(config: SpeakingConfig) -> Self {
+    Self {
+      config,
+      speakers: HashMap::new(),
+    }
+  }
+
+  pub(crate) fn update(
+    &mut self,
+    uri: &str,
+    is_speaking: bool,
+    energy: f64,
+    now: Instant,
+  ) -> Vec<SpeakingEvent> {
+    let mut events = Vec::new();
+    let speaker = self.speakers
+      .entry(uri.to_string())
+      .or_insert(Speaker {
+        raw: false,
+        reported: false,
+        changed_at: now,
+        energy: 0.0,
+      });
+
+    if speaker.energy != energy {
+      speaker.energy = energy;
+      events.push(SpeakingEvent::Energy { uri: uri.to_string(), energy });
+    }
+
+    if speaker.raw != is_speaking {
+      speaker.raw = is_speaking;
+      speaker.changed_at = now;
+    }
+
+    events.extend(self.poll(now));
+    events
+  }
+
+  /// Forgets a participant, reporting `Stopped` if they were lit.
+  pub(crate) fn remove(&mut self, uri: &str) -> Vec<SpeakingEvent> {
+    match self.speakers.remove(uri) {
+      Some(Speaker { reported: true, .. }) => vec![SpeakingEvent::Stopped { uri: uri.to_string() }],
+      _ => Vec::new(),
+    }
+  }
+
+  pub(crate) fn apply(&mut self, change: &RosterChange, now: Instant) -> Vec<SpeakingEvent> {
+    match change {
+      RosterChange::Updated(participant) => self.update(
+        &participant.uri,
+        participant.is_speaking,
+        participant.energy,
+        now,
+      ),
+      RosterChange::Removed(participant, _) => self.remove(&participant.uri),
+      RosterChange::Added(_) => Vec::new(),
+    }
+  }
+
+  /// Catches up with the roster after changes to it were missed.
+  pub(crate) fn resync(&mut self, participants: &[Participant], now: Instant) -> Vec<SpeakingEvent> {
+    let present: HashSet<&str> = participants.iter().map(|p| p.uri.as_str()).collect();
+    let departed: Vec<String> = self.speakers
+      .keys()
+      .filter(|uri| !present.contains(uri.as_str()))
+      .cloned()
+      .collect();
+    let mut events = Vec::new();
+
+    for uri in departed {
+      events.extend(self.remove(&uri));
+    }
+
+    for participant in participants {
+      events.extend(self.update(&participant.uri, participant.is_speaking, participant.energy, now));
+    }
+
+    events
+  }
+
+  /// Reported state of everyone the filter knows about.
+  pub(crate) fn activity(&self) -> impl Iterator<Item = (&String, VoiceActivity)> {
+    self.speakers.iter().map(|(uri, speaker)| {
+      (uri, VoiceActivity { is_speaking: speaker.reported, energy: speaker.energy })
+    })
+  }
+
+  /// Reports every transition that has become due by `now`.
+  pub(crate) fn poll(&mut self, now: Instant) -> Vec<SpeakingEvent> {
+    let config = self.config;
+    let mut events = Vec::new();
+
+    for (uri, speaker) in self.speakers.iter_mut() {
+      if speaker.raw == speaker.reported {
+        continue;
+      }
+
+      let wait = match speaker.raw {
+        true => config.debounce,
+        false => config.hold,
+      };
+
+      if now.duration_since(speaker.changed_at) >= wait {
+        speaker.reported = speaker.raw;
+        events.push(match speaker.raw {
+          true => SpeakingEvent::Started { uri: uri.clone() },
+          false => SpeakingEvent::Stopped { uri: uri.clone() },
+        });
+      }
+    }
+
+    events
+  }
+
+  /// When the next pending transition becomes due, if any.
+  pub(crate) fn next_deadline(&self) -> Option<Instant> {
+    let config = self.config;
+
+    self.speakers
+      .values()
+      .filter(|speaker| speaker.raw != speaker.reported)
+      .map(|speaker| speaker.changed_at + match speaker.raw {
+        true => config.debounce,
+        false => config.hold,
+      })
+      .min()
+  }
+}
+
+/// Runs a filter over a roster's changes until the receiver is dropped.
+pub(crate) fn spawn_filter(
+  roster: Arc<Roster>,
+  config: SpeakingConfig,
+) -> mpsc::Receiver<SpeakingEvent> {
+  use tokio::time::{delay_until, Instant as TokioInstant};
+
+  let (mut tx, rx) = mpsc::channel(STREAM_CAPACITY);
+  let mut changes = roster.subscribe();
+
+  tokio::spawn(async move {
+    let mut filter = SpeakingFilter::new(config);
+
+    loop {
+      let change = match filter.next_deadline() {
+        Some(deadline) => tokio::select! {
+          change = changes.recv() => Some(change),
+          _ = delay_until(TokioInstant::from_std(deadline)) => None,
+        },
+        None => Some(changes.recv().await),
+      };
+
+      let now = Instant::now();
+      let events = match change {
+        Some(Ok(change)) => filter.apply(&change, now),
+        Some(Err(broadcast::RecvError::Lagged(_))) => filter.resync(&roster.snapshot(), now),
+        Some(Err(broadcast::RecvError::Closed)) => break,
+        None => filter.poll(now),
+      };
+
+      for evt in events {
+        if tx.send(evt).await.is_err() {
+          return;
+        }
+      }
+    }
+  });
+
+  rx
+}
+
+/// Filters every session of a group and folds them into one map keyed by
+/// user URI. Someone counts as speaking while they speak in any session.
+///
+/// Sessions are followed as they join and leave the group, and everyone
+/// who leaves a session is dropped from it.
+pub(crate) fn spawn_merge(
+  mut membership: mpsc::UnboundedReceiver<Membership>,
+  config: SpeakingConfig,
+) -> watch::Receiver<HashMap<String, VoiceActivity>> {
+  use tokio::stream::{StreamExt, StreamMap};
+  use tokio::time::{delay_until, Instant as TokioInstant};
+
+  let (state_tx, state_rx) = watch::channel(HashMap::new());
+
+  tokio::spawn(async move {
+    let mut changes = StreamMap::new();
+    // Session handle to its roster and filter
+    let mut sessions: HashMap<String, (Arc<Roster>, SpeakingFilter)> = HashMap::new();
+    let mut following = true;
+    let mut last = HashMap::new();
+
+    loop {
+      let deadline = sessions
+        .values()
+        .filter_map(|(_, filter)| filter.next_deadline())
+        .min();
+
+      tokio::select! {
+        change = membership.recv(), if following => match change {
+          Some(Membership::Joined(session_handle, roster)) => {
+            let mut filter = SpeakingFilter::new(config);
+
+            changes.insert(session_handle.clone(), roster.subscribe());
+            filter.resync(&roster.snapshot(), Instant::now());
+            sessions.insert(session_handle, (roster, filter));
+          },
+          Some(Membership::Left(session_handle)) => {
+            changes.remove(&session_handle);
+            sessions.remove(&session_handle);
+          },
+          // The group is gone, its sessions can still be followed
+          None => following = false,
+        },
+        Some((session_handle, change)) = changes.next(), if !changes.is_empty() => {
+          if let Some((roster, filter)) = sessions.get_mut(&session_handle) {
+            match change {
+              Ok(change) => filter.apply(&change, Instant::now()),
+              Err(_) => filter.resync(&roster.snapshot(), Instant::now()),
+            };
+          }
+        },
+        _ = delay_until(TokioInstant::from_std(deadline.unwrap_or_else(Instant::now))), if deadline.is_some() => {
+          for (_, filter) in sessions.values_mut() {
+            filter.poll(Instant::now());
+          }
+        },
+        else => break,
+      }
+
+      let merged = merge(&sessions);
+
+      if merged != last {
+        if state_tx.broadcast(merged.clone()).is_err() {
+          break;
+        }
+
+        last = merged;
+      }
+    }
+  });
+
+  state_rx
+}
+
+fn merge(sessions: &HashMap<String, (Arc<Roster>, SpeakingFilter)>) -> HashMap<String, VoiceActivity> {
+  let mut merged: HashMap<String, VoiceActivity> = HashMap::new();
+
+  for (_, filter) in sessions.values() {
+    for (uri, activity) in filter.activity() {
+      let entry = merged.entry(uri.clone()).or_default();
+
+      entry.is_speaking |= activity.is_speaking;
+      entry.energy = entry.energy.max(activity.energy);
+    }
+  }
+
+  merged
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn config() -> SpeakingConfig {
+    SpeakingConfig {
+      debounce: Duration::from_millis(100),
+      hold: Duration::from_millis(300),
+    }
+  }
+
+  fn started(uri: &str) -> SpeakingEvent {
+    SpeakingEvent::Started { uri: uri.to_string() }
+  }
+
+  fn stopped(uri: &str) -> SpeakingEvent {
+    SpeakingEvent::Stopped { uri: uri.to_string() }
+  }
+
+  #[test]
+  fn short_blips_are_ignored() {
+    let t0 = Instant::now();
+    let mut filter = SpeakingFilter::new(config());
+
+    filter.update("sip:a@x", true, 0.0, t0);
+    assert_eq!(filter.next_deadline(), Some(t0 + Duration::from_millis(100)));
+
+    filter.update("sip:a@x", false, 0.0, t0 + Duration::from_millis(50));
+    assert_eq!(filter.next_deadline(), None);
+    assert!(filter.poll(t0 + Duration::from_millis(500)).is_empty());
+  }
+
+  #[test]
+  fn hold_bridges_short_pauses() {
+    let t0 = Instant::now();
+    let mut filter = SpeakingFilter::new(config());
+
+    filter.update("sip:a@x", true, 0.0, t0);
+    assert_eq!(filter.poll(t0 + Duration::from_millis(100)), vec![started("sip:a@x")]);
+
+    filter.update("sip:a@x", false, 0.0, t0 + Duration::from_millis(200));
+    assert!(filter.poll(t0 + Duration::from_millis(400)).is_empty());
+
+    filter.update("sip:a@x", true, 0.0, t0 + Duration::from_millis(450));
+    assert!(filter.poll(t0 + Duration::from_millis(900)).is_empty());
+
+    filter.update("sip:a@x", false, 0.0, t0 + Duration::from_millis(1000));
+    assert_eq!(filter.poll(t0 + Duration::from_millis(1300)), vec![stopped("sip:a@x")]);
+  }
+
+  #[test]
+  fn energy_and_removal_are_reported() {
+    let t0 = Instant::now();
+    let mut filter = SpeakingFilter::new(SpeakingConfig {
+      debounce: Duration::from_millis(0),
+      hold: Duration::from_millis(0),
+    });
+
+    assert_eq!(
+      filter.update("sip:a@x", true, 0.5, t0),
+      vec![
+        SpeakingEvent::Energy { uri: "sip:a@x".to_string(), energy: 0.5 },
+        started("sip:a@x"),
+      ],
+    );
+    assert_eq!(filter.remove("sip:a@x"), vec![stopped("sip:a@x")]);
+    assert!(filter.remove("sip:a@x").is_empty());
+  }
+
+  #[test]
+  fn resync_drops_departed_participants() {
+    let t0 = Instant::now();
+    let mut filter = SpeakingFilter::new(SpeakingConfig {
+      debounce: Duration::from_millis(0),
+      hold: Duration::from_millis(0),
+    });
+
+    filter.update("sip:a@x", true, 0.0, t0);
+    filter.update("sip:b@x", false, 0.0, t0);
+
+    let present = Participant {
+      uri: "sip:b@x".to_string(),
+      display_name: "B".to_string(),
+      is_current_user: false,
+      is_speaking: true,
+      energy: 0.0,
+      volume: 50,
+      is_muted_for_me: false,
+      is_moderator_muted: false,
+      is_typing: false,
+    };
+
+    let events = filter.resync(&[present], t0);
+    assert!(events.contains(&stopped("sip:a@x")));
+    assert!(events.contains(&started("sip:b@x")));
+
+    let activity: Vec<String> = filter.activity().map(|(uri, _)| uri.clone()).collect();
+    assert_eq!(activity, vec!["sip:b@x".to_string()]);
+  }
+}
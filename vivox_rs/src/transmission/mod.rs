@@ -0,0 +1,227 @@
+use crate::account::Account;
+use crate::client::VivoxClient;
+use crate::enums::VivoxError;
+use crate::sessiongroup::{SessionGroup, Transmit};
+
+use std::future::Future;
+use std::os::raw::c_int;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use vivox_rs_sys::*;
+
+/// Voice activity detection tuning, only used in voice activation mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VadConfig {
+  /// From 0 to 100, higher values pick up quieter speech
+  pub sensitivity: i32,
+  /// How long the microphone stays open after speech stops
+  pub hangover: Duration,
+  /// Background level below which nothing counts as speech, 0 to 20000
+  pub noise_floor: i32,
+}
+
+impl Default for VadConfig {
+  // The SDK's own defaults
+  fn default() -> Self {
+    Self {
+      sensitivity: 43,
+      hangover: Duration::from_millis(2000),
+      noise_floor: 576,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransmissionMode {
+  /// Transmits while the key is held
+  PushToTalk,
+  /// Each press flips between transmitting and silent
+  ToggleToTalk,
+  /// The microphone stays open and the SDK only sends detected speech
+  VoiceActivation(VadConfig),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransmissionState {
+  pub mode: TransmissionMode,
+  /// Where audio goes while the microphone is open
+  pub target: Transmit,
+  /// Whether the microphone is currently open
+  pub open: bool,
+  /// What the group was last told to transmit to by this controller
+  pub requested: Transmit,
+}
+
+/// Drives microphone mute and the transmitting session of a
+/// `SessionGroup` from push-to-talk, toggle or voice activation input.
+///
+/// While closed the microphone is muted and the group transmits to no
+/// session. Opening it unmutes the microphone and transmits to `target`.
+#[derive(Clone)]
+pub struct TransmissionController {
+  client: VivoxClient,
+  account: Account,
+  group: SessionGroup,
+  current: Arc<Mutex<TransmissionState>>,
+  state: Arc<watch::Sender<TransmissionState>>,
+  changes: watch::Receiver<TransmissionState>,
+}
+
+impl TransmissionController {
+  pub(crate) fn new(client: VivoxClient, account: Account, group: SessionGroup) -> Self {
+    let current = TransmissionState {
+      mode: TransmissionMode::PushToTalk,
+      target: Transmit::All,
+      open: false,
+      requested: group.transmitting(),
+    };
+    let (tx, rx) = watch::channel(current.clone());
+
+    Self {
+      client,
+      account,
+      group,
+      current: Arc::new(Mutex::new(current)),
+      state: Arc::new(tx),
+      changes: rx,
+    }
+  }
+
+  /// Current state. The receiver also resolves on every change.
+  pub fn state(&self) -> watch::Receiver<TransmissionState> {
+    self.changes.clone()
+  }
+
+  /// Switches mode. Push-to-talk and toggle start out closed, voice
+  /// activation opens the microphone right away.
+  pub fn set_mode(&self, mode: TransmissionMode) -> impl Future<Output = Result<(), VivoxError>> {
+    let vad = match mode {
+      TransmissionMode::VoiceActivation(config) => Some(self.set_vad(config)),
+      _ => None,
+    };
+    // Leave the state alone when the VAD settings are rejected up front
+    let apply = match vad {
+      Some(Err(_)) => None,
+      _ => Some(self.update(move |state| {
+        state.mode = mode;
+        state.open = matches!(mode, TransmissionMode::VoiceActivation(_));
+      })),
+    };
+
+    async move {
+      if let Some(vad) = vad {
+        vad?.await?;
+      }
+
+      match apply {
+        Some(apply) => apply.await,
+        None => Ok(()),
+      }
+    }
+  }
+
+  /// Picks where audio goes while the microphone is open.
+  pub fn set_target(&self, target: Transmit) -> impl Future<Output = Result<(), VivoxError>> {
+    self.update(move |state| state.target = target)
+  }
+
+  /// Push-to-talk key went down. Flips the microphone in toggle mode and
+  /// does nothing in voice activation mode.
+  pub fn press(&self) -> impl Future<Output = Result<(), VivoxError>> {
+    self.update(move |state| match state.mode {
+      TransmissionMode::PushToTalk => state.open = true,
+      TransmissionMode::ToggleToTalk => state.open = !state.open,
+      TransmissionMode::VoiceActivation(_) => {},
+    })
+  }
+
+  /// Push-to-talk key went up. Only closes the microphone in push-to-talk
+  /// mode.
+  pub fn release(&self) -> impl Future<Output = Result<(), VivoxError>> {
+    self.update(move |state| if state.mode == TransmissionMode::PushToTalk {
+      state.open = false;
+    })
+  }
+
+  /// Applies a state change, reports it and issues the requests it needs
+  /// right away, so quick presses and releases reach the SDK in order.
+  ///
+  /// Targets are compared with what this controller requested before, as
+  /// the group only learns about a target once the SDK accepted it.
+  fn update<F: FnOnce(&mut TransmissionState)>(&self, f: F) -> impl Future<Output = Result<(), VivoxError>> {
+    let (before, after) = {
+      let mut current = self.current.lock().unwrap();
+      let before = current.clone();
+
+      f(&mut current);
+      current.requested = match current.open {
+        true => current.target.clone(),
+        false => Transmit::None,
+      };
+
+      if *current != before {
+        let _ = self.state.broadcast(current.clone());
+      }
+
+      (before, current.clone())
+    };
+
+    let mute = match before.open != after.open {
+      true => Some(self.account.mute_mic(!after.open)),
+      false => None,
+    };
+    let transmit = match before.requested != after.requested {
+      true => Some(self.group.transmit_to(after.requested.clone())),
+      false => None,
+    };
+    let controller = self.clone();
+
+    async move {
+      if let Some(mute) = mute {
+        mute.await?;
+      }
+
+      if let Some(transmit) = transmit {
+        if let Err(e) = transmit.await {
+          controller.forget_request(&after.requested);
+
+          return Err(e);
+        }
+      }
+
+      Ok(())
+    }
+  }
+
+  /// Falls back to what the group transmits to after a refused request,
+  /// so asking for the same target again issues it again.
+  fn forget_request(&self, refused: &Transmit) {
+    let mut current = self.current.lock().unwrap();
+
+    if current.requested == *refused {
+      current.requested = self.group.transmitting();
+      let _ = self.state.broadcast(current.clone());
+    }
+  }
+
+  fn set_vad(&self, config: VadConfig) -> Result<impl Future<Output = Result<(), VivoxError>>, VivoxError> {
+    use std::mem;
+
+    if !(0..=100).contains(&config.sensitivity) || !(0..=20_000).contains(&config.noise_floor) {
+      return Err(VivoxError::INVALID_ARGUMENT);
+    }
+
+    unsafe {
+      let mut req: *mut vx_req_aux_set_vad_properties = mem::zeroed();
+
+      vx_req_aux_set_vad_properties_create(&mut req);
+
+      (*req).vad_hangover = config.hangover.as_millis() as c_int;
+      (*req).vad_sensitivity = config.sensitivity;
+      (*req).vad_noise_floor = config.noise_floor;
+
+      Ok(self.client.issue(&mut (*req).base))
+    }
+  }
+}
@@ -0,0 +1,145 @@
+//! Which SDK release is loaded and whether the bindings were generated for
+//! it.
+//!
+//! Structs are passed to the SDK as laid out in the bindings, so running
+//! against a library of another minor release corrupts memory instead of
+//! failing. `VivoxClient::new` checks the library first and fails with
+//! `InitError::Incompatible` instead of initializing it. `check_sdk` runs
+//! the same check without creating a client.
+
+use crate::helpers::*;
+
+use serde::{Serialize, Deserialize};
+use std::error::Error;
+use std::fmt;
+use vivox_rs_sys::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SdkVersion {
+  pub major: u32,
+  pub minor: u32,
+  pub patch: u32,
+  /// Only reported by libraries, not part of a release
+  pub build: Option<u32>,
+}
+
+impl SdkVersion {
+  /// Parses `5.9.0` or `5.9.0.1234`, ignoring anything after the numbers.
+  pub fn parse(input: &str) -> Option<Self> {
+    let end = input
+      .find(|c: char| !c.is_ascii_digit() && c != '.')
+      .unwrap_or(input.len());
+    let mut parts = input[..end].split('.').map(|part| part.parse::<u32>());
+
+    let major = parts.next()?.ok()?;
+    let minor = parts.next()?.ok()?;
+    let patch = parts.next()?.ok()?;
+    let build = match parts.next() {
+      Some(build) => Some(build.ok()?),
+      None => None,
+    };
+
+    Some(Self {
+      major,
+      minor,
+      patch,
+      build,
+    })
+  }
+
+  /// Release the bindings in use were generated from.
+  pub fn bindings() -> Self {
+    Self::parse(SDK_RELEASE).expect("SDK_RELEASE is not a version")
+  }
+
+  /// Struct layouts only change between minor releases.
+  pub fn is_compatible_with(&self, other: &SdkVersion) -> bool {
+    self.major == other.major && self.minor == other.minor
+  }
+}
+
+impl fmt::Display for SdkVersion {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+
+    match self.build {
+      Some(build) => write!(f, ".{}", build),
+      None => Ok(()),
+    }
+  }
+}
+
+/// Why the loaded SDK can't be used with these bindings.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IncompatibleSdk {
+  /// The library reported a version string that could not be read
+  Unknown(String),
+  Release { found: SdkVersion, expected: SdkVersion },
+  /// The library refused the size of `vx_sdk_config_t` in the bindings
+  ConfigSize { size: usize, found: SdkVersion },
+}
+
+impl fmt::Display for IncompatibleSdk {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      IncompatibleSdk::Unknown(info) => write!(
+        f,
+        "The Vivox SDK reported an unknown version '{}', the bindings are for {}",
+        info,
+        SdkVersion::bindings(),
+      ),
+      IncompatibleSdk::Release { found, expected } => write!(
+        f,
+        "The Vivox SDK is version {} but the bindings were generated for {}, \
+        build vivox_rs_sys against the SDK in use",
+        found,
+        expected,
+      ),
+      IncompatibleSdk::ConfigSize { size, found } => write!(
+        f,
+        "The Vivox SDK {} refused the {} byte vx_sdk_config_t of the {} bindings, \
+        build vivox_rs_sys against the SDK in use",
+        found,
+        size,
+        SdkVersion::bindings(),
+      ),
+    }
+  }
+}
+
+impl Error for IncompatibleSdk {}
+
+/// Version of the SDK library in use.
+pub fn sdk_version() -> Result<SdkVersion, IncompatibleSdk> {
+  let info = to_owned_string(unsafe { vx_get_sdk_version_info() });
+
+  SdkVersion::parse(&info).ok_or(IncompatibleSdk::Unknown(info))
+}
+
+/// Version of the SDK library in use, failing unless the bindings were
+/// generated for its release.
+pub fn check_sdk() -> Result<SdkVersion, IncompatibleSdk> {
+  let found = sdk_version()?;
+  let expected = SdkVersion::bindings();
+
+  match found.is_compatible_with(&expected) {
+    true => Ok(found),
+    false => Err(IncompatibleSdk::Release { found, expected }),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_library_versions() {
+    let version = SdkVersion::parse("5.9.0.1234 (release)").unwrap();
+
+    assert_eq!((version.major, version.minor, version.patch, version.build), (5, 9, 0, Some(1234)));
+    assert_eq!(version.to_string(), "5.9.0.1234");
+    assert!(version.is_compatible_with(&SdkVersion::parse("5.9.2").unwrap()));
+    assert!(!version.is_compatible_with(&SdkVersion::parse("5.10.0").unwrap()));
+    assert_eq!(SdkVersion::parse("5.9"), None);
+  }
+}
@@ -0,0 +1,1169 @@
+#![cfg(feature = "mock")]
+#![allow(non_upper_case_globals)]
+
+use futures::StreamExt;
+use std::future::Future;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::runtime::Runtime;
+use vivox_rs::account::{Account, AudioSettings};
+use vivox_rs::archive::{ArchiveQuery, ArchivedMessage};
+use vivox_rs::backend::{FfiBackend, Message, Request, VivoxBackend};
+use vivox_rs::client::{InitError, ResourceUsage, VivoxClient};
+use vivox_rs::devices::{DeviceChange, DeviceType};
+use vivox_rs::enums::{LoginState, MediaState, RemovalReason, TextState, VivoxError};
+use vivox_rs::events::Event;
+use vivox_rs::faults::Faults;
+use vivox_rs::messages::{CustomMetadata, UserMessageEvent};
+use vivox_rs::participant::RosterChange;
+use vivox_rs::positional::Position3d;
+use vivox_rs::presence::{BuddyEvent, PresenceStatus, SubscriptionReply};
+use vivox_rs::replay::{Recorder, Replay};
+use vivox_rs::session::{Session, SessionEvent};
+use vivox_rs::sessiongroup::Transmit;
+use vivox_rs::speaking::SpeakingConfig;
+use vivox_rs::tokengen::TokenGenerator;
+use vivox_rs::uri::{ChannelType, UserUri};
+use vivox_rs::version::{self, IncompatibleSdk, SdkVersion};
+use vivox_rs_sys::mock::server;
+use vivox_rs_sys::*;
+
+const SERVER: &str = "https://vdx5.www.vivox.com/api2";
+const CHANNEL: &str = "sip:confctl-g-issuer.lobby@vdx5.vivox.com";
+const REMOTE: &str = "sip:.issuer.friend.@vdx5.vivox.com";
+const KEY: &str = "secret";
+
+static TOKENS: TokenGenerator = TokenGenerator::init();
+
+// The simulated SDK is process wide
+static SDK: Mutex<()> = Mutex::new(());
+
+/// Runs a scenario against a freshly reset server, one at a time.
+fn run<F: Future<Output = ()>>(scenario: F) {
+  let _sdk = SDK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+  server::reset();
+  Runtime::new().unwrap().block_on(scenario);
+}
+
+/// Polls `check` until it holds, giving the poll thread time to deliver.
+async fn eventually<F: FnMut() -> bool>(mut check: F) {
+  let deadline = Instant::now() + Duration::from_secs(5);
+
+  while !check() {
+    assert!(Instant::now() < deadline, "condition not met in time");
+    tokio::time::delay_for(Duration::from_millis(20)).await;
+  }
+}
+
+async fn join(client: &VivoxClient) -> (Account, Session) {
+  let connector = client.create_connector(SERVER).await.unwrap();
+  let account = connector.login(
+    connector
+      .anonymous_login(".issuer.player.")
+      .displayname("Player")
+      .access_token(KEY, "issuer", "vdx5.vivox.com", &TOKENS)
+  ).await.unwrap();
+
+  let group = account.create_sessiongroup();
+  let session = group.join(
+    group
+      .add_session()
+      .uri(CHANNEL)
+      .unwrap()
+      .connect_audio(1)
+      .connect_text(1)
+      .access_token(KEY, "issuer", "vdx5.vivox.com", &TOKENS)
+  ).await.unwrap();
+
+  (account, session)
+}
+
+#[test]
+fn joins_a_channel() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, session) = join(&client).await;
+
+    assert_eq!(account.uri(), "sip:.issuer.player.@vdx5.vivox.com");
+
+    let state = session.state();
+    eventually(|| {
+      let state = *state.borrow();
+
+      state.media == MediaState::Connected && state.text == TextState::Connected
+    }).await;
+    eventually(|| session.participant(account.uri()).is_some()).await;
+
+    assert!(server::add_participant(session.handle(), REMOTE, "Friend"));
+    assert!(server::set_speaking(session.handle(), REMOTE, true));
+    eventually(|| session.participant(REMOTE).map(|p| p.is_speaking).unwrap_or(false)).await;
+
+    assert_eq!(client.usage().sessions, 1);
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn refuses_unusable_channel_uris() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, _session) = join(&client).await;
+    let group = account.create_sessiongroup();
+    let long = format!(
+      "sip:confctl-g-issuer.{}@vdx5.vivox.com",
+      "a".repeat(MAX_CHANNEL_URI_LENGTH as usize),
+    );
+
+    assert_eq!(group.add_session().uri(&long).err(), Some(VivoxError::CHANNEL_URI_TOO_LONG));
+    assert_eq!(
+      group.add_session().uri("sip:confctl-g-issuer.lobby #1@vdx5.vivox.com").err(),
+      Some(VivoxError::INVALID_ARGUMENT),
+    );
+    assert_eq!(group.add_session().uri("sip:@vdx5.vivox.com").err(), Some(VivoxError::INVALID_ARGUMENT));
+    assert!(group.add_session().uri(CHANNEL).is_ok());
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn reports_failed_logins() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let connector = client.create_connector(SERVER).await.unwrap();
+
+    server::fail_next(vx_request_type_req_account_anonymous_login, VX_E_FAILED);
+
+    let login = connector.login(&mut connector.anonymous_login(".issuer.player."));
+    assert_eq!(login.await.err(), Some(VivoxError::FAILED));
+    assert_eq!(client.usage().accounts, 0);
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn server_can_end_sessions() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (_account, session) = join(&client).await;
+    let mut events = session.events();
+
+    assert!(server::drop_session(session.handle(), VX_E_CALL_TERMINATED_BY_SERVER));
+    assert_eq!(
+      events.recv().await.unwrap(),
+      SessionEvent::Terminated(VivoxError::CALL_TERMINATED_BY_SERVER),
+    );
+
+    eventually(|| client.usage().sessions == 0).await;
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn leaves_sessions() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, _session) = join(&client).await;
+    let group = account.create_sessiongroup();
+    let session = group.join(group.add_session().uri(CHANNEL).unwrap().connect_audio(1)).await.unwrap();
+    let state = session.state();
+
+    eventually(|| session.participant(account.uri()).is_some()).await;
+    group.remove_session(&session).await.unwrap();
+
+    eventually(|| state.borrow().media == MediaState::Disconnected).await;
+    eventually(|| session.roster().is_empty()).await;
+    assert_eq!(client.usage().sessions, 1);
+
+    let session = group.join(group.add_session().uri(CHANNEL).unwrap().connect_audio(1)).await.unwrap();
+    let state = session.state();
+
+    eventually(|| state.borrow().media == MediaState::Connected).await;
+    group.terminate().await.unwrap();
+
+    eventually(|| state.borrow().media == MediaState::Disconnected).await;
+    eventually(|| client.usage().sessions == 1 && client.usage().sessiongroups == 1).await;
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn follows_voice_activity_across_sessions() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, _session) = join(&client).await;
+    let group = account.create_sessiongroup();
+    let activity = group.voice_activity(SpeakingConfig {
+      debounce: Duration::from_millis(0),
+      hold: Duration::from_millis(0),
+    });
+    let speaking = |uri: &str| activity.borrow().get(uri).map(|a| a.is_speaking);
+
+    let session = group.join(group.add_session().uri(CHANNEL).unwrap().connect_audio(1)).await.unwrap();
+
+    eventually(|| session.participant(account.uri()).is_some()).await;
+    assert!(server::add_participant(session.handle(), REMOTE, "Friend"));
+    assert!(server::set_speaking(session.handle(), REMOTE, true));
+    eventually(|| speaking(REMOTE) == Some(true)).await;
+
+    assert!(server::remove_participant(
+      session.handle(),
+      REMOTE,
+      vx_participant_removed_reason_participant_left,
+    ));
+    eventually(|| speaking(REMOTE).is_none()).await;
+
+    assert!(server::add_participant(session.handle(), REMOTE, "Friend"));
+    assert!(server::set_speaking(session.handle(), REMOTE, true));
+    eventually(|| speaking(REMOTE) == Some(true)).await;
+
+    group.remove_session(&session).await.unwrap();
+    eventually(|| activity.borrow().is_empty()).await;
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn keeps_the_disconnect_reason() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (_account, session) = join(&client).await;
+    let state = session.state();
+    let media = |state: vx_session_media_state, status_code: u32| {
+      let (sessiongroup, handle) = (session.sessiongroup_handle(), session.handle());
+
+      server::emit::<vx_evt_media_stream_updated>(|evt| {
+        evt.sessiongroup_handle = server::string(sessiongroup);
+        evt.session_handle = server::string(handle);
+        evt.state = state;
+        evt.status_code = status_code as _;
+      });
+    };
+
+    eventually(|| state.borrow().media == MediaState::Connected).await;
+
+    media(vx_session_media_state_session_media_disconnected, VX_E_CALL_TERMINATED_NO_RTP_RXED);
+    eventually(|| state.borrow().media == MediaState::Disconnected).await;
+
+    media(vx_session_media_state_session_media_connected, VX_E_SUCCESS);
+    eventually(|| state.borrow().media == MediaState::Connected).await;
+    assert_eq!(state.borrow().status, VivoxError::CALL_TERMINATED_NO_RTP_RXED);
+
+    assert_eq!(session.channel_type(), Some(ChannelType::NonPositional));
+    assert_eq!(
+      session.set_3d_position(Position3d::default(), Position3d::default()).await,
+      Err(VivoxError::SESSION_IS_NOT_3D),
+    );
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn orders_quick_key_presses() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, _session) = join(&client).await;
+    let group = account.create_sessiongroup();
+    let _session = group.join(group.add_session().uri(CHANNEL).unwrap().connect_audio(1)).await.unwrap();
+    let controller = group.transmission();
+
+    controller.release().await.unwrap();
+    assert_eq!(group.transmitting(), Transmit::None);
+
+    let press = controller.press();
+    assert!(controller.state().borrow().open);
+
+    let release = controller.release();
+    assert!(!controller.state().borrow().open);
+
+    press.await.unwrap();
+    release.await.unwrap();
+    assert_eq!(group.transmitting(), Transmit::None);
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn sends_positions_from_dropped_calls() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, _session) = join(&client).await;
+    let group = account.create_sessiongroup();
+    let arena = "sip:confctl-d-issuer.arena!p-60-10-1.0-1@vdx5.vivox.com";
+    let session = group.join(group.add_session().uri(arena).unwrap().connect_audio(1)).await.unwrap();
+    let sent = || {
+      server::issued()
+        .into_iter()
+        .filter(|kind| *kind == vx_request_type_req_session_set_3d_position)
+        .count()
+    };
+
+    drop(session.set_3d_position(Position3d::default(), Position3d::default()));
+    eventually(|| sent() == 1).await;
+
+    session.set_3d_position(Position3d::default(), Position3d::default()).await.unwrap();
+    assert_eq!(sent(), 1);
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn keeps_messages_from_unusual_senders() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, _session) = join(&client).await;
+    let mut messages = account.user_messages();
+    let sent_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+
+    server::emit::<vx_evt_user_to_user_message>(|evt| {
+      evt.base.message.create_time_ms = 1_600_000_000_000;
+      evt.account_handle = server::string(account.handle());
+      evt.from_uri = server::string("moderation-bot");
+      evt.message_body = server::string("hello");
+    });
+
+    match messages.recv().await.unwrap() {
+      UserMessageEvent::Received(message) => {
+        assert_eq!(message.sender, None);
+        assert_eq!(message.sender_uri, "moderation-bot");
+        assert_eq!(message.received_at, sent_at);
+      },
+      other => panic!("unexpected {:?}", other),
+    }
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn releases_accounts_once() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, _session) = join(&client).await;
+
+    let _ = account.clone().logout().await;
+    let _ = account.logout().await;
+
+    assert_eq!(client.usage().accounts, 0);
+    assert_eq!(client.usage().sessions, 0);
+
+    let connector = client.create_connector(SERVER).await.unwrap();
+    let account = connector.login(&mut connector.anonymous_login(".issuer.player.")).await.unwrap();
+    let group = account.create_sessiongroup();
+    group.join(group.add_session().uri(CHANNEL).unwrap().connect_audio(1)).await.unwrap();
+
+    connector.clone().shutdown().await.unwrap();
+    let _ = connector.shutdown().await;
+
+    // The connector from the first login is still up
+    assert_eq!(client.usage(), ResourceUsage { connectors: 1, ..ResourceUsage::default() });
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn shuts_down_before_returning() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let connector = client.create_connector(SERVER).await.unwrap();
+
+    server::ignore_next(vx_request_type_req_account_anonymous_login);
+    let login = connector.login(&mut connector.anonymous_login(".issuer.player."));
+
+    client.shutdown();
+
+    assert_eq!(unsafe { vx_uninitialize() }, VX_E_NOT_INITIALIZED as i32);
+    assert_eq!(login.await.err(), Some(VivoxError::REQUEST_CANCELLED));
+  });
+}
+
+#[test]
+fn checks_the_sdk_version() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+
+    assert!(client.sdk_version().unwrap().is_compatible_with(&SdkVersion::bindings()));
+
+    client.shutdown();
+
+    server::set_sdk_version("5.10.0.1234");
+    assert!(matches!(version::check_sdk(), Err(IncompatibleSdk::Release { .. })));
+    assert!(matches!(
+      VivoxClient::new(),
+      Err(InitError::Incompatible(IncompatibleSdk::Release { .. })),
+    ));
+
+    // The library was left alone
+    assert_eq!(unsafe { vx_uninitialize() }, VX_E_NOT_INITIALIZED as i32);
+  });
+}
+
+#[test]
+fn refuses_libraries_with_another_config_size() {
+  run(async {
+    let size = std::mem::size_of::<vx_sdk_config_t>();
+
+    server::set_sdk_version("5.9.3.1234");
+    server::set_config_size(size + 8);
+
+    match VivoxClient::new() {
+      Err(InitError::Incompatible(IncompatibleSdk::ConfigSize { size: refused, found })) => {
+        assert_eq!(refused, size);
+        assert_eq!(found, SdkVersion::parse("5.9.3.1234").unwrap());
+      },
+      Err(e) => panic!("failed with {}", e),
+      Ok(_) => panic!("initialized"),
+    }
+
+    assert_eq!(unsafe { vx_uninitialize() }, VX_E_NOT_INITIALIZED as i32);
+  });
+}
+
+/// Refuses logins before they reach the SDK.
+struct NoLogins {
+  refused: Arc<Mutex<Vec<String>>>,
+}
+
+impl VivoxBackend for NoLogins {
+  fn initialize(&self) -> Result<(), InitError> {
+    FfiBackend.initialize()
+  }
+
+  fn uninitialize(&self) {
+    FfiBackend.uninitialize()
+  }
+
+  fn issue(&self, req: Request) -> Result<(), VivoxError> {
+    match req.kind() {
+      vx_request_type_req_account_anonymous_login => {
+        self.refused.lock().unwrap().push(req.cookie());
+
+        Err(VivoxError::NETWORK_DOWN)
+      },
+      _ => FfiBackend.issue(req),
+    }
+  }
+
+  fn poll(&self) -> Option<Message> {
+    FfiBackend.poll()
+  }
+}
+
+#[test]
+fn runs_on_custom_backends() {
+  run(async {
+    let refused = Arc::new(Mutex::new(Vec::new()));
+    let client = VivoxClient::with_backend(NoLogins { refused: refused.clone() }).unwrap();
+    let connector = client.create_connector(SERVER).await.unwrap();
+
+    let login = connector.login(&mut connector.anonymous_login(".issuer.player."));
+    assert_eq!(login.await.err(), Some(VivoxError::NETWORK_DOWN));
+    assert_eq!(refused.lock().unwrap().len(), 1);
+    assert!(!server::issued().contains(&vx_request_type_req_account_anonymous_login));
+
+    client.shutdown();
+  });
+}
+
+/// Recording target the test can read back.
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.lock().unwrap().write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+#[test]
+fn replays_recordings() {
+  run(async {
+    let recording = Buffer::default();
+    let client = VivoxClient::with_backend(Recorder::new(FfiBackend, recording.clone())).unwrap();
+    let (_account, session) = join(&client).await;
+
+    assert!(server::add_participant(session.handle(), REMOTE, "Friend"));
+    eventually(|| session.participant(REMOTE).is_some()).await;
+
+    client.shutdown();
+
+    let recording = recording.0.lock().unwrap().clone();
+    let text = String::from_utf8(recording.clone()).unwrap();
+
+    assert!(text.contains(r#""request_type":"req_sessiongroup_add_session""#));
+    assert!(text.contains(r#""event_type":"evt_participant_added""#));
+    assert!(text.contains(&format!(r#""participant_uri":"{}""#, REMOTE)));
+    assert!(!text.contains("access_token"));
+
+    let replay = Replay::from_reader(&recording[..]).unwrap();
+
+    // Nothing reaches the simulated SDK from here on
+    server::reset();
+
+    let client = VivoxClient::with_backend(replay.clone()).unwrap();
+    let (account, session) = join(&client).await;
+
+    eventually(|| session.participant(REMOTE).is_some()).await;
+    eventually(|| replay.is_finished()).await;
+
+    assert!(session.participant(account.uri()).is_some());
+    assert_eq!(replay.divergence(), None);
+    assert!(server::issued().is_empty());
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn drops_calls_on_request() {
+  run(async {
+    let faults = Faults::new();
+    let client = VivoxClient::with_backend(faults.wrap(FfiBackend)).unwrap();
+    let (_account, session) = join(&client).await;
+    let mut events = session.events();
+    let state = session.state();
+
+    assert!(faults.terminate_session(session.handle(), VivoxError::CALL_TERMINATED_NO_RTP_RXED));
+    assert_eq!(
+      events.recv().await.unwrap(),
+      SessionEvent::Terminated(VivoxError::CALL_TERMINATED_NO_RTP_RXED),
+    );
+
+    eventually(|| client.usage().sessions == 0).await;
+    eventually(|| state.borrow().media == MediaState::Disconnected).await;
+    assert_eq!(state.borrow().status, VivoxError::CALL_TERMINATED_NO_RTP_RXED);
+
+    // The drop went through the simulated service
+    assert!(!server::drop_session(session.handle(), VX_E_CALL_TERMINATED_BY_SERVER));
+
+    client.shutdown();
+  });
+}
+
+/// Passes everything on to the SDK, but can't simulate drops.
+struct NoDrops;
+
+impl VivoxBackend for NoDrops {
+  fn initialize(&self) -> Result<(), InitError> {
+    FfiBackend.initialize()
+  }
+
+  fn uninitialize(&self) {
+    FfiBackend.uninitialize()
+  }
+
+  fn issue(&self, req: Request) -> Result<(), VivoxError> {
+    FfiBackend.issue(req)
+  }
+
+  fn poll(&self) -> Option<Message> {
+    FfiBackend.poll()
+  }
+}
+
+#[test]
+fn makes_up_drops_backends_cannot_simulate() {
+  run(async {
+    let faults = Faults::new();
+    let client = VivoxClient::with_backend(faults.wrap(NoDrops)).unwrap();
+    let (_account, session) = join(&client).await;
+    let mut events = session.events();
+
+    assert!(faults.terminate_session(session.handle(), VivoxError::CALL_TERMINATED_NO_RTP_RXED));
+    assert_eq!(
+      events.recv().await.unwrap(),
+      SessionEvent::Terminated(VivoxError::CALL_TERMINATED_NO_RTP_RXED),
+    );
+
+    eventually(|| client.usage().sessions == 0).await;
+    assert!(!faults.terminate_session(session.handle(), VivoxError::CALL_TERMINATED_NO_RTP_RXED));
+    assert!(faults.delivered().iter().any(|evt| matches!(evt, Event::SessionGroupRemoved { .. })));
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn leaves_logging_in_again_to_the_game() {
+  run(async {
+    let faults = Faults::new();
+    let client = VivoxClient::with_backend(faults.wrap(FfiBackend)).unwrap();
+    let (account, _session) = join(&client).await;
+    let mut events = account.events();
+
+    faults.clear_log();
+    faults.lose_connection(VivoxError::NETWORK_DOWN);
+
+    loop {
+      match events.recv().await.unwrap() {
+        Event::LoginStateChanged { state: LoginState::LoggedOut, status, .. } => {
+          assert_eq!(status, VivoxError::NETWORK_DOWN);
+          break;
+        },
+        _ => continue,
+      }
+    }
+
+    eventually(|| client.usage().sessions == 0 && client.usage().sessiongroups == 0).await;
+
+    // The simulated service logged the account out too, and the wrapper
+    // does not try to log it back in
+    assert!(!server::drop_account(account.handle(), VX_E_NETWORK_DOWN));
+    assert!(!faults.issued().contains(&vx_request_type_req_account_anonymous_login));
+
+    // What a game would do, with the first attempt still failing
+    faults.fail_next(vx_request_type_req_account_anonymous_login, VivoxError::NETWORK_DOWN);
+
+    let connector = client.create_connector(SERVER).await.unwrap();
+    let mut attempts = 0;
+    let relogged = loop {
+      attempts += 1;
+
+      let login = connector.login(&mut connector.anonymous_login(".issuer.player."));
+
+      match login.await {
+        Ok(account) => break account,
+        Err(status) => assert_eq!(status, VivoxError::NETWORK_DOWN),
+      }
+
+      assert!(attempts < 3, "login never recovered");
+    };
+
+    let logins = faults.issued()
+      .into_iter()
+      .filter(|kind| *kind == vx_request_type_req_account_anonymous_login)
+      .count();
+
+    assert_eq!(logins, 2);
+    assert_eq!(relogged.name(), account.name());
+    assert!(faults.delivered().iter().any(|evt| matches!(evt, Event::SessionGroupRemoved { .. })));
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn adds_and_removes_buddies() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, _session) = join(&client).await;
+    let buddies = account.buddy_list();
+    let mut changes = buddies.events();
+    let friend = UserUri::parse(REMOTE).unwrap();
+
+    buddies.add(&friend, "Friend").await.unwrap();
+
+    match changes.recv().await.unwrap() {
+      BuddyEvent::Added(buddy) => {
+        assert_eq!(buddy.user, Some(friend.clone()));
+        assert_eq!(buddy.uri, REMOTE);
+        assert_eq!(buddy.display_name, "Friend");
+        assert!(!buddy.is_online());
+      },
+      other => panic!("unexpected {:?}", other),
+    }
+
+    buddies.add(&friend, "Old friend").await.unwrap();
+
+    match changes.recv().await.unwrap() {
+      BuddyEvent::Updated(buddy) => assert_eq!(buddy.display_name, "Old friend"),
+      other => panic!("unexpected {:?}", other),
+    }
+
+    // Buddies the SDK reports with other addresses are kept as they are
+    server::emit::<vx_evt_buddy_changed>(|evt| {
+      evt.account_handle = server::string(account.handle());
+      evt.change_type = vx_change_type_t_change_type_set;
+      evt.buddy_uri = server::string("moderation-bot");
+      evt.display_name = server::string("Moderation");
+    });
+
+    match changes.recv().await.unwrap() {
+      BuddyEvent::Added(buddy) => {
+        assert_eq!(buddy.user, None);
+        assert_eq!(buddy.uri, "moderation-bot");
+      },
+      other => panic!("unexpected {:?}", other),
+    }
+
+    buddies.remove(&friend).await.unwrap();
+
+    match changes.recv().await.unwrap() {
+      BuddyEvent::Removed(buddy) => assert_eq!(buddy.uri, REMOTE),
+      other => panic!("unexpected {:?}", other),
+    }
+
+    assert_eq!(buddies.buddy(&friend), None);
+    assert_eq!(buddies.buddies().len(), 1);
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn follows_buddy_presence() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, _session) = join(&client).await;
+    let buddies = account.buddy_list();
+    let mut changes = buddies.events();
+    let friend = UserUri::parse(REMOTE).unwrap();
+
+    buddies.add(&friend, "Friend").await.unwrap();
+    assert!(matches!(changes.recv().await.unwrap(), BuddyEvent::Added(_)));
+
+    server::emit::<vx_evt_buddy_presence>(|evt| {
+      evt.account_handle = server::string(account.handle());
+      evt.buddy_uri = server::string(REMOTE);
+      evt.presence = vx_buddy_presence_state_buddy_presence_online;
+      evt.custom_message = server::string("In the lobby");
+    });
+
+    match changes.recv().await.unwrap() {
+      BuddyEvent::Updated(buddy) => {
+        assert!(buddy.is_online());
+        assert_eq!(buddy.presence.status, PresenceStatus::Online);
+        assert_eq!(buddy.presence.message, "In the lobby");
+      },
+      other => panic!("unexpected {:?}", other),
+    }
+
+    assert!(buddies.buddy(&friend).unwrap().is_online());
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn blocks_subscriptions_on_request() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, _session) = join(&client).await;
+    let buddies = account.buddy_list();
+    let mut changes = buddies.events();
+
+    server::emit::<vx_evt_subscription>(|evt| {
+      evt.account_handle = server::string(account.handle());
+      evt.buddy_uri = server::string(REMOTE);
+      evt.displayname = server::string("Friend");
+      evt.subscription_handle = server::string("subscription-1");
+    });
+
+    let request = match changes.recv().await.unwrap() {
+      BuddyEvent::SubscriptionRequested(request) => request,
+      other => panic!("unexpected {:?}", other),
+    };
+
+    assert_eq!(request.from, Some(UserUri::parse(REMOTE).unwrap()));
+    assert_eq!(request.from_uri, REMOTE);
+
+    buddies.reply(&request, SubscriptionReply::Block, false).await.unwrap();
+    assert!(buddies.block_rules().is_empty());
+
+    buddies.reply(&request, SubscriptionReply::Block, true).await.unwrap();
+    assert_eq!(buddies.block_rules(), vec![REMOTE.to_string()]);
+    assert!(server::issued().contains(&vx_request_type_req_account_send_subscription_reply));
+
+    client.shutdown();
+  });
+}
+
+/// Archive queries of `kind` issued since the server was reset.
+fn archive_queries(kind: vx_request_type) -> usize {
+  server::issued().into_iter().filter(|issued| *issued == kind).count()
+}
+
+#[test]
+fn pages_through_session_archives() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (_account, session) = join(&client).await;
+    let state = session.state();
+    eventually(|| state.borrow().text == TextState::Connected).await;
+
+    let mut ids = Vec::new();
+    for n in 0..12 {
+      ids.push(server::archive_message(CHANNEL, REMOTE, &format!("hello {}", n)));
+      server::archive_message("sip:confctl-g-issuer.arena@vdx5.vivox.com", REMOTE, "elsewhere");
+    }
+
+    // Pages of 5, 5 and a short one of 2 that ends the query
+    let query = ArchiveQuery { page_size: 5, ..ArchiveQuery::default() };
+    let messages: Vec<ArchivedMessage> = session.archive(query)
+      .map(|message| message.unwrap())
+      .collect()
+      .await;
+
+    assert_eq!(messages.iter().map(|message| &message.id).collect::<Vec<_>>(), ids.iter().collect::<Vec<_>>());
+    assert!(messages.iter().all(|message| message.channel_uri == CHANNEL && message.sender_uri == REMOTE));
+    assert_eq!(messages[11].body, "hello 11");
+    assert_eq!(archive_queries(vx_request_type_req_session_archive_query), 3);
+
+    // The cursor continues after a known message, the limit ends the query
+    // in the middle of the second page
+    let query = ArchiveQuery {
+      after: Some(ids[3].clone()),
+      page_size: 5,
+      limit: Some(7),
+      ..ArchiveQuery::default()
+    };
+    let messages: Vec<ArchivedMessage> = session.archive(query)
+      .map(|message| message.unwrap())
+      .collect()
+      .await;
+
+    assert_eq!(messages.iter().map(|message| &message.id).collect::<Vec<_>>(), ids[4..11].iter().collect::<Vec<_>>());
+    assert_eq!(archive_queries(vx_request_type_req_session_archive_query), 5);
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn pages_through_account_archives() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, _session) = join(&client).await;
+    let arena = "sip:confctl-g-issuer.arena@vdx5.vivox.com";
+
+    for n in 0..5 {
+      server::archive_message(CHANNEL, REMOTE, &format!("lobby {}", n));
+      server::archive_message(arena, account.uri(), &format!("arena {}", n));
+    }
+
+    // A full last page takes one more, empty, page to notice the end
+    let query = ArchiveQuery { page_size: 5, ..ArchiveQuery::default() };
+    let messages: Vec<ArchivedMessage> = account.archive(query)
+      .map(|message| message.unwrap())
+      .collect()
+      .await;
+
+    assert_eq!(messages.len(), 10);
+    assert_eq!(messages[0].channel_uri, CHANNEL);
+    assert!(messages[0].is_inbound);
+    assert_eq!(messages[1].channel_uri, arena);
+    assert!(!messages[1].is_inbound);
+    assert_eq!(archive_queries(vx_request_type_req_account_archive_query), 3);
+
+    let query = ArchiveQuery {
+      channel_uri: Some(arena.to_string()),
+      page_size: 2,
+      limit: Some(4),
+      ..ArchiveQuery::default()
+    };
+    let bodies: Vec<String> = account.archive(query)
+      .map(|message| message.unwrap().body)
+      .collect()
+      .await;
+
+    assert_eq!(bodies, vec!["arena 0", "arena 1", "arena 2", "arena 3"]);
+    assert_eq!(archive_queries(vx_request_type_req_account_archive_query), 5);
+
+    // Page sizes outside 1 to 50 are refused before asking the server
+    for page_size in &[0, 51] {
+      let query = ArchiveQuery { page_size: *page_size, ..ArchiveQuery::default() };
+      let results: Vec<_> = account.archive(query).collect().await;
+
+      assert_eq!(results, vec![Err(VivoxError::INVALID_ARGUMENT)]);
+    }
+
+    assert_eq!(archive_queries(vx_request_type_req_account_archive_query), 5);
+
+    let query = ArchiveQuery { page_size: 50, ..ArchiveQuery::default() };
+    assert_eq!(account.archive(query).collect::<Vec<_>>().await.len(), 10);
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn selects_audio_devices() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, _session) = join(&client).await;
+    let devices = account.audio_devices();
+
+    let capture = devices.capture_devices().await.unwrap();
+    let microphone = capture.devices.iter().find(|device| device.id == server::CAPTURE_DEVICE).unwrap();
+
+    assert_eq!(microphone.device_type, DeviceType::Specific);
+    assert_eq!(capture.current.unwrap().device_type, DeviceType::DefaultSystem);
+    assert!(capture.devices.iter().any(|device| device.device_type == DeviceType::DefaultCommunication));
+
+    devices.set_capture_device(server::CAPTURE_DEVICE).await.unwrap();
+    assert_eq!(devices.capture_devices().await.unwrap().current.unwrap().id, server::CAPTURE_DEVICE);
+
+    let render = devices.render_devices().await.unwrap();
+    assert!(render.devices.iter().any(|device| device.id == server::RENDER_DEVICE));
+
+    devices.set_render_device(server::RENDER_DEVICE).await.unwrap();
+    assert_eq!(devices.render_devices().await.unwrap().current.unwrap().id, server::RENDER_DEVICE);
+
+    // Unknown devices leave the selection alone
+    assert_eq!(
+      devices.set_capture_device("unplugged-microphone").await,
+      Err(VivoxError::INVALID_CAPTURE_DEVICE_SPECIFIER),
+    );
+    assert_eq!(
+      devices.set_render_device(server::CAPTURE_DEVICE).await,
+      Err(VivoxError::RENDER_DEVICE_DOES_NOT_EXIST),
+    );
+    assert_eq!(devices.capture_devices().await.unwrap().current.unwrap().id, server::CAPTURE_DEVICE);
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn reports_hot_swapped_devices() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let mut changes = client.audio_devices().changes();
+
+    server::hot_swap(
+      vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_audio_device_added,
+      "usb-headset",
+      "USB Headset",
+    );
+    server::hot_swap(
+      vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_active_capture_device_changed,
+      "usb-headset",
+      "USB Headset",
+    );
+    server::hot_swap(
+      vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_active_render_device_removed,
+      server::RENDER_DEVICE,
+      "Mock Speakers",
+    );
+
+    match changes.recv().await.unwrap() {
+      DeviceChange::Added(device) => {
+        assert_eq!(device.id, "usb-headset");
+        assert_eq!(device.display_name, "USB Headset");
+      },
+      other => panic!("unexpected {:?}", other),
+    }
+
+    assert!(matches!(changes.recv().await.unwrap(), DeviceChange::CaptureChanged(device) if device.id == "usb-headset"));
+    assert!(matches!(
+      changes.recv().await.unwrap(),
+      DeviceChange::RenderRemoved(device) if device.id == server::RENDER_DEVICE
+    ));
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn keeps_audio_settings() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, _session) = join(&client).await;
+
+    assert_eq!(account.audio_settings(), AudioSettings::default());
+
+    account.set_mic_volume(70).await.unwrap();
+    account.set_speaker_volume(30).await.unwrap();
+    account.mute_mic(true).await.unwrap();
+
+    // Refused levels and failed requests don't change the snapshot
+    assert_eq!(account.set_speaker_volume(101).await, Err(VivoxError::INVALID_ARGUMENT));
+    server::fail_next(vx_request_type_req_connector_mute_local_speaker, VX_E_FAILED);
+    assert_eq!(account.mute_speaker(true).await, Err(VivoxError::FAILED));
+
+    let saved = account.audio_settings();
+    assert_eq!(saved, AudioSettings { mic_volume: 70, speaker_volume: 30, mic_muted: true, speaker_muted: false });
+
+    let json = serde_json::to_string(&saved).unwrap();
+    assert_eq!(serde_json::from_str::<AudioSettings>(&json).unwrap(), saved);
+
+    account.restore_audio_settings(AudioSettings::default()).await.unwrap();
+    assert_eq!(account.audio_settings(), AudioSettings::default());
+
+    account.restore_audio_settings(serde_json::from_str(&json).unwrap()).await.unwrap();
+    assert_eq!(account.audio_settings(), saved);
+
+    let restored = server::issued()
+      .into_iter()
+      .filter(|kind| *kind == vx_request_type_req_connector_set_local_mic_volume)
+      .count();
+    assert_eq!(restored, 3);
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn shows_local_mutes_and_volumes_in_the_roster() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (_account, session) = join(&client).await;
+
+    assert!(server::add_participant(session.handle(), REMOTE, "Friend"));
+    eventually(|| session.participant(REMOTE).is_some()).await;
+
+    session.set_participant_mute_for_me(REMOTE, true).await.unwrap();
+    session.set_participant_volume_for_me(REMOTE, 80).await.unwrap();
+
+    let friend = session.participant(REMOTE).unwrap();
+    assert!(friend.is_muted_for_me);
+    assert_eq!(friend.volume, 80);
+
+    // Only the local user is affected, the others keep their defaults
+    assert!(session.roster().iter().filter(|p| p.uri != REMOTE).all(|p| !p.is_muted_for_me && p.volume == 50));
+
+    assert_eq!(session.set_participant_volume_for_me(REMOTE, -1).await, Err(VivoxError::INVALID_ARGUMENT));
+    assert_eq!(
+      session.set_participant_mute_for_me("sip:.issuer.stranger.@vdx5.vivox.com", true).await,
+      Err(VivoxError::NO_EXIST),
+    );
+    assert_eq!(session.participant(REMOTE).unwrap().volume, 80);
+
+    // Later updates from the SDK carry the local settings along
+    assert!(server::set_speaking(session.handle(), REMOTE, true));
+    eventually(|| session.participant(REMOTE).unwrap().is_speaking).await;
+
+    let friend = session.participant(REMOTE).unwrap();
+    assert!(friend.is_muted_for_me);
+    assert_eq!(friend.volume, 80);
+
+    session.set_participant_mute_for_me(REMOTE, false).await.unwrap();
+    assert!(!session.participant(REMOTE).unwrap().is_muted_for_me);
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn reports_roster_changes() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, session) = join(&client).await;
+    let third = "sip:.issuer.third.@vdx5.vivox.com";
+
+    eventually(|| session.participant(account.uri()).is_some()).await;
+    let mut changes = session.roster_changes();
+
+    assert!(server::add_participant(session.handle(), REMOTE, "Friend"));
+
+    match changes.recv().await.unwrap() {
+      RosterChange::Added(participant) => {
+        assert_eq!(participant.uri, REMOTE);
+        assert_eq!(participant.display_name, "Friend");
+        assert!(!participant.is_current_user);
+      },
+      other => panic!("unexpected {:?}", other),
+    }
+
+    assert!(server::set_speaking(session.handle(), REMOTE, true));
+    assert!(matches!(changes.recv().await.unwrap(), RosterChange::Updated(p) if p.uri == REMOTE && p.is_speaking));
+
+    // Updates that change nothing are not reported, so the next change is
+    // the new participant
+    assert!(server::set_speaking(session.handle(), REMOTE, true));
+    assert!(server::add_participant(session.handle(), third, "Third"));
+    assert!(matches!(changes.recv().await.unwrap(), RosterChange::Added(p) if p.uri == third));
+
+    assert!(server::remove_participant(session.handle(), REMOTE, vx_participant_removed_reason_participant_kicked));
+
+    match changes.recv().await.unwrap() {
+      RosterChange::Removed(participant, reason) => {
+        assert_eq!(participant.uri, REMOTE);
+        assert!(participant.is_speaking);
+        assert_eq!(reason, RemovalReason::Kicked);
+      },
+      other => panic!("unexpected {:?}", other),
+    }
+
+    // Everyone left goes away with the session
+    assert!(server::drop_session(session.handle(), VX_E_CALL_TERMINATED_BY_SERVER));
+
+    let mut removed = Vec::new();
+    while removed.len() < 2 {
+      match changes.recv().await.unwrap() {
+        RosterChange::Removed(participant, _) => removed.push(participant.uri),
+        other => panic!("unexpected {:?}", other),
+      }
+    }
+
+    removed.sort();
+    assert_eq!(removed, vec![account.uri().to_string(), third.to_string()]);
+    assert!(session.roster().is_empty());
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn sends_channel_messages() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (account, session) = join(&client).await;
+    let state = session.state();
+    eventually(|| state.borrow().text == TextState::Connected).await;
+
+    let mut messages = session.messages();
+    let metadata = CustomMetadata {
+      namespace: "urn:game".to_string(),
+      body: "team=red".to_string(),
+    };
+
+    session.send_message("gg", Some("en"), Some(&metadata)).await.unwrap();
+
+    let message = messages.recv().await.unwrap();
+    assert_eq!(message.sender_uri, account.uri());
+    assert_eq!(message.sender_display_name, "Player");
+    assert_eq!(message.body, "gg");
+    assert_eq!(message.language.as_deref(), Some("en"));
+    assert_eq!(message.custom_metadata, Some(metadata));
+    assert_eq!(session.history(), vec![message]);
+
+    // A session joined without text can't post
+    let group = account.create_sessiongroup();
+    let arena = "sip:confctl-g-issuer.arena@vdx5.vivox.com";
+    let voice_only = group.join(group.add_session().uri(arena).unwrap().connect_audio(1)).await.unwrap();
+
+    assert_eq!(voice_only.send_message("gg", None, None).await, Err(VivoxError::SESSION_DOES_NOT_HAVE_TEXT));
+    assert!(voice_only.history().is_empty());
+
+    client.shutdown();
+  });
+}
+
+#[test]
+fn keeps_the_most_recent_messages() {
+  run(async {
+    let client = VivoxClient::new().unwrap();
+    let (_account, session) = join(&client).await;
+    let state = session.state();
+    eventually(|| state.borrow().text == TextState::Connected).await;
+
+    for n in 0..105 {
+      assert!(server::post_message(session.handle(), REMOTE, "Friend", &format!("message {}", n)));
+    }
+
+    eventually(|| session.history().last().map(|m| m.body == "message 104").unwrap_or(false)).await;
+
+    let history = session.history();
+    assert_eq!(history.len(), 100);
+    assert_eq!(history[0].body, "message 5");
+    assert!(history.iter().all(|m| m.sender_uri == REMOTE && m.session_handle == session.handle()));
+
+    client.shutdown();
+  });
+}
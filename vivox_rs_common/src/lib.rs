@@ -0,0 +1,16 @@
+//! The parts of vivox_rs that work without the Vivox SDK.
+//!
+//! Game servers handing out access tokens only need this crate, which
+//! builds anywhere Rust does. `vivox_rs` re-exports all of it.
+
+#![allow(non_camel_case_types)]
+
+extern crate serde;
+
+pub mod error;
+pub mod tokengen;
+pub mod uri;
+
+pub use crate::error::VivoxError;
+pub use crate::tokengen::TokenGenerator;
+pub use crate::uri::{ChannelType, ChannelUri, UserUri};
@@ -0,0 +1,135 @@
+use serde::{Serialize, Deserialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Serialize, Deserialize)]
+struct TokenRequest {
+  iss: String,
+  exp: u64,
+  vxa: String,
+  vxi: u64,
+  f: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  t: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  sub: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct TokenGenerator {
+  req_index: AtomicU64,
+}
+
+impl TokenGenerator {
+  pub const fn init() -> Self {
+    Self {
+      req_index: AtomicU64::new(0),
+    }
+  }
+
+  pub fn generate(
+    &self,
+    key: &str,
+    issuer: &str,
+    exp: u64,
+    vxa: &str,
+    f: &str,
+    t: Option<String>,
+  ) -> String {
+    self.generate_with_subject(key, issuer, exp, vxa, f, t, None)
+  }
+
+  /// Generates a token acting on another user, as needed by the `kick` and
+  /// `mute` actions. `sub` is the URI of the user being acted on.
+  #[allow(clippy::too_many_arguments)]
+  pub fn generate_with_subject(
+    &self,
+    key: &str,
+    issuer: &str,
+    exp: u64,
+    vxa: &str,
+    f: &str,
+    t: Option<String>,
+    sub: Option<String>,
+  ) -> String {
+    use data_encoding::BASE64URL_NOPAD;
+    use hmac::{Hmac, Mac, NewMac};
+    use sha2::Sha256;
+    // Header is static - base64url encoded {}
+    let header = BASE64URL_NOPAD.encode(b"{}");
+  
+    // Create payload and base64 encode
+    let tr = TokenRequest {
+      iss: issuer.to_string(),
+      exp,
+      vxa: vxa.to_string(),
+      vxi: self.req_index.fetch_add(1, Ordering::Relaxed),
+      f: f.to_string(),
+      t,
+      sub,
+    };
+    let mut obj = serde_json::to_string(&tr)
+      .expect("Unable to serialize object!");
+  
+    obj = BASE64URL_NOPAD.encode(obj.as_bytes());
+  
+    // Join segments to prepare for signing
+    let to_sign = format!("{}.{}", header, obj);
+  
+    // Create alias for HMAC-SHA256
+    type HmacSha256 = Hmac<Sha256>;
+  
+    let mut mac = HmacSha256::new_varkey(key.as_bytes())
+      .expect("HMAC can take key of any size");
+  
+    mac.update(to_sign.as_bytes());
+  
+    let res = mac.finalize();
+  
+    // Sign token with key and HMACSHA256, then base64 encode
+    let signed_payload = BASE64URL_NOPAD.encode(&res.into_bytes());
+  
+    // Combine header and payload with signature
+    format!("{}.{}", to_sign, signed_payload)
+  }  
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use data_encoding::BASE64URL_NOPAD;
+
+  fn payload(token: &str) -> serde_json::Value {
+    let segment = token.split('.').nth(1).unwrap();
+    let decoded = BASE64URL_NOPAD.decode(segment.as_bytes()).unwrap();
+
+    serde_json::from_slice(&decoded).unwrap()
+  }
+
+  #[test]
+  fn request_index_increments() {
+    let generator = TokenGenerator::init();
+    let first = generator.generate("key", "issuer", 0, "login", "sip:a@x", None);
+    let second = generator.generate("key", "issuer", 0, "login", "sip:a@x", None);
+
+    assert_eq!(payload(&first)["vxi"], 0);
+    assert_eq!(payload(&second)["vxi"], 1);
+  }
+
+  #[test]
+  fn subject_is_only_set_when_given() {
+    let generator = TokenGenerator::init();
+    let mute_all = generator.generate("key", "issuer", 0, "mute", "sip:a@x", Some("sip:c@x".to_string()));
+    let kick = generator.generate_with_subject(
+      "key",
+      "issuer",
+      0,
+      "kick",
+      "sip:a@x",
+      Some("sip:c@x".to_string()),
+      Some("sip:b@x".to_string()),
+    );
+
+    assert!(payload(&mute_all).get("sub").is_none());
+    assert_eq!(payload(&kick)["sub"], "sip:b@x");
+  }
+}
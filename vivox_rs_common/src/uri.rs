@@ -0,0 +1,256 @@
+use crate::error::VivoxError;
+
+use serde::{Serialize, Deserialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// SIP address of a Vivox user, `sip:<name>@<domain>`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct UserUri {
+  name: String,
+  domain: String,
+}
+
+impl UserUri {
+  /// Builds the URI for an account name such as `.issuer.player.` on a
+  /// domain like `vdx5.vivox.com`.
+  pub fn new(name: &str, domain: &str) -> Self {
+    Self {
+      name: name.to_string(),
+      domain: domain.to_string(),
+    }
+  }
+
+  /// Parses `sip:<name>@<domain>`, failing with `INVALID_ARGUMENT` for
+  /// anything else.
+  pub fn parse(input: &str) -> Result<Self, VivoxError> {
+    let rest = input.strip_prefix("sip:").ok_or(VivoxError::INVALID_ARGUMENT)?;
+    let at = rest.find('@').ok_or(VivoxError::INVALID_ARGUMENT)?;
+    let (name, domain) = (&rest[..at], &rest[at + 1..]);
+
+    let valid_name = !name.is_empty()
+      && name.chars().all(|c| c.is_alphanumeric() || is_valid_non_alphanumeric(&c));
+
+    if !valid_name || domain.is_empty() {
+      return Err(VivoxError::INVALID_ARGUMENT);
+    }
+
+    Ok(Self::new(name, domain))
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn domain(&self) -> &str {
+    &self.domain
+  }
+}
+
+impl fmt::Display for UserUri {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "sip:{}@{}", self.name, self.domain)
+  }
+}
+
+impl FromStr for UserUri {
+  type Err = VivoxError;
+
+  fn from_str(input: &str) -> Result<Self, Self::Err> {
+    Self::parse(input)
+  }
+}
+
+impl From<UserUri> for String {
+  fn from(uri: UserUri) -> Self {
+    uri.to_string()
+  }
+}
+
+impl TryFrom<String> for UserUri {
+  type Error = VivoxError;
+
+  fn try_from(input: String) -> Result<Self, Self::Error> {
+    Self::parse(&input)
+  }
+}
+
+/// Kind of channel, encoded in its URI after `confctl-`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChannelType {
+  /// `g`, everyone is heard at the same volume
+  NonPositional,
+  /// `e`, plays the local user's voice back to them
+  Echo,
+  /// `d`, voices are placed in 3D space
+  Positional,
+}
+
+impl ChannelType {
+  fn tag(self) -> char {
+    match self {
+      ChannelType::NonPositional => 'g',
+      ChannelType::Echo => 'e',
+      ChannelType::Positional => 'd',
+    }
+  }
+}
+
+/// SIP address of a Vivox channel, `sip:confctl-<type>-<issuer>.<name>@<domain>`.
+///
+/// Positional channels may carry their audio properties after the name,
+/// as in `confctl-d-issuer.arena!p-60-10-1.0-1@vdx5.vivox.com`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct ChannelUri {
+  channel_type: ChannelType,
+  issuer: String,
+  name: String,
+  properties: Option<String>,
+  domain: String,
+}
+
+impl ChannelUri {
+  pub fn new(channel_type: ChannelType, issuer: &str, name: &str, domain: &str) -> Self {
+    Self {
+      channel_type,
+      issuer: issuer.to_string(),
+      name: name.to_string(),
+      properties: None,
+      domain: domain.to_string(),
+    }
+  }
+
+  /// Parses a channel URI, failing with `INVALID_ARGUMENT` for anything
+  /// else, user URIs included.
+  pub fn parse(input: &str) -> Result<Self, VivoxError> {
+    let rest = input.strip_prefix("sip:confctl-").ok_or(VivoxError::INVALID_ARGUMENT)?;
+    let channel_type = match rest.get(..2) {
+      Some("g-") => ChannelType::NonPositional,
+      Some("e-") => ChannelType::Echo,
+      Some("d-") => ChannelType::Positional,
+      _ => return Err(VivoxError::INVALID_ARGUMENT),
+    };
+    let rest = &rest[2..];
+    let at = rest.find('@').ok_or(VivoxError::INVALID_ARGUMENT)?;
+    let (local, domain) = (&rest[..at], &rest[at + 1..]);
+    let (channel, properties) = match local.find("!p-") {
+      Some(start) => (&local[..start], Some(&local[start + 3..])),
+      None => (local, None),
+    };
+    let dot = channel.find('.').ok_or(VivoxError::INVALID_ARGUMENT)?;
+    let (issuer, name) = (&channel[..dot], &channel[dot + 1..]);
+
+    let valid = |part: &str| {
+      !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || is_valid_non_alphanumeric(&c))
+    };
+
+    if !valid(issuer) || !valid(name) || domain.is_empty() {
+      return Err(VivoxError::INVALID_ARGUMENT);
+    }
+
+    Ok(Self {
+      properties: properties.map(str::to_string),
+      ..Self::new(channel_type, issuer, name, domain)
+    })
+  }
+
+  pub fn channel_type(&self) -> ChannelType {
+    self.channel_type
+  }
+
+  pub fn issuer(&self) -> &str {
+    &self.issuer
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// The 3D properties of a positional channel, without the `!p-`.
+  pub fn properties(&self) -> Option<&str> {
+    self.properties.as_deref()
+  }
+
+  pub fn domain(&self) -> &str {
+    &self.domain
+  }
+}
+
+impl fmt::Display for ChannelUri {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "sip:confctl-{}-{}.{}", self.channel_type.tag(), self.issuer, self.name)?;
+
+    if let Some(properties) = &self.properties {
+      write!(f, "!p-{}", properties)?;
+    }
+
+    write!(f, "@{}", self.domain)
+  }
+}
+
+impl FromStr for ChannelUri {
+  type Err = VivoxError;
+
+  fn from_str(input: &str) -> Result<Self, Self::Err> {
+    Self::parse(input)
+  }
+}
+
+impl From<ChannelUri> for String {
+  fn from(uri: ChannelUri) -> Self {
+    uri.to_string()
+  }
+}
+
+impl TryFrom<String> for ChannelUri {
+  type Error = VivoxError;
+
+  fn try_from(input: String) -> Result<Self, Self::Error> {
+    Self::parse(&input)
+  }
+}
+
+/// Characters besides letters and digits allowed in user and channel names.
+pub fn is_valid_non_alphanumeric(x: &char) -> bool {
+  "-_.!~*'()&=+$,;?/".chars().any(|y| y == *x)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn round_trips_through_strings() {
+    let uri = UserUri::new(".issuer.player.", "vdx5.vivox.com");
+
+    assert_eq!(uri.to_string(), "sip:.issuer.player.@vdx5.vivox.com");
+    assert_eq!(UserUri::parse(&uri.to_string()), Ok(uri));
+  }
+
+  #[test]
+  fn rejects_malformed_uris() {
+    assert!(UserUri::parse(".issuer.player.@vdx5.vivox.com").is_err());
+    assert!(UserUri::parse("sip:.issuer.player.").is_err());
+    assert!(UserUri::parse("sip:a b@vdx5.vivox.com").is_err());
+  }
+
+  #[test]
+  fn reads_channel_types() {
+    let positional = ChannelUri::parse("sip:confctl-d-issuer.arena!p-60-10-1.0-1@vdx5.vivox.com").unwrap();
+
+    assert_eq!(positional.channel_type(), ChannelType::Positional);
+    assert_eq!((positional.issuer(), positional.name()), ("issuer", "arena"));
+    assert_eq!(positional.properties(), Some("60-10-1.0-1"));
+    assert_eq!(positional.to_string(), "sip:confctl-d-issuer.arena!p-60-10-1.0-1@vdx5.vivox.com");
+
+    let lobby = ChannelUri::parse("sip:confctl-g-issuer.lobby@vdx5.vivox.com").unwrap();
+    assert_eq!(lobby.channel_type(), ChannelType::NonPositional);
+    assert_eq!(lobby.properties(), None);
+
+    assert!(ChannelUri::parse("sip:.issuer.player.@vdx5.vivox.com").is_err());
+    assert!(ChannelUri::parse("sip:confctl-x-issuer.lobby@vdx5.vivox.com").is_err());
+  }
+}
@@ -4,104 +4,259 @@ use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+/// SDK releases with committed bindings, by the feature selecting them.
+const RELEASES: &[(&str, &str)] = &[
+  ("CARGO_FEATURE_SDK_5_9_0", "5.9.0"),
+];
+
 fn main() {
   println!("cargo:rerun-if-changed=src/vivox.h");
 
-  let sdk_path = PathBuf::from(env::var("VIVOX_SDK_PATH").expect(MISSING_SDK_PATH));
-  let mut header_path = PathBuf::from(&sdk_path);
-  header_path.push("SDK");
-  header_path.push("include");
+  let release = selected_release();
+
+  // Checked against the library at runtime, see `SDK_RELEASE`. Freshly
+  // generated bindings of an unlisted release go by the crate version.
+  println!(
+    "cargo:rustc-env=VIVOX_SDK_RELEASE={}",
+    release.unwrap_or(env!("CARGO_PKG_VERSION")),
+  );
+
+  // The mock SDK is plain Rust, there is nothing to generate or link
+  if env::var_os("CARGO_FEATURE_MOCK").is_some() {
+    return;
+  }
 
-  verify_installation(&env::var("TARGET").unwrap(), &sdk_path);
-  configure_linkage(&env::var("TARGET").unwrap(), &sdk_path);
+  let target = env::var("TARGET").unwrap();
+  let sdk_path = env::var_os("VIVOX_SDK_PATH").map(PathBuf::from);
+  let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("bindings.rs");
+  let committed = release.map(committed_bindings);
 
   println!("cargo:rerun-if-env-changed=VIVOX_SDK_PATH");
-  println!("cargo:rerun-if-changed={}", sdk_path.display());
+
+  if env::var_os("CARGO_FEATURE_GENERATE").is_some() {
+    let sdk_path = sdk_path.as_ref().expect(MISSING_SDK_PATH);
+
+    generate(sdk_path, &out_path);
+
+    if let Some(committed) = &committed {
+      check_bindings(&out_path, committed);
+    }
+  }
+  else {
+    match (&committed, &sdk_path) {
+      (Some(committed), _) if committed.exists() => {
+        println!("cargo:rerun-if-changed={}", committed.display());
+        fs::copy(committed, &out_path).unwrap();
+      },
+      // Same as `generate`, minus the comparison with bindings that are
+      // not there
+      (_, Some(sdk_path)) => {
+        generate(sdk_path, &out_path);
+
+        if let Some(committed) = &committed {
+          warn(&MISSING_BINDINGS.replace("{path}", &committed.display().to_string()));
+        }
+      },
+      (Some(committed), None) => {
+        panic!("{}", NO_BINDINGS.replace("{path}", &committed.display().to_string()))
+      },
+      (None, None) => panic!("{}", NO_RELEASE),
+    }
+  }
+
+  // docs.rs only needs the bindings, and with `dynamic` the library is
+  // opened at runtime instead
+  if env::var_os("DOCS_RS").is_some() || env::var_os("CARGO_FEATURE_DYNAMIC").is_some() {
+    return;
+  }
+
+  match sdk_path {
+    Some(sdk_path) => {
+      verify_installation(&target, &sdk_path);
+      configure_linkage(&target, &sdk_path);
+
+      println!("cargo:rerun-if-changed={}", sdk_path.display());
+    },
+    None => {
+      println!("cargo:warning=VIVOX_SDK_PATH is not set, vivoxsdk will not be linked");
+    },
+  }
+}
+
+/// The release picked through the `sdk-*` features, if any.
+fn selected_release() -> Option<&'static str> {
+  let selected: Vec<&str> = RELEASES
+    .iter()
+    .filter(|(feature, _)| env::var_os(feature).is_some())
+    .map(|(_, version)| *version)
+    .collect();
+
+  match selected.as_slice() {
+    [] => None,
+    [version] => Some(*version),
+    _ => panic!("{}", MULTIPLE_RELEASES),
+  }
+}
+
+/// Bindgen output depends on the target, mostly on how wide C enums are,
+/// so Windows and everything else get their own copy.
+fn committed_bindings(version: &str) -> PathBuf {
+  let family = match env::var("CARGO_CFG_TARGET_FAMILY").as_deref() {
+    Ok("windows") => "windows",
+    _ => "unix",
+  };
+
+  PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap())
+    .join("src")
+    .join("bindings")
+    .join(format!("{}-{}.rs", version, family))
+}
+
+fn generate(sdk_path: &Path, out_path: &Path) {
+  let header_path = sdk_path.join("SDK").join("include");
+
   println!("cargo:rerun-if-changed={}/SDK/include/vivox-config.h", sdk_path.display());
 
-  // Copy vivox headers to our src folder
-  let mut prop_dir = PathBuf::from(env::current_dir().unwrap());
-  
-  prop_dir.push("src");
-  prop_dir.push("vivox-proprietary");
-  println!("{}", prop_dir.display());
+  // bindgen reads the headers from OUT_DIR, so nothing from the SDK ends
+  // up next to the sources
+  let include_dir = out_path.parent().unwrap();
+  let prop_dir = include_dir.join("vivox-proprietary");
 
   fs::create_dir_all(&prop_dir).unwrap();
 
-  // Copy all headers into vivox-proprietary
   for entry in fs::read_dir(header_path).unwrap() {
     let entry = entry.unwrap();
-    let path = entry.path();
-    fs::copy(path, format!("{}/{}", &prop_dir.display(), entry.file_name().to_str().unwrap())).unwrap();
+    fs::copy(entry.path(), prop_dir.join(entry.file_name())).unwrap();
   }
 
+  let header = include_dir.join("vivox.h");
+
+  fs::copy("src/vivox.h", &header).unwrap();
+
   let bindings = bindgen::Builder::default()
-    .header("src/vivox.h")
+    .header(header.to_str().unwrap())
     .parse_callbacks(Box::new(bindgen::CargoCallbacks))
     .generate()
     .expect("Unable to generate bindings");
   
-  let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
   bindings
-    .write_to_file(out_path.join("bindings.rs"))
+    .write_to_file(out_path)
     .expect("Couldn't write bindings!");
+}
+
+/// Fails the build when freshly generated bindings differ from the
+/// committed ones, unless asked to replace them.
+fn check_bindings(generated: &Path, committed: &Path) {
+  println!("cargo:rerun-if-env-changed=VIVOX_UPDATE_BINDINGS");
+
+  let fresh = fs::read_to_string(generated).unwrap();
+  let current = fs::read_to_string(committed).ok();
+
+  if current.as_deref() == Some(fresh.as_str()) {
+    return;
+  }
+
+  if env::var_os("VIVOX_UPDATE_BINDINGS").is_some() {
+    fs::create_dir_all(committed.parent().unwrap()).unwrap();
+    fs::write(committed, fresh).unwrap();
+    println!("cargo:warning=Updated {}", committed.display());
+
+    return;
+  }
+
+  panic!("{}", OUTDATED_BINDINGS.replace("{path}", &committed.display().to_string()));
+}
+
+/// Shows a message in the build output, which takes one line at a time.
+fn warn(message: &str) {
+  for line in message.lines().filter(|line| !line.is_empty()) {
+    println!("cargo:warning={}", line);
+  }
+}
+
+/// Where the SDK keeps the libraries for a target and how they are linked.
+struct Layout {
+  /// Relative to `VIVOX_SDK_PATH`
+  dir: &'static str,
+  /// Files that must exist for linking to work
+  files: &'static [&'static str],
+  /// Values for `cargo:rustc-link-lib`
+  libs: &'static [&'static str],
+  setup: &'static str,
+}
 
-  // Delete local proprietary files
-  fs::remove_dir_all(prop_dir).unwrap();
+fn layout(target: &str) -> Option<Layout> {
+  let layout = match target {
+    "x86_64-apple-darwin" | "aarch64-apple-darwin" => Layout {
+      dir: "SDK/Libraries/Release",
+      files: &["libvivoxsdk.dylib", "libortp.dylib"],
+      libs: &["vivoxsdk", "ortp"],
+      setup: MACOS_SETUP,
+    },
+
+    "x86_64-pc-windows-gnu" | "x86_64-pc-windows-msvc" => Layout {
+      dir: "SDK/Libraries/Release/x64",
+      files: &["vivoxsdk.dll", "vivoxsdk.lib"],
+      libs: &["vivoxsdk"],
+      setup: WINDOWS_SETUP,
+    },
+
+    "x86_64-unknown-linux-gnu" => Layout {
+      dir: "SDK/Libraries/Release",
+      files: &["libvivoxsdk.so"],
+      libs: &["vivoxsdk"],
+      setup: LINUX_SETUP,
+    },
+
+    "aarch64-linux-android" => Layout {
+      dir: "SDK/Libraries/Release/arm64-v8a",
+      files: &["libvivox-sdk.so"],
+      libs: &["vivox-sdk"],
+      setup: ANDROID_SETUP,
+    },
+
+    // The iOS SDK is a static library, so everything it uses has to be
+    // linked as well
+    "aarch64-apple-ios" => Layout {
+      dir: "SDK/Libraries/Release",
+      files: &["libvivoxsdk.a"],
+      libs: &[
+        "static=vivoxsdk",
+        "c++",
+        "framework=AudioToolbox",
+        "framework=AVFoundation",
+        "framework=CFNetwork",
+        "framework=Security",
+        "framework=SystemConfiguration",
+      ],
+      setup: IOS_SETUP,
+    },
+
+    _ => return None,
+  };
+
+  Some(layout)
 }
 
 fn verify_installation(target: &str, sdk_path: &Path) {
-  match target {
-    "x86_64-apple-darwin" => {
-      assert!(
-        sdk_path
-          .join("SDK/Libraries/Release/libvivoxsdk.dylib")
-          .exists(),
-        MISSING_SETUP
-      );
-      assert!(
-        sdk_path
-          .join("SDK/Libraries/Release/libortp.dylib")
-          .exists(),
-        MISSING_SETUP
-      );
-    }
+  let layout = layout(target).unwrap_or_else(|| panic!("{}", INCOMPATIBLE_PLATFORM.replace("{target}", target)));
 
-    "x86_64-pc-windows-gnu" | "x86_64-pc-windows-msvc" => {
-      assert!(
-        sdk_path.join("SDK/Libraries/Release/x64/vivoxsdk.dll").exists(),
-        MISSING_SETUP
-      );
-      assert!(
-        sdk_path.join("SDK/Libraries/Release/x64/vivoxsdk.lib").exists(),
-        MISSING_SETUP
-      );
-    }
+  for file in layout.files {
+    let path = sdk_path.join(layout.dir).join(file);
 
-    _ => panic!(INCOMPATIBLE_PLATFORM),
+    if !path.exists() {
+      panic!("{}", layout.setup.replace("{missing}", &path.display().to_string()));
+    }
   }
 }
 
 fn configure_linkage(target: &str, sdk_path: &Path) {
-  match target {
-    "x86_64-apple-darwin" => {
-      println!("cargo:rustc-link-lib=vivoxsdk");
-      println!("cargo:rustc-link-lib=ortp");
-      println!(
-        "cargo:rustc-link-search={}",
-        sdk_path.join("SDK/Libraries/Release").display()
-      );
+  if let Some(layout) = layout(target) {
+    for lib in layout.libs {
+      println!("cargo:rustc-link-lib={}", lib);
     }
 
-    "x86_64-pc-windows-gnu" | "x86_64-pc-windows-msvc" => {
-      println!("cargo:rustc-link-lib=vivoxsdk");
-      println!(
-        "cargo:rustc-link-search={}",
-        sdk_path.join("SDK/Libraries/Release/x64").display()
-      );
-    }
-
-    _ => {}
+    println!("cargo:rustc-link-search={}", sdk_path.join(layout.dir).display());
   }
 }
 
@@ -129,38 +284,139 @@ https://github.com/MOONMOONOSS/vivox-rs
 Thanks, and apologies for the inconvenience
 "#;
 
-const MISSING_SETUP: &str = r#"
+const MACOS_SETUP: &str = r#"
 vivox-rs: Hello,
 
-You are trying to link to the Discord Game SDK.
-Some additional set-up is required, namely some files need to be copied for the linker:
-
-# Linux: You cannot create bindings for Vivox Core SDK targeting Linux
+You are trying to link to the Vivox Core SDK for macOS, but this file is
+missing:
+{missing}
 
-# Mac OS: prepend with `SDK/Libraries/Release` and add to library search path
+The libraries ship without their `lib` prefix, so some files need to be
+copied for the linker:
 $ cp $VIVOX_SDK_PATH/SDK/Libraries/Release/{,lib}vivoxsdk.dylib
 $ cp $VIVOX_SDK_PATH/SDK/Libraries/Release/{,lib}ortp.dylib
 $ export DYLD_LIBRARY_PATH=${DYLD_LIBRARY_PATH:+${DYLD_LIBRARY_PATH}:}$VIVOX_SDK_PATH/SDK/Libraries/Release
 
-# Windows: No action is needed
+Apple silicon needs an SDK release shipping universal libraries.
 
-After all this, `cargo build` and `cargo run` should function as expected.
 Please report any issues you have at:
 https://github.com/MOONMOONOSS/vivox-rs
+"#;
 
-Thanks, and apologies for the inconvenience
+const WINDOWS_SETUP: &str = r#"
+vivox-rs: Hello,
+
+You are trying to link to the Vivox Core SDK for Windows, but this file is
+missing:
+{missing}
+
+Make sure VIVOX_SDK_PATH points at the extracted Windows SDK. Only 64-bit
+libraries are supported. Copy vivoxsdk.dll next to your executable to run
+it.
+
+Please report any issues you have at:
+https://github.com/MOONMOONOSS/vivox-rs
+"#;
+
+const LINUX_SETUP: &str = r#"
+vivox-rs: Hello,
+
+You are trying to link to the Vivox Core SDK for Linux, but this file is
+missing:
+{missing}
+
+The Linux SDK is distributed separately from the desktop ones, ask Vivox
+for access if it is not listed with your downloads. To run the binaries,
+add the library to the search path:
+$ export LD_LIBRARY_PATH=${LD_LIBRARY_PATH:+${LD_LIBRARY_PATH}:}$VIVOX_SDK_PATH/SDK/Libraries/Release
+
+Please report any issues you have at:
+https://github.com/MOONMOONOSS/vivox-rs
+"#;
+
+const ANDROID_SETUP: &str = r#"
+vivox-rs: Hello,
+
+You are trying to link to the Vivox Core SDK for Android, but this file is
+missing:
+{missing}
+
+Point VIVOX_SDK_PATH at the extracted Android SDK. Your APK also has to
+package libvivox-sdk.so under lib/arm64-v8a, and the SDK's Java classes
+must be initialized before the first `vx_initialize3` call.
+
+Please report any issues you have at:
+https://github.com/MOONMOONOSS/vivox-rs
+"#;
+
+const IOS_SETUP: &str = r#"
+vivox-rs: Hello,
+
+You are trying to link to the Vivox Core SDK for iOS, but this file is
+missing:
+{missing}
+
+Point VIVOX_SDK_PATH at the extracted iOS SDK. The library is linked
+statically, so nothing has to be bundled with the app, but it needs the
+microphone usage description (NSMicrophoneUsageDescription) in its
+Info.plist.
+
+Please report any issues you have at:
+https://github.com/MOONMOONOSS/vivox-rs
 "#;
 
 const INCOMPATIBLE_PLATFORM: &str = r#"
 vivox-rs: Hello,
 
-You are trying to link to the Vivox Core SDK.
+You are trying to link to the Vivox Core SDK for {target}.
+
+Unfortunately, this target is not supported. The SDK can be linked for
+x86_64-pc-windows-msvc, x86_64-pc-windows-gnu, x86_64-apple-darwin,
+aarch64-apple-darwin, x86_64-unknown-linux-gnu, aarch64-linux-android
+and aarch64-apple-ios.
+
+The `mock` feature builds everywhere if you only need to test.
+"#;
+
+const NO_RELEASE: &str = r#"
+vivox-rs: Hello,
+
+No bindings were selected. Either enable the feature of the SDK release
+you are using, such as `sdk-5-9-0`, or set VIVOX_SDK_PATH to create them
+from your own copy of the SDK.
+"#;
+
+const MULTIPLE_RELEASES: &str = r#"
+vivox-rs: Hello,
+
+More than one `sdk-*` feature is enabled. Bindings of different SDK
+releases are not compatible with each other, please pick one.
+"#;
+
+const MISSING_BINDINGS: &str = r#"
+vivox-rs: There are no committed bindings for this SDK release and target at
+{path}
+so they were generated from VIVOX_SDK_PATH. Commit them with:
+$ VIVOX_UPDATE_BINDINGS=1 cargo build -p vivox_rs_sys --features generate
+"#;
 
-Unfortunately, the platform you are trying to target is not supported.
+const NO_BINDINGS: &str = r#"
+vivox-rs: There are no committed bindings for this SDK release and target at
+{path}
+and VIVOX_SDK_PATH is not set to generate them. Point it at the SDK and
+commit the result with:
+$ VIVOX_UPDATE_BINDINGS=1 cargo build -p vivox_rs_sys --features generate
+To build without the SDK, enable the `mock` feature instead.
+"#;
+
+const OUTDATED_BINDINGS: &str = r#"
+vivox-rs: Hello,
 
-Vivox has no intentions of providing binaries for Linux.
+The bindings generated from your SDK differ from the committed ones at:
+{path}
 
-I sincerely apologize for cutting your adventure short.
+If your SDK is the release those bindings belong to, update them with:
+$ VIVOX_UPDATE_BINDINGS=1 cargo build -p vivox_rs_sys --features generate
 
-All the best.
+Otherwise select the `sdk-*` feature matching your SDK.
 "#;
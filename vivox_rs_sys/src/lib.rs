@@ -2,4 +2,29 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 
-include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+/// SDK release the bindings were generated from. Libraries of another
+/// minor release lay their structs out differently.
+pub const SDK_RELEASE: &str = env!("VIVOX_SDK_RELEASE");
+
+#[cfg(not(feature = "mock"))]
+#[allow(dead_code)]
+mod bindings {
+  include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
+
+#[cfg(all(not(feature = "mock"), not(feature = "dynamic")))]
+pub use bindings::*;
+
+// Shadows the linked functions of the bindings with ones forwarding to the
+// loaded library
+#[cfg(all(not(feature = "mock"), feature = "dynamic"))]
+pub mod loader;
+
+#[cfg(all(not(feature = "mock"), feature = "dynamic"))]
+pub use loader::*;
+
+#[cfg(feature = "mock")]
+pub mod mock;
+
+#[cfg(feature = "mock")]
+pub use mock::sdk::*;
@@ -0,0 +1,162 @@
+//! Resolves the SDK at runtime instead of linking it, enabled by the
+//! `dynamic` feature.
+//!
+//! `load` has to succeed before anything else calls into the SDK. The
+//! `vx_*` functions exported from the crate root keep their signatures and
+//! forward to the loaded library, and panic when nothing was loaded.
+
+// Each function has the contract of the C function it forwards to
+#![allow(clippy::missing_safety_doc)]
+
+use libloading::Library;
+use once_cell::sync::OnceCell;
+use std::error::Error;
+use std::fmt;
+use std::os::raw::{c_char, c_int, c_void};
+use std::path::{Path, PathBuf};
+
+pub use super::bindings::*;
+
+#[derive(Debug)]
+pub enum LoadError {
+  /// The library does not exist or could not be opened
+  Library { path: PathBuf, source: libloading::Error },
+  /// The library lacks a function the bindings need, typically because it
+  /// is from another SDK release
+  Symbol { name: &'static str, source: libloading::Error },
+  /// `load` already succeeded once
+  AlreadyLoaded,
+}
+
+impl fmt::Display for LoadError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      LoadError::Library { path, source } => write!(f, "unable to open {}: {}", path.display(), source),
+      LoadError::Symbol { name, source } => write!(f, "the Vivox SDK has no {}: {}", name, source),
+      LoadError::AlreadyLoaded => write!(f, "the Vivox SDK is already loaded"),
+    }
+  }
+}
+
+impl Error for LoadError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      LoadError::Library { source, .. } | LoadError::Symbol { source, .. } => Some(source),
+      LoadError::AlreadyLoaded => None,
+    }
+  }
+}
+
+static SDK: OnceCell<Symbols> = OnceCell::new();
+
+/// Opens the SDK library at `path`, such as `vivoxsdk.dll` or
+/// `libvivoxsdk.so`, and resolves every function the bindings use.
+///
+/// The library stays loaded for the rest of the process.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<(), LoadError> {
+  let path = path.as_ref();
+
+  if SDK.get().is_some() {
+    return Err(LoadError::AlreadyLoaded);
+  }
+
+  let library = Library::new(path).map_err(|source| LoadError::Library {
+    path: path.to_path_buf(),
+    source,
+  })?;
+
+  SDK.set(Symbols::resolve(library)?).map_err(|_| LoadError::AlreadyLoaded)
+}
+
+pub fn is_loaded() -> bool {
+  SDK.get().is_some()
+}
+
+fn sdk() -> &'static Symbols {
+  SDK.get().expect("vivox_rs_sys::load must succeed before the SDK is used")
+}
+
+macro_rules! symbols {
+  ($(fn $name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)?;)*) => {
+    struct Symbols {
+      _library: Library,
+      $($name: unsafe extern "C" fn($($ty),*) $(-> $ret)?,)*
+    }
+
+    impl Symbols {
+      fn resolve(library: Library) -> Result<Self, LoadError> {
+        $(
+          let $name = unsafe {
+            *library
+              .get::<unsafe extern "C" fn($($ty),*) $(-> $ret)?>(concat!(stringify!($name), "\0").as_bytes())
+              .map_err(|source| LoadError::Symbol { name: stringify!($name), source })?
+          };
+        )*
+
+        Ok(Self {
+          _library: library,
+          $($name,)*
+        })
+      }
+    }
+
+    $(
+      pub unsafe fn $name($($arg: $ty),*) $(-> $ret)? {
+        (sdk().$name)($($arg),*)
+      }
+    )*
+  };
+}
+
+symbols! {
+  fn vx_get_default_config3(config: *mut vx_sdk_config_t, config_size: size_t) -> c_int;
+  fn vx_initialize3(config: *mut vx_sdk_config_t, config_size: size_t) -> c_int;
+  fn vx_uninitialize() -> c_int;
+  fn vx_issue_request(request: *mut vx_req_base_t) -> c_int;
+  fn vx_get_message(message: *mut *mut vx_message_base_t) -> c_int;
+  fn vx_destroy_message(message: *mut vx_message_base_t) -> c_int;
+  fn vx_strdup(input: *const c_char) -> *mut c_char;
+  fn vx_free(input: *mut c_void);
+  fn vx_get_error_string(error_code: c_int) -> *const c_char;
+  fn vx_get_sdk_version_info() -> *const c_char;
+  fn vx_req_account_anonymous_login_create(req: *mut *mut vx_req_account_anonymous_login) -> c_int;
+  fn vx_req_account_archive_query_create(req: *mut *mut vx_req_account_archive_query) -> c_int;
+  fn vx_req_account_buddy_delete_create(req: *mut *mut vx_req_account_buddy_delete) -> c_int;
+  fn vx_req_account_buddy_set_create(req: *mut *mut vx_req_account_buddy_set) -> c_int;
+  fn vx_req_account_create_block_rule_create(req: *mut *mut vx_req_account_create_block_rule) -> c_int;
+  fn vx_req_account_delete_block_rule_create(req: *mut *mut vx_req_account_delete_block_rule) -> c_int;
+  fn vx_req_account_logout_create(req: *mut *mut vx_req_account_logout) -> c_int;
+  fn vx_req_account_send_message_create(req: *mut *mut vx_req_account_send_message) -> c_int;
+  fn vx_req_account_send_subscription_reply_create(req: *mut *mut vx_req_account_send_subscription_reply) -> c_int;
+  fn vx_req_account_set_presence_create(req: *mut *mut vx_req_account_set_presence) -> c_int;
+  fn vx_req_aux_get_capture_devices_create(req: *mut *mut vx_req_aux_get_capture_devices) -> c_int;
+  fn vx_req_aux_get_render_devices_create(req: *mut *mut vx_req_aux_get_render_devices) -> c_int;
+  fn vx_req_aux_set_capture_device_create(req: *mut *mut vx_req_aux_set_capture_device) -> c_int;
+  fn vx_req_aux_set_render_device_create(req: *mut *mut vx_req_aux_set_render_device) -> c_int;
+  fn vx_req_aux_set_vad_properties_create(req: *mut *mut vx_req_aux_set_vad_properties) -> c_int;
+  fn vx_req_channel_kick_user_create(req: *mut *mut vx_req_channel_kick_user) -> c_int;
+  fn vx_req_channel_mute_all_users_create(req: *mut *mut vx_req_channel_mute_all_users) -> c_int;
+  fn vx_req_channel_mute_user_create(req: *mut *mut vx_req_channel_mute_user) -> c_int;
+  fn vx_req_connector_create_create(req: *mut *mut vx_req_connector_create) -> c_int;
+  fn vx_req_connector_initiate_shutdown_create(req: *mut *mut vx_req_connector_initiate_shutdown) -> c_int;
+  fn vx_req_connector_mute_local_mic_create(req: *mut *mut vx_req_connector_mute_local_mic) -> c_int;
+  fn vx_req_connector_mute_local_speaker_create(req: *mut *mut vx_req_connector_mute_local_speaker) -> c_int;
+  fn vx_req_connector_set_local_mic_volume_create(req: *mut *mut vx_req_connector_set_local_mic_volume) -> c_int;
+  fn vx_req_connector_set_local_speaker_volume_create(req: *mut *mut vx_req_connector_set_local_speaker_volume) -> c_int;
+  fn vx_req_session_archive_query_create(req: *mut *mut vx_req_session_archive_query) -> c_int;
+  fn vx_req_session_media_connect_create(req: *mut *mut vx_req_session_media_connect) -> c_int;
+  fn vx_req_session_media_disconnect_create(req: *mut *mut vx_req_session_media_disconnect) -> c_int;
+  fn vx_req_session_send_message_create(req: *mut *mut vx_req_session_send_message) -> c_int;
+  fn vx_req_session_set_3d_position_create(req: *mut *mut vx_req_session_set_3d_position) -> c_int;
+  fn vx_req_session_set_local_render_volume_create(req: *mut *mut vx_req_session_set_local_render_volume) -> c_int;
+  fn vx_req_session_set_participant_mute_for_me_create(req: *mut *mut vx_req_session_set_participant_mute_for_me) -> c_int;
+  fn vx_req_session_set_participant_volume_for_me_create(req: *mut *mut vx_req_session_set_participant_volume_for_me) -> c_int;
+  fn vx_req_session_text_connect_create(req: *mut *mut vx_req_session_text_connect) -> c_int;
+  fn vx_req_session_text_disconnect_create(req: *mut *mut vx_req_session_text_disconnect) -> c_int;
+  fn vx_req_sessiongroup_add_session_create(req: *mut *mut vx_req_sessiongroup_add_session) -> c_int;
+  fn vx_req_sessiongroup_remove_session_create(req: *mut *mut vx_req_sessiongroup_remove_session) -> c_int;
+  fn vx_req_sessiongroup_set_tx_all_sessions_create(req: *mut *mut vx_req_sessiongroup_set_tx_all_sessions) -> c_int;
+  fn vx_req_sessiongroup_set_tx_no_session_create(req: *mut *mut vx_req_sessiongroup_set_tx_no_session) -> c_int;
+  fn vx_req_sessiongroup_set_tx_session_create(req: *mut *mut vx_req_sessiongroup_set_tx_session) -> c_int;
+  fn vx_req_sessiongroup_terminate_create(req: *mut *mut vx_req_sessiongroup_terminate) -> c_int;
+}
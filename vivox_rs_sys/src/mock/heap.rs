@@ -0,0 +1,69 @@
+//! Ownership of the messages handed out by the mock.
+//!
+//! The SDK frees every message through `vx_destroy_message`, which only
+//! sees the base pointer. Each allocation therefore remembers how to free
+//! the concrete struct behind it.
+
+use super::Message;
+use super::types::vx_message_base_t;
+
+use std::collections::BTreeMap;
+use std::mem;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+type Free = unsafe fn(*mut vx_message_base_t);
+
+static LIVE: Mutex<BTreeMap<usize, Free>> = Mutex::new(BTreeMap::new());
+
+/// Allocates a zeroed message with its type fields filled in.
+pub(crate) fn alloc<T: Message>() -> *mut T {
+  let mut msg: Box<T> = Box::new(unsafe { mem::zeroed() });
+
+  msg.stamp();
+
+  let ptr = Box::into_raw(msg);
+
+  unsafe {
+    (*(ptr as *mut vx_message_base_t)).create_time_ms = now_ms();
+  }
+
+  LIVE.lock().unwrap().insert(ptr as usize, free::<T>);
+
+  ptr
+}
+
+/// Frees a message allocated by `alloc`. Returns false for pointers the
+/// mock does not own, including ones that were already destroyed.
+pub(crate) unsafe fn destroy(msg: *mut vx_message_base_t) -> bool {
+  // Freeing a response frees its request, so the lock must be released
+  // before calling into the message
+  let free = LIVE.lock().unwrap().remove(&(msg as usize));
+
+  match free {
+    Some(free) => {
+      free(msg);
+
+      true
+    },
+    None => false,
+  }
+}
+
+/// Number of messages not destroyed yet.
+pub(crate) fn live() -> usize {
+  LIVE.lock().unwrap().len()
+}
+
+unsafe fn free<T: Message>(msg: *mut vx_message_base_t) {
+  let mut msg = Box::from_raw(msg as *mut T);
+
+  msg.release();
+}
+
+fn now_ms() -> u64 {
+  SystemTime::now()
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .map(|elapsed| elapsed.as_millis() as u64)
+    .unwrap_or(0)
+}
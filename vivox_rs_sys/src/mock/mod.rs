@@ -0,0 +1,88 @@
+//! In-process stand-in for the Vivox SDK, enabled by the `mock` feature.
+//!
+//! The `vx_*` entry points keep the signatures and semantics `vivox_rs`
+//! relies on: requests are answered asynchronously through
+//! `vx_get_message`, every message is freed with `vx_destroy_message` and
+//! strings come from `vx_strdup`. Requests are answered by the fake server
+//! in `server`, which tests can script to fail requests or raise events.
+//!
+//! Nothing leaves the process, so the mock runs anywhere Rust does,
+//! including targets the real SDK has no binaries for.
+
+// The entry points keep the contracts of the C API they stand in for
+#![allow(clippy::missing_safety_doc)]
+
+mod heap;
+mod types;
+
+pub mod sdk;
+pub mod server;
+
+use std::ffi::CString;
+use std::os::raw::{c_char, c_double, c_int, c_uint, c_void};
+use std::ptr;
+
+use types::vx_device_t;
+
+/// A struct the mock hands to the wrapper as a message.
+pub trait Message: Release {
+  /// Fills in the message and sub type of a zeroed struct.
+  fn stamp(&mut self);
+}
+
+/// Frees what a field owns when the struct holding it is destroyed.
+pub trait Release {
+  /// # Safety
+  /// Pointers must be null or have been allocated by the mock.
+  unsafe fn release(&mut self) {}
+}
+
+impl Release for c_int {}
+impl Release for c_uint {}
+impl Release for c_double {}
+impl Release for [c_double; 3] {}
+impl Release for *mut c_void {}
+
+impl Release for *mut c_char {
+  unsafe fn release(&mut self) {
+    if !self.is_null() {
+      drop(CString::from_raw(*self));
+      *self = ptr::null_mut();
+    }
+  }
+}
+
+impl Release for *mut vx_device_t {
+  unsafe fn release(&mut self) {
+    if !self.is_null() {
+      Box::from_raw(*self).release();
+      *self = ptr::null_mut();
+    }
+  }
+}
+
+/// Device lists are allocated with a trailing null so they can be freed
+/// without the count stored next to them.
+impl Release for *mut *mut vx_device_t {
+  unsafe fn release(&mut self) {
+    if self.is_null() {
+      return;
+    }
+
+    let mut len = 0;
+
+    while !(*self.add(len)).is_null() {
+      (*self.add(len)).release();
+      len += 1;
+    }
+
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(*self, len + 1)));
+    *self = ptr::null_mut();
+  }
+}
+
+/// Number of messages handed out and not destroyed yet, for catching
+/// leaks in tests.
+pub fn live_messages() -> usize {
+  heap::live()
+}
@@ -0,0 +1,178 @@
+//! The `vx_*` entry points, re-exported from the crate root in place of
+//! the generated bindings.
+
+pub use super::types::*;
+
+use super::{heap, server};
+
+use std::collections::VecDeque;
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::Mutex;
+
+struct Sdk {
+  initialized: bool,
+  /// Messages waiting for `vx_get_message`, oldest first
+  queue: VecDeque<usize>,
+}
+
+static SDK: Mutex<Sdk> = Mutex::new(Sdk {
+  initialized: false,
+  queue: VecDeque::new(),
+});
+
+/// Queues a message for `vx_get_message`.
+pub(crate) fn post(msg: *mut vx_message_base_t) {
+  SDK.lock().unwrap().queue.push_back(msg as usize);
+}
+
+pub(crate) fn is_initialized() -> bool {
+  SDK.lock().unwrap().initialized
+}
+
+pub unsafe extern "C" fn vx_get_default_config3(config: *mut vx_sdk_config_t, config_size: size_t) -> c_int {
+  if config.is_null() || config_size != server::config_size() {
+    return VX_E_INVALID_ARGUMENT as c_int;
+  }
+
+  *config = vx_sdk_config_t {
+    num_codec_threads: 1,
+    num_voice_threads: 1,
+    num_web_threads: 1,
+    render_source_queue_depth_max: 50,
+    render_source_initial_buffer_count: 5,
+    upstream_jitter_frame_count: 0,
+    max_logins_per_user: 0,
+    initial_log_level: 0,
+    disable_devices: 0,
+    force_capture_silence: 0,
+  };
+
+  VX_E_SUCCESS as c_int
+}
+
+/// Like the real SDK, refuses to initialize twice in a row.
+pub unsafe extern "C" fn vx_initialize3(config: *mut vx_sdk_config_t, config_size: size_t) -> c_int {
+  if config.is_null() || config_size != server::config_size() {
+    return VX_E_INVALID_ARGUMENT as c_int;
+  }
+
+  let mut sdk = SDK.lock().unwrap();
+
+  if sdk.initialized {
+    return VX_E_ALREADY_INITIALIZED as c_int;
+  }
+
+  sdk.initialized = true;
+
+  VX_E_SUCCESS as c_int
+}
+
+/// Drops undelivered messages and every connector. Scripted behaviour of
+/// the fake server is kept.
+pub unsafe extern "C" fn vx_uninitialize() -> c_int {
+  let undelivered = {
+    let mut sdk = SDK.lock().unwrap();
+
+    if !sdk.initialized {
+      return VX_E_NOT_INITIALIZED as c_int;
+    }
+
+    sdk.initialized = false;
+    mem::take(&mut sdk.queue)
+  };
+
+  for msg in undelivered {
+    heap::destroy(msg as *mut vx_message_base_t);
+  }
+
+  server::disconnect();
+
+  VX_E_SUCCESS as c_int
+}
+
+/// Takes ownership of the request. It is freed along with its response.
+pub unsafe extern "C" fn vx_issue_request(req: *mut vx_req_base_t) -> c_int {
+  if !is_initialized() {
+    return VX_E_NOT_INITIALIZED as c_int;
+  }
+
+  if req.is_null() {
+    return VX_E_INVALID_ARGUMENT as c_int;
+  }
+
+  server::handle(req);
+
+  VX_E_SUCCESS as c_int
+}
+
+pub unsafe extern "C" fn vx_get_message(msg: *mut *mut vx_message_base_t) -> c_int {
+  let mut sdk = SDK.lock().unwrap();
+
+  if !sdk.initialized || msg.is_null() {
+    return VX_GET_MESSAGE_FAILURE as c_int;
+  }
+
+  match sdk.queue.pop_front() {
+    Some(next) => {
+      *msg = next as *mut vx_message_base_t;
+
+      VX_GET_MESSAGE_AVAILABLE as c_int
+    },
+    None => VX_GET_MESSAGE_NO_MESSAGE as c_int,
+  }
+}
+
+pub unsafe extern "C" fn vx_destroy_message(msg: *mut vx_message_base_t) -> c_int {
+  match heap::destroy(msg) {
+    true => VX_E_SUCCESS as c_int,
+    false => VX_E_INVALID_ARGUMENT as c_int,
+  }
+}
+
+pub unsafe extern "C" fn vx_strdup(input: *const c_char) -> *mut c_char {
+  if input.is_null() {
+    return std::ptr::null_mut();
+  }
+
+  CStr::from_ptr(input).to_owned().into_raw()
+}
+
+pub unsafe extern "C" fn vx_free(input: *mut c_void) {
+  if !input.is_null() {
+    drop(CString::from_raw(input as *mut c_char));
+  }
+}
+
+/// The release of the bindings unless a test set another, see
+/// `server::set_sdk_version`.
+pub unsafe extern "C" fn vx_get_sdk_version_info() -> *const c_char {
+  server::sdk_version()
+    .unwrap_or_else(|| concat!(env!("VIVOX_SDK_RELEASE"), ".0\0").as_ptr() as *const c_char)
+}
+
+pub unsafe extern "C" fn vx_get_error_string(error_code: c_int) -> *const c_char {
+  let message: &'static [u8] = match error_code as u32 {
+    VX_E_SUCCESS => b"Success\0",
+    VX_E_NO_EXIST => b"The object does not exist\0",
+    VX_E_FAILED => b"Failed\0",
+    VX_E_NOT_LOGGED_IN => b"Not logged in\0",
+    VX_E_INVALID_ARGUMENT => b"Invalid argument\0",
+    VX_E_SESSION_DOES_NOT_HAVE_TEXT => b"The session is not connected to text\0",
+    VX_E_NOT_INITIALIZED => b"The SDK is not initialized\0",
+    VX_E_RENDER_DEVICE_DOES_NOT_EXIST => b"The render device does not exist\0",
+    VX_E_INVALID_SDK_HANDLE => b"Invalid handle\0",
+    VX_E_CHANNEL_URI_REQUIRED => b"A channel URI is required\0",
+    VX_E_ALREADY_INITIALIZED => b"The SDK is already initialized\0",
+    VX_E_NETWORK_DOWN => b"The network is down\0",
+    VX_E_POWER_STATE_CHANGE => b"The device changed its power state\0",
+    VX_E_HANDLE_ALREADY_TAKEN => b"The handle is already in use\0",
+    VX_E_CALL_TERMINATED_NO_RTP_RXED => b"The call was terminated because no media was received\0",
+    VX_E_CALL_TERMINATED_BY_SERVER => b"The call was terminated by the server\0",
+    VX_E_INVALID_CAPTURE_DEVICE_SPECIFIER => b"Invalid capture device specifier\0",
+    _ => b"Unknown error\0",
+  };
+
+  message.as_ptr() as *const c_char
+}
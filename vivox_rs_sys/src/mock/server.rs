@@ -0,0 +1,1118 @@
+//! The fake Vivox service answering requests issued to the mock.
+//!
+//! By default every request succeeds and is followed by the events the
+//! real service sends for it: logging in reports `logging_in` then
+//! `logged_in`, joining a channel connects its media and text streams and
+//! adds the local participant, and so on. Handles are validated the way
+//! the SDK does, so using an unknown or reused handle fails.
+//!
+//! Tests script anything beyond that with `fail_next`, `ignore_next`, the
+//! participant helpers, `archive_message`, `hot_swap`, or raw events
+//! through `emit`. The server is shared by the whole process; tests using
+//! it should not run concurrently.
+
+use super::sdk::*;
+use super::{heap, Message};
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::os::raw::{c_char, c_int, c_uint};
+use std::ptr;
+use std::sync::Mutex;
+
+/// Reported as `vx_resp_connector_create::version_id`.
+pub const VERSION: &str = "5.9.0.mock";
+
+/// Id of the microphone and speaker the mock pretends to have, besides
+/// the default system and communication devices.
+pub const CAPTURE_DEVICE: &str = "mock-microphone";
+pub const RENDER_DEVICE: &str = "mock-speakers";
+
+const DEFAULT_SYSTEM_DEVICE: &str = "Default System Device";
+const DEFAULT_COMMUNICATION_DEVICE: &str = "Default Communication Device";
+
+struct Connector {
+  domain: String,
+}
+
+struct Account {
+  connector: String,
+  display_name: String,
+  uri: String,
+}
+
+struct Session {
+  sessiongroup: String,
+  account: String,
+  uri: String,
+  audio: bool,
+  text: bool,
+  participants: BTreeSet<String>,
+  /// Participants the local user muted or changed the volume of
+  muted_for_me: BTreeSet<String>,
+  volumes: BTreeMap<String, c_int>,
+}
+
+/// A channel message kept for archive queries.
+struct Archived {
+  id: String,
+  channel_uri: String,
+  sender_uri: String,
+  body: String,
+  time_stamp: String,
+}
+
+enum Outcome {
+  Fail(u32),
+  Ignore,
+}
+
+struct Rule {
+  request: vx_request_type,
+  outcome: Outcome,
+}
+
+struct Server {
+  connectors: BTreeMap<String, Connector>,
+  accounts: BTreeMap<String, Account>,
+  sessions: BTreeMap<String, Session>,
+  archive: Vec<Archived>,
+  capture_device: Option<String>,
+  render_device: Option<String>,
+  rules: VecDeque<Rule>,
+  issued: Vec<vx_request_type>,
+  next_id: u64,
+  /// Reported by `vx_get_sdk_version_info` in place of the default
+  sdk_version: Option<CString>,
+  /// Size of `vx_sdk_config_t` accepted in place of the mock's own
+  config_size: Option<usize>,
+}
+
+static SERVER: Mutex<Server> = Mutex::new(Server {
+  connectors: BTreeMap::new(),
+  accounts: BTreeMap::new(),
+  sessions: BTreeMap::new(),
+  archive: Vec::new(),
+  capture_device: None,
+  render_device: None,
+  rules: VecDeque::new(),
+  issued: Vec::new(),
+  next_id: 1,
+  sdk_version: None,
+  config_size: None,
+});
+
+/// Forgets every connector, account and session as well as the archive,
+/// the scripted rules, the request log and any SDK version or config size
+/// set.
+pub fn reset() {
+  let mut server = SERVER.lock().unwrap();
+
+  server.disconnect();
+  server.archive.clear();
+  server.rules.clear();
+  server.issued.clear();
+  server.next_id = 1;
+  server.sdk_version = None;
+  server.config_size = None;
+}
+
+/// Has the SDK report another version, e.g. `4.10.0.1234`, to test how
+/// mismatched libraries are handled.
+pub fn set_sdk_version(version: &str) {
+  SERVER.lock().unwrap().sdk_version = Some(CString::new(version).unwrap());
+}
+
+/// Has the SDK only accept a `vx_sdk_config_t` of `size` bytes, as a
+/// library of another release would.
+pub fn set_config_size(size: usize) {
+  SERVER.lock().unwrap().config_size = Some(size);
+}
+
+pub(crate) fn config_size() -> usize {
+  SERVER.lock().unwrap().config_size.unwrap_or(mem::size_of::<vx_sdk_config_t>())
+}
+
+/// Stays valid until the version is set again or reset.
+pub(crate) fn sdk_version() -> Option<*const c_char> {
+  SERVER.lock().unwrap().sdk_version.as_ref().map(|version| version.as_ptr())
+}
+
+/// Fails the next request of this type with `status_code`, one of the
+/// `VX_E_*` constants. Rules queue up and are used in order.
+pub fn fail_next(request: vx_request_type, status_code: u32) {
+  SERVER.lock().unwrap().rules.push_back(Rule {
+    request,
+    outcome: Outcome::Fail(status_code),
+  });
+}
+
+/// Drops the next request of this type without ever answering it.
+pub fn ignore_next(request: vx_request_type) {
+  SERVER.lock().unwrap().rules.push_back(Rule {
+    request,
+    outcome: Outcome::Ignore,
+  });
+}
+
+/// Types of every request issued since the last `reset`, oldest first.
+pub fn issued() -> Vec<vx_request_type> {
+  SERVER.lock().unwrap().issued.clone()
+}
+
+/// Copies a string for one of the `char *` fields of an emitted event.
+pub fn string(input: &str) -> *mut c_char {
+  CString::new(input)
+    .expect("Strings passed to the SDK cannot contain null bytes")
+    .into_raw()
+}
+
+/// Raises an arbitrary event. `fill` receives a zeroed event with its type
+/// already set; strings must come from `string`.
+pub fn emit<T: Message>(fill: impl FnOnce(&mut T)) {
+  let evt = heap::alloc::<T>();
+
+  unsafe { fill(&mut *evt); }
+
+  post(evt as *mut vx_message_base_t);
+}
+
+/// A remote user joins a session. Returns false for unknown sessions.
+pub fn add_participant(session_handle: &str, uri: &str, display_name: &str) -> bool {
+  let mut server = SERVER.lock().unwrap();
+
+  match server.sessions.get_mut(session_handle) {
+    Some(session) => {
+      session.participants.insert(uri.to_string());
+      participant_added(session_handle, session, uri, display_name, false);
+
+      true
+    },
+    None => false,
+  }
+}
+
+/// A remote user leaves a session, e.g. with
+/// `vx_participant_removed_reason_participant_kicked`.
+pub fn remove_participant(
+  session_handle: &str,
+  uri: &str,
+  reason: vx_participant_removed_reason,
+) -> bool
+{
+  let mut server = SERVER.lock().unwrap();
+
+  let session = match server.sessions.get_mut(session_handle) {
+    Some(session) => session,
+    None => return false,
+  };
+
+  if !session.participants.remove(uri) {
+    return false;
+  }
+
+  session.muted_for_me.remove(uri);
+  session.volumes.remove(uri);
+
+  emit::<vx_evt_participant_removed>(|evt| {
+    evt.sessiongroup_handle = string(&session.sessiongroup);
+    evt.session_handle = string(session_handle);
+    evt.participant_uri = string(uri);
+    evt.account_name = string(account_name(uri));
+    evt.reason = reason;
+  });
+
+  true
+}
+
+/// A participant starts or stops talking.
+pub fn set_speaking(session_handle: &str, uri: &str, speaking: bool) -> bool {
+  let server = SERVER.lock().unwrap();
+
+  let session = match server.sessions.get(session_handle) {
+    Some(session) if session.participants.contains(uri) => session,
+    _ => return false,
+  };
+  let is_current_user = server.accounts.get(&session.account)
+    .map(|account| account.uri == uri)
+    .unwrap_or(false);
+
+  emit::<vx_evt_participant_updated>(|evt| {
+    evt.sessiongroup_handle = string(&session.sessiongroup);
+    evt.session_handle = string(session_handle);
+    evt.participant_uri = string(uri);
+    evt.is_speaking = speaking as c_int;
+    evt.energy = match speaking {
+      true => 0.8,
+      false => 0.0,
+    };
+    evt.volume = session.volumes.get(uri).copied().unwrap_or(50);
+    evt.is_muted_for_me = session.muted_for_me.contains(uri) as c_int;
+    evt.active_media = session.audio as c_int;
+    evt.is_current_user = is_current_user as c_int;
+  });
+
+  true
+}
+
+/// A participant posts a text message to a session.
+pub fn post_message(session_handle: &str, uri: &str, display_name: &str, body: &str) -> bool {
+  let server = SERVER.lock().unwrap();
+
+  let session = match server.sessions.get(session_handle) {
+    Some(session) if session.text => session,
+    _ => return false,
+  };
+
+  emit::<vx_evt_message>(|evt| {
+    evt.sessiongroup_handle = string(&session.sessiongroup);
+    evt.session_handle = string(session_handle);
+    evt.participant_uri = string(uri);
+    evt.participant_displayname = string(display_name);
+    evt.message_header = string("text/plain");
+    evt.message_body = string(body);
+  });
+
+  true
+}
+
+/// The system plugs in, removes or switches to the device `id`, reported
+/// with an `audio_device_hot_swap` event of `event_type`.
+pub fn hot_swap(event_type: vx_audio_device_hot_swap_event_type_t, id: &str, display_name: &str) {
+  emit::<vx_evt_audio_device_hot_swap>(|evt| {
+    evt.event_type = event_type;
+    evt.relevant_device = device(id, display_name, vx_device_type_vx_device_type_specific_device);
+  });
+}
+
+/// Adds a message to the server side history of `channel_uri`, which
+/// archive queries return oldest first. Returns the message id.
+pub fn archive_message(channel_uri: &str, sender_uri: &str, body: &str) -> String {
+  let mut server = SERVER.lock().unwrap();
+  let id = server.next_id("msg");
+  let seconds = server.archive.len();
+
+  server.archive.push(Archived {
+    id: id.clone(),
+    channel_uri: channel_uri.to_string(),
+    sender_uri: sender_uri.to_string(),
+    body: body.to_string(),
+    time_stamp: format!("2020-08-01T18:{:02}:{:02}Z", seconds / 60 % 60, seconds % 60),
+  });
+
+  id
+}
+
+/// The server ends a session on its own, reporting `status_code` on the
+/// stream updates, e.g. `VX_E_CALL_TERMINATED_BY_SERVER`.
+pub fn drop_session(session_handle: &str, status_code: u32) -> bool {
+  let mut server = SERVER.lock().unwrap();
+
+  match server.sessions.contains_key(session_handle) {
+    true => {
+      server.end_session(session_handle, status_code);
+
+      true
+    },
+    false => false,
+  }
+}
+
+/// The account loses its connection, ending its sessions and logging it
+/// out with `status_code`, e.g. `VX_E_NETWORK_DOWN`.
+pub fn drop_account(account_handle: &str, status_code: u32) -> bool {
+  let mut server = SERVER.lock().unwrap();
+
+  match server.accounts.contains_key(account_handle) {
+    true => {
+      server.end_account(account_handle, status_code);
+
+      true
+    },
+    false => false,
+  }
+}
+
+/// Forgets live objects without raising events, as when the SDK is torn
+/// down.
+pub(crate) fn disconnect() {
+  SERVER.lock().unwrap().disconnect();
+}
+
+/// Answers a request issued through `vx_issue_request`.
+pub(crate) unsafe fn handle(req: *mut vx_req_base_t) {
+  let mut server = SERVER.lock().unwrap();
+  let kind = (*req).type_;
+
+  server.issued.push(kind);
+
+  if let Some(index) = server.rules.iter().position(|rule| rule.request == kind) {
+    match server.rules.remove(index).map(|rule| rule.outcome) {
+      Some(Outcome::Fail(status_code)) => return fail(req, status_code),
+      Some(Outcome::Ignore) => {
+        heap::destroy(req as *mut vx_message_base_t);
+
+        return;
+      },
+      None => {},
+    }
+  }
+
+  let result = match kind {
+    vx_request_type_req_connector_create => server.connector_create(req as *mut _),
+    vx_request_type_req_connector_initiate_shutdown => server.connector_shutdown(req as *mut _),
+    vx_request_type_req_account_anonymous_login => server.login(req as *mut _),
+    vx_request_type_req_account_logout => server.logout(req as *mut _),
+    vx_request_type_req_sessiongroup_add_session => server.add_session(req as *mut _),
+    vx_request_type_req_sessiongroup_remove_session => server.remove_session(req as *mut _),
+    vx_request_type_req_sessiongroup_terminate => server.terminate(req as *mut _),
+    vx_request_type_req_session_media_connect => server.media_connect(req as *mut _),
+    vx_request_type_req_session_media_disconnect => server.media_disconnect(req as *mut _),
+    vx_request_type_req_session_text_connect => server.text_connect(req as *mut _),
+    vx_request_type_req_session_text_disconnect => server.text_disconnect(req as *mut _),
+    vx_request_type_req_session_send_message => server.send_message(req as *mut _),
+    vx_request_type_req_account_send_message => server.send_user_message(req as *mut _),
+    vx_request_type_req_session_archive_query => server.session_archive_query(req as *mut _),
+    vx_request_type_req_account_archive_query => server.account_archive_query(req as *mut _),
+    vx_request_type_req_aux_get_capture_devices => server.capture_devices(req),
+    vx_request_type_req_aux_get_render_devices => server.render_devices(req),
+    vx_request_type_req_aux_set_capture_device => server.set_capture_device(req as *mut _),
+    vx_request_type_req_aux_set_render_device => server.set_render_device(req as *mut _),
+    vx_request_type_req_session_set_participant_mute_for_me => server.mute_for_me(req as *mut _),
+    vx_request_type_req_session_set_participant_volume_for_me => server.volume_for_me(req as *mut _),
+    vx_request_type_req_account_buddy_set => server.buddy_set(req as *mut _),
+    vx_request_type_req_account_buddy_delete => server.buddy_delete(req as *mut _),
+    _ => {
+      reply::<vx_resp_base_t>(req, |_| {});
+
+      Ok(())
+    },
+  };
+
+  if let Err(status_code) = result {
+    fail(req, status_code);
+  }
+}
+
+impl Server {
+  fn disconnect(&mut self) {
+    self.connectors.clear();
+    self.accounts.clear();
+    self.sessions.clear();
+    self.capture_device = None;
+    self.render_device = None;
+  }
+
+  fn next_id(&mut self, prefix: &str) -> String {
+    let id = self.next_id;
+
+    self.next_id += 1;
+
+    format!("{}{}", prefix, id)
+  }
+
+  unsafe fn connector_create(&mut self, req: *mut vx_req_connector_create) -> Result<(), u32> {
+    let handle = read((*req).connector_handle);
+
+    if handle.is_empty() {
+      return Err(VX_E_INVALID_ARGUMENT);
+    }
+
+    if self.connectors.contains_key(&handle) {
+      return Err(VX_E_HANDLE_ALREADY_TAKEN);
+    }
+
+    self.connectors.insert(handle.clone(), Connector {
+      domain: domain(&read((*req).acct_mgmt_server)),
+    });
+
+    reply::<vx_resp_connector_create>(&mut (*req).base, |resp| {
+      resp.connector_handle = string(&handle);
+      resp.version_id = string(VERSION);
+    });
+
+    Ok(())
+  }
+
+  unsafe fn connector_shutdown(&mut self, req: *mut vx_req_connector_initiate_shutdown) -> Result<(), u32> {
+    let handle = read((*req).connector_handle);
+
+    if self.connectors.remove(&handle).is_none() {
+      return Err(VX_E_INVALID_SDK_HANDLE);
+    }
+
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+
+    let accounts: Vec<String> = self.accounts.iter()
+      .filter(|(_, account)| account.connector == handle)
+      .map(|(handle, _)| handle.clone())
+      .collect();
+
+    for account in accounts {
+      self.end_account(&account, VX_E_SUCCESS);
+    }
+
+    Ok(())
+  }
+
+  unsafe fn login(&mut self, req: *mut vx_req_account_anonymous_login) -> Result<(), u32> {
+    let connector = read((*req).connector_handle);
+    let handle = read((*req).account_handle);
+    let name = read((*req).acct_name);
+
+    let domain = match self.connectors.get(&connector) {
+      Some(connector) => connector.domain.clone(),
+      None => return Err(VX_E_INVALID_SDK_HANDLE),
+    };
+
+    if handle.is_empty() || name.is_empty() {
+      return Err(VX_E_INVALID_ARGUMENT);
+    }
+
+    if self.accounts.contains_key(&handle) {
+      return Err(VX_E_HANDLE_ALREADY_TAKEN);
+    }
+
+    let account = Account {
+      connector,
+      uri: format!("sip:{}@{}", name, domain),
+      display_name: read((*req).displayname),
+    };
+
+    reply::<vx_resp_account_anonymous_login>(&mut (*req).base, |resp| {
+      resp.account_handle = string(&handle);
+      resp.displayname = string(&account.display_name);
+      resp.uri = string(&account.uri);
+    });
+
+    login_state(&handle, vx_login_state_change_state_login_state_logging_in, VX_E_SUCCESS);
+    login_state(&handle, vx_login_state_change_state_login_state_logged_in, VX_E_SUCCESS);
+
+    self.accounts.insert(handle, account);
+
+    Ok(())
+  }
+
+  unsafe fn logout(&mut self, req: *mut vx_req_account_logout) -> Result<(), u32> {
+    let handle = read((*req).account_handle);
+
+    if !self.accounts.contains_key(&handle) {
+      return Err(VX_E_INVALID_SDK_HANDLE);
+    }
+
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+    login_state(&handle, vx_login_state_change_state_login_state_logging_out, VX_E_SUCCESS);
+    self.end_account(&handle, VX_E_SUCCESS);
+
+    Ok(())
+  }
+
+  unsafe fn add_session(&mut self, req: *mut vx_req_sessiongroup_add_session) -> Result<(), u32> {
+    let account = read((*req).account_handle);
+    let sessiongroup = read((*req).sessiongroup_handle);
+    let handle = read((*req).session_handle);
+    let uri = read((*req).uri);
+
+    let (account_uri, display_name) = match self.accounts.get(&account) {
+      Some(account) => (account.uri.clone(), account.display_name.clone()),
+      None => return Err(VX_E_NOT_LOGGED_IN),
+    };
+
+    if uri.is_empty() {
+      return Err(VX_E_CHANNEL_URI_REQUIRED);
+    }
+
+    if handle.is_empty() || sessiongroup.is_empty() {
+      return Err(VX_E_INVALID_ARGUMENT);
+    }
+
+    if self.sessions.contains_key(&handle) {
+      return Err(VX_E_HANDLE_ALREADY_TAKEN);
+    }
+
+    let new_group = !self.sessions.values().any(|session| session.sessiongroup == sessiongroup);
+    let mut session = Session {
+      sessiongroup,
+      account,
+      uri,
+      audio: (*req).connect_audio != 0,
+      text: (*req).connect_text != 0,
+      participants: BTreeSet::new(),
+      muted_for_me: BTreeSet::new(),
+      volumes: BTreeMap::new(),
+    };
+
+    reply::<vx_resp_sessiongroup_add_session>(&mut (*req).base, |resp| {
+      resp.session_handle = string(&handle);
+    });
+
+    if new_group {
+      emit::<vx_evt_sessiongroup_added>(|evt| {
+        evt.account_handle = string(&session.account);
+        evt.sessiongroup_handle = string(&session.sessiongroup);
+      });
+    }
+
+    emit::<vx_evt_session_added>(|evt| {
+      evt.sessiongroup_handle = string(&session.sessiongroup);
+      evt.session_handle = string(&handle);
+      evt.uri = string(&session.uri);
+      evt.is_channel = 1;
+    });
+
+    if session.audio {
+      media_state(&handle, &session, vx_session_media_state_session_media_connecting, VX_E_SUCCESS);
+      media_state(&handle, &session, vx_session_media_state_session_media_connected, VX_E_SUCCESS);
+    }
+
+    if session.text {
+      text_state(&handle, &session, vx_session_text_state_session_text_connected, VX_E_SUCCESS);
+    }
+
+    if session.audio || session.text {
+      session.participants.insert(account_uri.clone());
+      participant_added(&handle, &session, &account_uri, &display_name, true);
+    }
+
+    self.sessions.insert(handle, session);
+
+    Ok(())
+  }
+
+  unsafe fn remove_session(&mut self, req: *mut vx_req_sessiongroup_remove_session) -> Result<(), u32> {
+    let handle = read((*req).session_handle);
+
+    if !self.sessions.contains_key(&handle) {
+      return Err(VX_E_INVALID_SDK_HANDLE);
+    }
+
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+    self.end_session(&handle, VX_E_SUCCESS);
+
+    Ok(())
+  }
+
+  unsafe fn terminate(&mut self, req: *mut vx_req_sessiongroup_terminate) -> Result<(), u32> {
+    let sessiongroup = read((*req).sessiongroup_handle);
+    let sessions = self.sessions_of(|session| session.sessiongroup == sessiongroup);
+
+    if sessions.is_empty() {
+      return Err(VX_E_INVALID_SDK_HANDLE);
+    }
+
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+
+    for session in sessions {
+      self.end_session(&session, VX_E_SUCCESS);
+    }
+
+    Ok(())
+  }
+
+  unsafe fn media_connect(&mut self, req: *mut vx_req_session_media_connect) -> Result<(), u32> {
+    let handle = read((*req).session_handle);
+    let session = self.sessions.get_mut(&handle).ok_or(VX_E_INVALID_SDK_HANDLE)?;
+
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+
+    if !session.audio {
+      session.audio = true;
+      media_state(&handle, session, vx_session_media_state_session_media_connecting, VX_E_SUCCESS);
+      media_state(&handle, session, vx_session_media_state_session_media_connected, VX_E_SUCCESS);
+    }
+
+    Ok(())
+  }
+
+  unsafe fn media_disconnect(&mut self, req: *mut vx_req_session_media_disconnect) -> Result<(), u32> {
+    let handle = read((*req).session_handle);
+    let session = self.sessions.get_mut(&handle).ok_or(VX_E_INVALID_SDK_HANDLE)?;
+
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+
+    if session.audio {
+      session.audio = false;
+      media_state(&handle, session, vx_session_media_state_session_media_disconnected, VX_E_SUCCESS);
+    }
+
+    Ok(())
+  }
+
+  unsafe fn text_connect(&mut self, req: *mut vx_req_session_text_connect) -> Result<(), u32> {
+    let handle = read((*req).session_handle);
+    let session = self.sessions.get_mut(&handle).ok_or(VX_E_INVALID_SDK_HANDLE)?;
+
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+
+    if !session.text {
+      session.text = true;
+      text_state(&handle, session, vx_session_text_state_session_text_connected, VX_E_SUCCESS);
+    }
+
+    Ok(())
+  }
+
+  unsafe fn text_disconnect(&mut self, req: *mut vx_req_session_text_disconnect) -> Result<(), u32> {
+    let handle = read((*req).session_handle);
+    let session = self.sessions.get_mut(&handle).ok_or(VX_E_INVALID_SDK_HANDLE)?;
+
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+
+    if session.text {
+      session.text = false;
+      text_state(&handle, session, vx_session_text_state_session_text_disconnected, VX_E_SUCCESS);
+    }
+
+    Ok(())
+  }
+
+  /// Delivers the message back to the sender, as the service does for
+  /// everyone in the channel.
+  unsafe fn send_message(&mut self, req: *mut vx_req_session_send_message) -> Result<(), u32> {
+    let handle = read((*req).session_handle);
+    let session = self.sessions.get(&handle).ok_or(VX_E_INVALID_SDK_HANDLE)?;
+
+    if !session.text {
+      return Err(VX_E_SESSION_DOES_NOT_HAVE_TEXT);
+    }
+
+    let sender = self.accounts.get(&session.account).ok_or(VX_E_NOT_LOGGED_IN)?;
+
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+
+    emit::<vx_evt_message>(|evt| {
+      evt.sessiongroup_handle = string(&session.sessiongroup);
+      evt.session_handle = string(&handle);
+      evt.participant_uri = string(&sender.uri);
+      evt.participant_displayname = string(&sender.display_name);
+      evt.message_header = string("text/plain");
+      evt.message_body = string(&read((*req).message_body));
+      evt.application_stanza_namespace = string(&read((*req).application_stanza_namespace));
+      evt.application_stanza_body = string(&read((*req).application_stanza_body));
+      evt.language = string(&read((*req).language));
+    });
+
+    Ok(())
+  }
+
+  unsafe fn send_user_message(&mut self, req: *mut vx_req_account_send_message) -> Result<(), u32> {
+    if !self.accounts.contains_key(&read((*req).account_handle)) {
+      return Err(VX_E_NOT_LOGGED_IN);
+    }
+
+    let id = self.next_id("msg");
+
+    reply::<vx_resp_account_send_message>(&mut (*req).base, |resp| {
+      resp.request_id = string(&id);
+    });
+
+    Ok(())
+  }
+
+  /// Answers with up to `max` archived messages of the session's channel
+  /// following `after_id`.
+  unsafe fn session_archive_query(&mut self, req: *mut vx_req_session_archive_query) -> Result<(), u32> {
+    let handle = read((*req).session_handle);
+    let (sessiongroup, channel_uri) = match self.sessions.get(&handle) {
+      Some(session) => (session.sessiongroup.clone(), session.uri.clone()),
+      None => return Err(VX_E_INVALID_SDK_HANDLE),
+    };
+    let id = self.next_id("q");
+    let page = self.archive_page(Some(&channel_uri), read_opt((*req).after_id), (*req).max);
+
+    reply::<vx_resp_session_archive_query>(&mut (*req).base, |resp| {
+      resp.query_id = string(&id);
+    });
+
+    for message in &page {
+      emit::<vx_evt_session_archive_message>(|evt| {
+        evt.sessiongroup_handle = string(&sessiongroup);
+        evt.session_handle = string(&handle);
+        evt.query_id = string(&id);
+        evt.message_id = string(&message.id);
+        evt.time_stamp = string(&message.time_stamp);
+        evt.participant_uri = string(&message.sender_uri);
+        evt.message_body = string(&message.body);
+      });
+    }
+
+    emit::<vx_evt_session_archive_query_end>(|evt| {
+      evt.sessiongroup_handle = string(&sessiongroup);
+      evt.session_handle = string(&handle);
+      evt.query_id = string(&id);
+      evt.count = page.len() as c_uint;
+    });
+
+    Ok(())
+  }
+
+  /// Like `session_archive_query`, across every channel unless the request
+  /// names one.
+  unsafe fn account_archive_query(&mut self, req: *mut vx_req_account_archive_query) -> Result<(), u32> {
+    let handle = read((*req).account_handle);
+    let uri = match self.accounts.get(&handle) {
+      Some(account) => account.uri.clone(),
+      None => return Err(VX_E_NOT_LOGGED_IN),
+    };
+    let id = self.next_id("q");
+    let channel_uri = read_opt((*req).channel_uri);
+    let page = self.archive_page(channel_uri.as_deref(), read_opt((*req).after_id), (*req).max);
+
+    reply::<vx_resp_account_archive_query>(&mut (*req).base, |resp| {
+      resp.query_id = string(&id);
+    });
+
+    for message in &page {
+      emit::<vx_evt_account_archive_message>(|evt| {
+        evt.account_handle = string(&handle);
+        evt.query_id = string(&id);
+        evt.message_id = string(&message.id);
+        evt.time_stamp = string(&message.time_stamp);
+        evt.channel_uri = string(&message.channel_uri);
+        evt.participant_uri = string(&message.sender_uri);
+        evt.message_body = string(&message.body);
+        evt.is_inbound = (message.sender_uri != uri) as c_int;
+      });
+    }
+
+    emit::<vx_evt_account_archive_query_end>(|evt| {
+      evt.account_handle = string(&handle);
+      evt.query_id = string(&id);
+      evt.count = page.len() as c_uint;
+    });
+
+    Ok(())
+  }
+
+  fn archive_page(&self, channel_uri: Option<&str>, after_id: Option<String>, max: c_uint) -> Vec<&Archived> {
+    let start = after_id
+      .and_then(|after_id| self.archive.iter().position(|message| message.id == after_id))
+      .map(|index| index + 1)
+      .unwrap_or(0);
+
+    self.archive[start..]
+      .iter()
+      .filter(|message| channel_uri.map(|uri| message.channel_uri == uri).unwrap_or(true))
+      .take(max as usize)
+      .collect()
+  }
+
+  unsafe fn capture_devices(&mut self, req: *mut vx_req_base_t) -> Result<(), u32> {
+    let selected = self.capture_device.clone();
+
+    reply::<vx_resp_aux_get_capture_devices>(req, |resp| {
+      let (devices, count) = device_list(CAPTURE_DEVICE, "Mock Microphone");
+
+      resp.capture_devices = devices;
+      resp.count = count;
+      resp.current_capture_device = current_device(selected.as_deref(), CAPTURE_DEVICE, "Mock Microphone");
+      resp.effective_capture_device = device(CAPTURE_DEVICE, "Mock Microphone", vx_device_type_vx_device_type_specific_device);
+      resp.default_capture_device = device(CAPTURE_DEVICE, "Mock Microphone", vx_device_type_vx_device_type_specific_device);
+      resp.default_communication_capture_device = device(CAPTURE_DEVICE, "Mock Microphone", vx_device_type_vx_device_type_specific_device);
+    });
+
+    Ok(())
+  }
+
+  unsafe fn render_devices(&mut self, req: *mut vx_req_base_t) -> Result<(), u32> {
+    let selected = self.render_device.clone();
+
+    reply::<vx_resp_aux_get_render_devices>(req, |resp| {
+      let (devices, count) = device_list(RENDER_DEVICE, "Mock Speakers");
+
+      resp.render_devices = devices;
+      resp.count = count;
+      resp.current_render_device = current_device(selected.as_deref(), RENDER_DEVICE, "Mock Speakers");
+      resp.effective_render_device = device(RENDER_DEVICE, "Mock Speakers", vx_device_type_vx_device_type_specific_device);
+      resp.default_render_device = device(RENDER_DEVICE, "Mock Speakers", vx_device_type_vx_device_type_specific_device);
+      resp.default_communication_render_device = device(RENDER_DEVICE, "Mock Speakers", vx_device_type_vx_device_type_specific_device);
+    });
+
+    Ok(())
+  }
+
+  unsafe fn set_capture_device(&mut self, req: *mut vx_req_aux_set_capture_device) -> Result<(), u32> {
+    let id = read((*req).capture_device_specifier);
+
+    if !is_known_device(&id, CAPTURE_DEVICE) {
+      return Err(VX_E_INVALID_CAPTURE_DEVICE_SPECIFIER);
+    }
+
+    self.capture_device = Some(id);
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+
+    Ok(())
+  }
+
+  unsafe fn set_render_device(&mut self, req: *mut vx_req_aux_set_render_device) -> Result<(), u32> {
+    let id = read((*req).render_device_specifier);
+
+    if !is_known_device(&id, RENDER_DEVICE) {
+      return Err(VX_E_RENDER_DEVICE_DOES_NOT_EXIST);
+    }
+
+    self.render_device = Some(id);
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+
+    Ok(())
+  }
+
+  unsafe fn mute_for_me(&mut self, req: *mut vx_req_session_set_participant_mute_for_me) -> Result<(), u32> {
+    let uri = read((*req).participant_uri);
+    let session = self.participant_session(&read((*req).session_handle), &uri)?;
+
+    match (*req).mute != 0 {
+      true => session.muted_for_me.insert(uri),
+      false => session.muted_for_me.remove(&uri),
+    };
+
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+
+    Ok(())
+  }
+
+  unsafe fn volume_for_me(&mut self, req: *mut vx_req_session_set_participant_volume_for_me) -> Result<(), u32> {
+    let uri = read((*req).participant_uri);
+    let session = self.participant_session(&read((*req).session_handle), &uri)?;
+
+    session.volumes.insert(uri, (*req).volume);
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+
+    Ok(())
+  }
+
+  /// The session `handle`, if `uri` takes part in it.
+  fn participant_session(&mut self, handle: &str, uri: &str) -> Result<&mut Session, u32> {
+    match self.sessions.get_mut(handle) {
+      Some(session) if session.participants.contains(uri) => Ok(session),
+      Some(_) => Err(VX_E_NO_EXIST),
+      None => Err(VX_E_INVALID_SDK_HANDLE),
+    }
+  }
+
+  unsafe fn buddy_set(&mut self, req: *mut vx_req_account_buddy_set) -> Result<(), u32> {
+    let handle = read((*req).account_handle);
+
+    if !self.accounts.contains_key(&handle) {
+      return Err(VX_E_NOT_LOGGED_IN);
+    }
+
+    let uri = read((*req).buddy_uri);
+    let display_name = read((*req).display_name);
+
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+
+    emit::<vx_evt_buddy_changed>(|evt| {
+      evt.account_handle = string(&handle);
+      evt.change_type = vx_change_type_t_change_type_set;
+      evt.buddy_uri = string(&uri);
+      evt.display_name = string(&display_name);
+    });
+
+    Ok(())
+  }
+
+  unsafe fn buddy_delete(&mut self, req: *mut vx_req_account_buddy_delete) -> Result<(), u32> {
+    let handle = read((*req).account_handle);
+
+    if !self.accounts.contains_key(&handle) {
+      return Err(VX_E_NOT_LOGGED_IN);
+    }
+
+    let uri = read((*req).buddy_uri);
+
+    reply::<vx_resp_base_t>(&mut (*req).base, |_| {});
+
+    emit::<vx_evt_buddy_changed>(|evt| {
+      evt.account_handle = string(&handle);
+      evt.change_type = vx_change_type_t_change_type_delete;
+      evt.buddy_uri = string(&uri);
+    });
+
+    Ok(())
+  }
+
+  fn sessions_of<F: Fn(&Session) -> bool>(&self, filter: F) -> Vec<String> {
+    self.sessions.iter()
+      .filter(|(_, session)| filter(session))
+      .map(|(handle, _)| handle.clone())
+      .collect()
+  }
+
+  /// Disconnects and removes a session, then its group if it was the last
+  /// one in it.
+  fn end_session(&mut self, handle: &str, status_code: u32) {
+    let session = match self.sessions.remove(handle) {
+      Some(session) => session,
+      None => return,
+    };
+
+    if session.audio {
+      media_state(handle, &session, vx_session_media_state_session_media_disconnected, status_code);
+    }
+
+    if session.text {
+      text_state(handle, &session, vx_session_text_state_session_text_disconnected, status_code);
+    }
+
+    emit::<vx_evt_session_removed>(|evt| {
+      evt.sessiongroup_handle = string(&session.sessiongroup);
+      evt.session_handle = string(handle);
+      evt.uri = string(&session.uri);
+    });
+
+    if !self.sessions.values().any(|other| other.sessiongroup == session.sessiongroup) {
+      emit::<vx_evt_sessiongroup_removed>(|evt| {
+        evt.sessiongroup_handle = string(&session.sessiongroup);
+      });
+    }
+  }
+
+  fn end_account(&mut self, handle: &str, status_code: u32) {
+    for session in self.sessions_of(|session| session.account == handle) {
+      self.end_session(&session, status_code);
+    }
+
+    if self.accounts.remove(handle).is_some() {
+      login_state(handle, vx_login_state_change_state_login_state_logged_out, status_code);
+    }
+  }
+}
+
+/// Answers `req` with a response of type `T`. The response takes over the
+/// request, as it does in the SDK.
+unsafe fn reply<T: Message>(req: *mut vx_req_base_t, fill: impl FnOnce(&mut T)) {
+  let resp = heap::alloc::<T>();
+  let base = resp as *mut vx_resp_base_t;
+
+  (*base).type_ = (*req).type_ as vx_response_type;
+  (*base).request = req;
+  fill(&mut *resp);
+
+  post(resp as *mut vx_message_base_t);
+}
+
+unsafe fn fail(req: *mut vx_req_base_t, status_code: u32) {
+  let status_string = CStr::from_ptr(vx_get_error_string(status_code as c_int));
+
+  reply::<vx_resp_base_t>(req, |resp| {
+    resp.return_code = 1;
+    resp.status_code = status_code as c_int;
+    resp.status_string = string(&status_string.to_string_lossy());
+  });
+}
+
+fn login_state(account_handle: &str, state: vx_login_state_change_state, status_code: u32) {
+  emit::<vx_evt_account_login_state_change>(|evt| {
+    evt.account_handle = string(account_handle);
+    evt.state = state;
+    evt.status_code = status_code as c_int;
+  });
+}
+
+fn media_state(handle: &str, session: &Session, state: vx_session_media_state, status_code: u32) {
+  emit::<vx_evt_media_stream_updated>(|evt| {
+    evt.sessiongroup_handle = string(&session.sessiongroup);
+    evt.session_handle = string(handle);
+    evt.state = state;
+    evt.status_code = status_code as c_int;
+  });
+}
+
+fn text_state(handle: &str, session: &Session, state: vx_session_text_state, status_code: u32) {
+  emit::<vx_evt_text_stream_updated>(|evt| {
+    evt.sessiongroup_handle = string(&session.sessiongroup);
+    evt.session_handle = string(handle);
+    evt.enabled = (state == vx_session_text_state_session_text_connected) as c_int;
+    evt.state = state;
+    evt.status_code = status_code as c_int;
+  });
+}
+
+fn participant_added(handle: &str, session: &Session, uri: &str, display_name: &str, is_current_user: bool) {
+  emit::<vx_evt_participant_added>(|evt| {
+    evt.sessiongroup_handle = string(&session.sessiongroup);
+    evt.session_handle = string(handle);
+    evt.participant_uri = string(uri);
+    evt.account_name = string(account_name(uri));
+    evt.displayname = string(display_name);
+    evt.is_current_user = is_current_user as c_int;
+  });
+}
+
+fn device(id: &str, display_name: &str, device_type: vx_device_type) -> *mut vx_device_t {
+  Box::into_raw(Box::new(vx_device {
+    device: string(id),
+    display_name: string(display_name),
+    device_type,
+  }))
+}
+
+/// The two default entries followed by the mock's own device.
+fn device_list(id: &str, display_name: &str) -> (*mut *mut vx_device_t, c_int) {
+  let devices = vec![
+    device(DEFAULT_SYSTEM_DEVICE, DEFAULT_SYSTEM_DEVICE, vx_device_type_vx_device_type_default_system),
+    device(DEFAULT_COMMUNICATION_DEVICE, DEFAULT_COMMUNICATION_DEVICE, vx_device_type_vx_device_type_default_communication),
+    device(id, display_name, vx_device_type_vx_device_type_specific_device),
+    ptr::null_mut(),
+  ];
+  let count = devices.len() as c_int - 1;
+
+  (Box::into_raw(devices.into_boxed_slice()) as *mut *mut vx_device_t, count)
+}
+
+fn current_device(selected: Option<&str>, id: &str, display_name: &str) -> *mut vx_device_t {
+  match selected {
+    Some(DEFAULT_COMMUNICATION_DEVICE) => device(
+      DEFAULT_COMMUNICATION_DEVICE,
+      DEFAULT_COMMUNICATION_DEVICE,
+      vx_device_type_vx_device_type_default_communication,
+    ),
+    Some(selected) if selected == id => device(id, display_name, vx_device_type_vx_device_type_specific_device),
+    _ => device(DEFAULT_SYSTEM_DEVICE, DEFAULT_SYSTEM_DEVICE, vx_device_type_vx_device_type_default_system),
+  }
+}
+
+fn is_known_device(id: &str, own: &str) -> bool {
+  id == own || id == DEFAULT_SYSTEM_DEVICE || id == DEFAULT_COMMUNICATION_DEVICE
+}
+
+fn read(input: *const c_char) -> String {
+  match input.is_null() {
+    true => String::new(),
+    false => unsafe { CStr::from_ptr(input).to_string_lossy().into_owned() },
+  }
+}
+
+/// Unset filters of a request are null.
+fn read_opt(input: *const c_char) -> Option<String> {
+  match input.is_null() {
+    true => None,
+    false => Some(read(input)),
+  }
+}
+
+/// `https://vdx5.www.vivox.com/api2` is the account management server of
+/// the `vdx5.vivox.com` domain.
+fn domain(server: &str) -> String {
+  let host = server
+    .split("://")
+    .last()
+    .unwrap_or_default()
+    .split(['/', ':'])
+    .next()
+    .unwrap_or_default();
+
+  match host.is_empty() {
+    true => "mock.vivox.com".to_string(),
+    false => host.replacen("www.", "", 1),
+  }
+}
+
+/// `sip:.issuer.user.@domain` belongs to the account `.issuer.user.`.
+fn account_name(uri: &str) -> &str {
+  let name = uri.strip_prefix("sip:").unwrap_or(uri);
+
+  name.split('@').next().unwrap_or(name)
+}
@@ -0,0 +1,976 @@
+//! The subset of the SDK header used by `vivox_rs`, with the names bindgen
+//! generates for the real thing.
+
+use super::{heap, Message, Release};
+
+use std::os::raw::{c_char, c_double, c_int, c_uint, c_void};
+
+pub type size_t = usize;
+pub type VX_COOKIE = *mut c_char;
+pub type VX_HANDLE = *mut c_char;
+
+pub const VX_GET_MESSAGE_AVAILABLE: u32 = 0;
+pub const VX_GET_MESSAGE_FAILURE: u32 = 1;
+pub const VX_GET_MESSAGE_NO_MESSAGE: u32 = 2;
+
+pub const MAX_CHANNEL_URI_LENGTH: u32 = 200;
+
+pub const VX_E_SUCCESS: u32 = 0;
+pub const VX_E_NO_EXIST: u32 = 1001;
+pub const VX_E_FAILED: u32 = 1004;
+pub const VX_E_NOT_LOGGED_IN: u32 = 1007;
+pub const VX_E_INVALID_ARGUMENT: u32 = 1008;
+pub const VX_E_SESSION_DOES_NOT_HAVE_TEXT: u32 = 1027;
+pub const VX_E_NOT_INITIALIZED: u32 = 1012;
+pub const VX_E_RENDER_DEVICE_DOES_NOT_EXIST: u32 = 1064;
+pub const VX_E_INVALID_SDK_HANDLE: u32 = 1071;
+pub const VX_E_CHANNEL_URI_REQUIRED: u32 = 1076;
+pub const VX_E_ALREADY_INITIALIZED: u32 = 1085;
+pub const VX_E_NETWORK_DOWN: u32 = 1088;
+pub const VX_E_POWER_STATE_CHANGE: u32 = 1089;
+pub const VX_E_HANDLE_ALREADY_TAKEN: u32 = 1090;
+pub const VX_E_CALL_TERMINATED_NO_RTP_RXED: u32 = 1095;
+pub const VX_E_CALL_TERMINATED_BY_SERVER: u32 = 1100;
+pub const VX_E_INVALID_CAPTURE_DEVICE_SPECIFIER: u32 = 7005;
+
+macro_rules! enumeration {
+  ($name:ident { $($variant:ident = $value:expr,)* }) => {
+    pub type $name = c_uint;
+
+    $(pub const $variant: $name = $value;)*
+  };
+}
+
+enumeration!(vx_message_type {
+  vx_message_type_msg_none = 0,
+  vx_message_type_msg_request = 1,
+  vx_message_type_msg_response = 2,
+  vx_message_type_msg_event = 3,
+});
+
+// Request and response types share their values so the fake server can
+// answer any request, including ones it has no special handling for.
+enumeration!(vx_request_type {
+  vx_request_type_req_none = 0,
+  vx_request_type_req_connector_create = 1,
+  vx_request_type_req_connector_initiate_shutdown = 2,
+  vx_request_type_req_account_anonymous_login = 3,
+  vx_request_type_req_account_logout = 4,
+  vx_request_type_req_sessiongroup_add_session = 5,
+  vx_request_type_req_sessiongroup_remove_session = 6,
+  vx_request_type_req_sessiongroup_terminate = 7,
+  vx_request_type_req_sessiongroup_set_tx_session = 8,
+  vx_request_type_req_sessiongroup_set_tx_all_sessions = 9,
+  vx_request_type_req_sessiongroup_set_tx_no_session = 10,
+  vx_request_type_req_session_media_connect = 11,
+  vx_request_type_req_session_media_disconnect = 12,
+  vx_request_type_req_session_text_connect = 13,
+  vx_request_type_req_session_text_disconnect = 14,
+  vx_request_type_req_session_set_participant_mute_for_me = 15,
+  vx_request_type_req_session_set_participant_volume_for_me = 16,
+  vx_request_type_req_session_set_local_render_volume = 17,
+  vx_request_type_req_session_set_3d_position = 18,
+  vx_request_type_req_session_send_message = 19,
+  vx_request_type_req_session_archive_query = 20,
+  vx_request_type_req_channel_kick_user = 21,
+  vx_request_type_req_channel_mute_user = 22,
+  vx_request_type_req_channel_mute_all_users = 23,
+  vx_request_type_req_connector_mute_local_mic = 24,
+  vx_request_type_req_connector_mute_local_speaker = 25,
+  vx_request_type_req_connector_set_local_mic_volume = 26,
+  vx_request_type_req_connector_set_local_speaker_volume = 27,
+  vx_request_type_req_aux_get_capture_devices = 28,
+  vx_request_type_req_aux_get_render_devices = 29,
+  vx_request_type_req_aux_set_capture_device = 30,
+  vx_request_type_req_aux_set_render_device = 31,
+  vx_request_type_req_aux_set_vad_properties = 32,
+  vx_request_type_req_account_send_message = 33,
+  vx_request_type_req_account_archive_query = 34,
+  vx_request_type_req_account_set_presence = 35,
+  vx_request_type_req_account_buddy_set = 36,
+  vx_request_type_req_account_buddy_delete = 37,
+  vx_request_type_req_account_send_subscription_reply = 38,
+  vx_request_type_req_account_create_block_rule = 39,
+  vx_request_type_req_account_delete_block_rule = 40,
+});
+
+enumeration!(vx_response_type {
+  vx_response_type_resp_none = 0,
+  vx_response_type_resp_connector_create = 1,
+  vx_response_type_resp_connector_initiate_shutdown = 2,
+  vx_response_type_resp_account_anonymous_login = 3,
+  vx_response_type_resp_account_logout = 4,
+  vx_response_type_resp_sessiongroup_add_session = 5,
+  vx_response_type_resp_sessiongroup_remove_session = 6,
+  vx_response_type_resp_sessiongroup_terminate = 7,
+  vx_response_type_resp_sessiongroup_set_tx_session = 8,
+  vx_response_type_resp_sessiongroup_set_tx_all_sessions = 9,
+  vx_response_type_resp_sessiongroup_set_tx_no_session = 10,
+  vx_response_type_resp_session_media_connect = 11,
+  vx_response_type_resp_session_media_disconnect = 12,
+  vx_response_type_resp_session_text_connect = 13,
+  vx_response_type_resp_session_text_disconnect = 14,
+  vx_response_type_resp_session_set_participant_mute_for_me = 15,
+  vx_response_type_resp_session_set_participant_volume_for_me = 16,
+  vx_response_type_resp_session_set_local_render_volume = 17,
+  vx_response_type_resp_session_set_3d_position = 18,
+  vx_response_type_resp_session_send_message = 19,
+  vx_response_type_resp_session_archive_query = 20,
+  vx_response_type_resp_channel_kick_user = 21,
+  vx_response_type_resp_channel_mute_user = 22,
+  vx_response_type_resp_channel_mute_all_users = 23,
+  vx_response_type_resp_connector_mute_local_mic = 24,
+  vx_response_type_resp_connector_mute_local_speaker = 25,
+  vx_response_type_resp_connector_set_local_mic_volume = 26,
+  vx_response_type_resp_connector_set_local_speaker_volume = 27,
+  vx_response_type_resp_aux_get_capture_devices = 28,
+  vx_response_type_resp_aux_get_render_devices = 29,
+  vx_response_type_resp_aux_set_capture_device = 30,
+  vx_response_type_resp_aux_set_render_device = 31,
+  vx_response_type_resp_aux_set_vad_properties = 32,
+  vx_response_type_resp_account_send_message = 33,
+  vx_response_type_resp_account_archive_query = 34,
+  vx_response_type_resp_account_set_presence = 35,
+  vx_response_type_resp_account_buddy_set = 36,
+  vx_response_type_resp_account_buddy_delete = 37,
+  vx_response_type_resp_account_send_subscription_reply = 38,
+  vx_response_type_resp_account_create_block_rule = 39,
+  vx_response_type_resp_account_delete_block_rule = 40,
+});
+
+enumeration!(vx_event_type {
+  vx_event_type_evt_none = 0,
+  vx_event_type_evt_account_login_state_change = 2,
+  vx_event_type_evt_buddy_presence = 3,
+  vx_event_type_evt_subscription = 4,
+  vx_event_type_evt_session_notification = 5,
+  vx_event_type_evt_message = 6,
+  vx_event_type_evt_participant_added = 9,
+  vx_event_type_evt_participant_removed = 10,
+  vx_event_type_evt_participant_updated = 11,
+  vx_event_type_evt_sessiongroup_added = 13,
+  vx_event_type_evt_sessiongroup_removed = 14,
+  vx_event_type_evt_session_added = 15,
+  vx_event_type_evt_session_removed = 16,
+  vx_event_type_evt_media_stream_updated = 18,
+  vx_event_type_evt_text_stream_updated = 19,
+  vx_event_type_evt_buddy_changed = 21,
+  vx_event_type_evt_user_to_user_message = 26,
+  vx_event_type_evt_session_archive_message = 30,
+  vx_event_type_evt_session_archive_query_end = 31,
+  vx_event_type_evt_account_archive_message = 32,
+  vx_event_type_evt_account_archive_query_end = 33,
+  vx_event_type_evt_account_send_message_failed = 36,
+  vx_event_type_evt_audio_device_hot_swap = 37,
+});
+
+enumeration!(vx_login_state_change_state {
+  vx_login_state_change_state_login_state_logged_out = 0,
+  vx_login_state_change_state_login_state_logged_in = 1,
+  vx_login_state_change_state_login_state_logging_in = 2,
+  vx_login_state_change_state_login_state_logging_out = 3,
+  vx_login_state_change_state_login_state_resetting = 4,
+  vx_login_state_change_state_login_state_error = 100,
+});
+
+enumeration!(vx_session_media_state {
+  vx_session_media_state_session_media_none = 0,
+  vx_session_media_state_session_media_disconnected = 1,
+  vx_session_media_state_session_media_connected = 2,
+  vx_session_media_state_session_media_ringing = 3,
+  vx_session_media_state_session_media_disconnecting = 6,
+  vx_session_media_state_session_media_connecting = 7,
+});
+
+enumeration!(vx_session_text_state {
+  vx_session_text_state_session_text_disconnected = 0,
+  vx_session_text_state_session_text_connected = 1,
+  vx_session_text_state_session_text_connecting = 2,
+  vx_session_text_state_session_text_disconnecting = 3,
+});
+
+enumeration!(vx_password_hash_algorithm_t {
+  vx_password_hash_algorithm_t_password_hash_algorithm_cleartext = 0,
+  vx_password_hash_algorithm_t_password_hash_algorithm_sha1_username_hash = 1,
+});
+
+enumeration!(vx_media_type {
+  vx_media_type_media_type_none = 0,
+  vx_media_type_media_type_text = 1,
+  vx_media_type_media_type_audio = 2,
+});
+
+enumeration!(vx_participant_removed_reason {
+  vx_participant_removed_reason_participant_left = 0,
+  vx_participant_removed_reason_participant_timeout = 1,
+  vx_participant_removed_reason_participant_kicked = 2,
+  vx_participant_removed_reason_participant_banned = 3,
+});
+
+enumeration!(vx_notification_type {
+  vx_notification_type_notification_not_typing = 0,
+  vx_notification_type_notification_typing = 1,
+  vx_notification_type_notification_hand_lowered = 2,
+  vx_notification_type_notification_hand_raised = 3,
+});
+
+enumeration!(vx_mute_scope {
+  vx_mute_scope_mute_scope_all = 0,
+  vx_mute_scope_mute_scope_audio = 1,
+  vx_mute_scope_mute_scope_text = 2,
+});
+
+enumeration!(vx_device_type {
+  vx_device_type_vx_device_type_specific_device = 0,
+  vx_device_type_vx_device_type_default_system = 1,
+  vx_device_type_vx_device_type_null = 2,
+  vx_device_type_vx_device_type_default_communication = 3,
+});
+
+enumeration!(vx_audio_device_hot_swap_event_type_t {
+  vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_disabled = 0,
+  vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_active_render_device_removed = 1,
+  vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_active_render_device_changed = 2,
+  vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_active_capture_device_removed = 3,
+  vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_active_capture_device_changed = 4,
+  vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_audio_device_added = 5,
+  vx_audio_device_hot_swap_event_type_t_vx_audio_device_hot_swap_event_type_audio_device_removed = 6,
+});
+
+enumeration!(req_disposition_type_t {
+  req_disposition_type_t_req_disposition_replace_previous = 0,
+  req_disposition_type_t_req_disposition_no_reply_required = 1,
+});
+
+enumeration!(vx_buddy_management_mode {
+  vx_buddy_management_mode_mode_auto_accept = 0,
+  vx_buddy_management_mode_mode_auto_add = 1,
+  vx_buddy_management_mode_mode_block = 2,
+  vx_buddy_management_mode_mode_hide = 3,
+  vx_buddy_management_mode_mode_application = 4,
+});
+
+enumeration!(vx_buddy_presence_state {
+  vx_buddy_presence_state_buddy_presence_unknown = 0,
+  vx_buddy_presence_state_buddy_presence_pending = 1,
+  vx_buddy_presence_state_buddy_presence_online = 2,
+  vx_buddy_presence_state_buddy_presence_busy = 3,
+  vx_buddy_presence_state_buddy_presence_brb = 4,
+  vx_buddy_presence_state_buddy_presence_away = 5,
+  vx_buddy_presence_state_buddy_presence_onthephone = 6,
+  vx_buddy_presence_state_buddy_presence_outtolunch = 7,
+  vx_buddy_presence_state_buddy_presence_custom = 8,
+  vx_buddy_presence_state_buddy_presence_online_slc = 9,
+  vx_buddy_presence_state_buddy_presence_chat = 10,
+  vx_buddy_presence_state_buddy_presence_closed = 0,
+  vx_buddy_presence_state_buddy_presence_offline = 0,
+});
+
+enumeration!(vx_rule_type {
+  vx_rule_type_rule_none = 0,
+  vx_rule_type_rule_allow = 1,
+  vx_rule_type_rule_block = 2,
+  vx_rule_type_rule_hide = 3,
+});
+
+enumeration!(vx_change_type_t {
+  vx_change_type_t_change_type_set = 1,
+  vx_change_type_t_change_type_delete = 2,
+});
+
+enumeration!(vx_subscription_type {
+  vx_subscription_type_subscription_presence = 0,
+});
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct vx_sdk_config_t {
+  pub num_codec_threads: c_int,
+  pub num_voice_threads: c_int,
+  pub num_web_threads: c_int,
+  pub render_source_queue_depth_max: c_int,
+  pub render_source_initial_buffer_count: c_int,
+  pub upstream_jitter_frame_count: c_int,
+  pub max_logins_per_user: c_int,
+  pub initial_log_level: c_int,
+  pub disable_devices: c_int,
+  pub force_capture_silence: c_int,
+}
+
+#[repr(C)]
+pub struct vx_message_base_t {
+  pub type_: vx_message_type,
+  pub create_time_ms: u64,
+  pub last_step_ms: u64,
+}
+
+#[repr(C)]
+pub struct vx_req_base_t {
+  pub message: vx_message_base_t,
+  pub type_: vx_request_type,
+  pub cookie: VX_COOKIE,
+  pub vcookie: *mut c_void,
+}
+
+#[repr(C)]
+pub struct vx_resp_base_t {
+  pub message: vx_message_base_t,
+  pub type_: vx_response_type,
+  pub return_code: c_int,
+  pub status_code: c_int,
+  pub status_string: *mut c_char,
+  pub request: *mut vx_req_base_t,
+  pub extended_status_info: *mut c_char,
+}
+
+#[repr(C)]
+pub struct vx_evt_base_t {
+  pub message: vx_message_base_t,
+  pub type_: vx_event_type,
+  pub extended_status_info: *mut c_char,
+}
+
+impl Release for vx_message_base_t {}
+
+impl Release for vx_req_base_t {
+  unsafe fn release(&mut self) {
+    self.cookie.release();
+  }
+}
+
+impl Release for vx_resp_base_t {
+  unsafe fn release(&mut self) {
+    self.status_string.release();
+    self.extended_status_info.release();
+
+    // Responses own the request they answer
+    if !self.request.is_null() {
+      heap::destroy(self.request as *mut vx_message_base_t);
+    }
+  }
+}
+
+impl Release for vx_evt_base_t {
+  unsafe fn release(&mut self) {
+    self.extended_status_info.release();
+  }
+}
+
+/// Responses without a payload of their own.
+impl Message for vx_resp_base_t {
+  fn stamp(&mut self) {
+    self.message.type_ = vx_message_type_msg_response;
+  }
+}
+
+/// Declares a plain struct whose fields are freed along with it.
+macro_rules! structure {
+  ($name:ident $(as $alias:ident)? { $($field:ident: $ty:ty,)* }) => {
+    #[repr(C)]
+    pub struct $name {
+      $(pub $field: $ty,)*
+    }
+
+    $(pub type $alias = $name;)?
+
+    impl Release for $name {
+      unsafe fn release(&mut self) {
+        $(self.$field.release();)*
+      }
+    }
+  };
+}
+
+/// Declares a request along with its `_create` function.
+macro_rules! request {
+  ($name:ident as $alias:ident = $kind:ident, $create:ident { $($field:ident: $ty:ty,)* }) => {
+    structure!($name as $alias { base: vx_req_base_t, $($field: $ty,)* });
+
+    impl Message for $name {
+      fn stamp(&mut self) {
+        self.base.message.type_ = vx_message_type_msg_request;
+        self.base.type_ = $kind;
+      }
+    }
+
+    pub unsafe extern "C" fn $create(req: *mut *mut $name) -> c_int {
+      *req = heap::alloc::<$name>();
+
+      0
+    }
+  };
+}
+
+macro_rules! response {
+  ($name:ident as $alias:ident = $kind:ident { $($field:ident: $ty:ty,)* }) => {
+    structure!($name as $alias { base: vx_resp_base_t, $($field: $ty,)* });
+
+    impl Message for $name {
+      fn stamp(&mut self) {
+        self.base.message.type_ = vx_message_type_msg_response;
+        self.base.type_ = $kind;
+      }
+    }
+  };
+}
+
+macro_rules! event {
+  ($name:ident as $alias:ident = $kind:ident { $($field:ident: $ty:ty,)* }) => {
+    structure!($name as $alias { base: vx_evt_base_t, $($field: $ty,)* });
+
+    impl Message for $name {
+      fn stamp(&mut self) {
+        self.base.message.type_ = vx_message_type_msg_event;
+        self.base.type_ = $kind;
+      }
+    }
+  };
+}
+
+structure!(vx_device as vx_device_t {
+  device: *mut c_char,
+  display_name: *mut c_char,
+  device_type: vx_device_type,
+});
+
+request!(vx_req_connector_create as vx_req_connector_create_t = vx_request_type_req_connector_create, vx_req_connector_create_create {
+  client_name: *mut c_char,
+  acct_mgmt_server: *mut c_char,
+  connector_handle: VX_HANDLE,
+  application: *mut c_char,
+  max_calls: c_int,
+});
+
+request!(vx_req_connector_initiate_shutdown as vx_req_connector_initiate_shutdown_t = vx_request_type_req_connector_initiate_shutdown, vx_req_connector_initiate_shutdown_create {
+  connector_handle: VX_HANDLE,
+  client_name: *mut c_char,
+});
+
+request!(vx_req_account_anonymous_login as vx_req_account_anonymous_login_t = vx_request_type_req_account_anonymous_login, vx_req_account_anonymous_login_create {
+  connector_handle: VX_HANDLE,
+  acct_name: *mut c_char,
+  displayname: *mut c_char,
+  enable_buddies_and_presence: c_int,
+  buddy_management_mode: vx_buddy_management_mode,
+  account_handle: VX_HANDLE,
+  access_token: *mut c_char,
+  languages: *mut c_char,
+  participant_property_frequency: c_int,
+  application_override: *mut c_char,
+  acct_mgmt_server: *mut c_char,
+  application_token: *mut c_char,
+  autopost_crash_dumps: c_int,
+  enable_presence_persistence: c_int,
+});
+
+request!(vx_req_account_logout as vx_req_account_logout_t = vx_request_type_req_account_logout, vx_req_account_logout_create {
+  account_handle: VX_HANDLE,
+  logout_reason: *mut c_char,
+});
+
+request!(vx_req_sessiongroup_add_session as vx_req_sessiongroup_add_session_t = vx_request_type_req_sessiongroup_add_session, vx_req_sessiongroup_add_session_create {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+  uri: *mut c_char,
+  name: *mut c_char,
+  password: *mut c_char,
+  jitter_compensation: c_int,
+  connect_audio: c_int,
+  password_hash_algorithm: vx_password_hash_algorithm_t,
+  connect_text: c_int,
+  session_font_id: c_int,
+  account_handle: VX_HANDLE,
+  access_token: *mut c_char,
+});
+
+request!(vx_req_sessiongroup_remove_session as vx_req_sessiongroup_remove_session_t = vx_request_type_req_sessiongroup_remove_session, vx_req_sessiongroup_remove_session_create {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+});
+
+request!(vx_req_sessiongroup_terminate as vx_req_sessiongroup_terminate_t = vx_request_type_req_sessiongroup_terminate, vx_req_sessiongroup_terminate_create {
+  sessiongroup_handle: VX_HANDLE,
+});
+
+request!(vx_req_sessiongroup_set_tx_session as vx_req_sessiongroup_set_tx_session_t = vx_request_type_req_sessiongroup_set_tx_session, vx_req_sessiongroup_set_tx_session_create {
+  session_handle: VX_HANDLE,
+});
+
+request!(vx_req_sessiongroup_set_tx_all_sessions as vx_req_sessiongroup_set_tx_all_sessions_t = vx_request_type_req_sessiongroup_set_tx_all_sessions, vx_req_sessiongroup_set_tx_all_sessions_create {
+  sessiongroup_handle: VX_HANDLE,
+});
+
+request!(vx_req_sessiongroup_set_tx_no_session as vx_req_sessiongroup_set_tx_no_session_t = vx_request_type_req_sessiongroup_set_tx_no_session, vx_req_sessiongroup_set_tx_no_session_create {
+  sessiongroup_handle: VX_HANDLE,
+});
+
+request!(vx_req_session_media_connect as vx_req_session_media_connect_t = vx_request_type_req_session_media_connect, vx_req_session_media_connect_create {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+  session_font_id: c_int,
+  jitter_compensation: c_int,
+  media: vx_media_type,
+  capture_device_id: *mut c_char,
+  render_device_id: *mut c_char,
+});
+
+request!(vx_req_session_media_disconnect as vx_req_session_media_disconnect_t = vx_request_type_req_session_media_disconnect, vx_req_session_media_disconnect_create {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+  media: vx_media_type,
+});
+
+request!(vx_req_session_text_connect as vx_req_session_text_connect_t = vx_request_type_req_session_text_connect, vx_req_session_text_connect_create {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+});
+
+request!(vx_req_session_text_disconnect as vx_req_session_text_disconnect_t = vx_request_type_req_session_text_disconnect, vx_req_session_text_disconnect_create {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+});
+
+request!(vx_req_session_set_participant_mute_for_me as vx_req_session_set_participant_mute_for_me_t = vx_request_type_req_session_set_participant_mute_for_me, vx_req_session_set_participant_mute_for_me_create {
+  session_handle: VX_HANDLE,
+  participant_uri: *mut c_char,
+  mute: c_int,
+  scope: vx_mute_scope,
+});
+
+request!(vx_req_session_set_participant_volume_for_me as vx_req_session_set_participant_volume_for_me_t = vx_request_type_req_session_set_participant_volume_for_me, vx_req_session_set_participant_volume_for_me_create {
+  session_handle: VX_HANDLE,
+  participant_uri: *mut c_char,
+  volume: c_int,
+});
+
+request!(vx_req_session_set_local_render_volume as vx_req_session_set_local_render_volume_t = vx_request_type_req_session_set_local_render_volume, vx_req_session_set_local_render_volume_create {
+  session_handle: VX_HANDLE,
+  volume: c_int,
+});
+
+request!(vx_req_session_set_3d_position as vx_req_session_set_3d_position_t = vx_request_type_req_session_set_3d_position, vx_req_session_set_3d_position_create {
+  session_handle: VX_HANDLE,
+  speaker_position: [c_double; 3],
+  speaker_velocity: [c_double; 3],
+  speaker_at_orientation: [c_double; 3],
+  speaker_up_orientation: [c_double; 3],
+  speaker_left_orientation: [c_double; 3],
+  listener_position: [c_double; 3],
+  listener_velocity: [c_double; 3],
+  listener_at_orientation: [c_double; 3],
+  listener_up_orientation: [c_double; 3],
+  listener_left_orientation: [c_double; 3],
+  type_: req_disposition_type_t,
+});
+
+request!(vx_req_session_send_message as vx_req_session_send_message_t = vx_request_type_req_session_send_message, vx_req_session_send_message_create {
+  session_handle: VX_HANDLE,
+  message_header: *mut c_char,
+  message_body: *mut c_char,
+  application_stanza_namespace: *mut c_char,
+  application_stanza_body: *mut c_char,
+  language: *mut c_char,
+});
+
+request!(vx_req_session_archive_query as vx_req_session_archive_query_t = vx_request_type_req_session_archive_query, vx_req_session_archive_query_create {
+  session_handle: VX_HANDLE,
+  time_start: *mut c_char,
+  time_end: *mut c_char,
+  search_text: *mut c_char,
+  participant_uri: *mut c_char,
+  max: c_uint,
+  after_id: *mut c_char,
+  before_id: *mut c_char,
+  first_message_index: c_int,
+});
+
+request!(vx_req_channel_kick_user as vx_req_channel_kick_user_t = vx_request_type_req_channel_kick_user, vx_req_channel_kick_user_create {
+  account_handle: VX_HANDLE,
+  channel_uri: *mut c_char,
+  participant_uri: *mut c_char,
+  access_token: *mut c_char,
+});
+
+request!(vx_req_channel_mute_user as vx_req_channel_mute_user_t = vx_request_type_req_channel_mute_user, vx_req_channel_mute_user_create {
+  account_handle: VX_HANDLE,
+  channel_uri: *mut c_char,
+  participant_uri: *mut c_char,
+  set_muted: c_int,
+  scope: vx_mute_scope,
+  access_token: *mut c_char,
+});
+
+request!(vx_req_channel_mute_all_users as vx_req_channel_mute_all_users_t = vx_request_type_req_channel_mute_all_users, vx_req_channel_mute_all_users_create {
+  account_handle: VX_HANDLE,
+  channel_uri: *mut c_char,
+  set_muted: c_int,
+  scope: vx_mute_scope,
+  access_token: *mut c_char,
+});
+
+request!(vx_req_connector_mute_local_mic as vx_req_connector_mute_local_mic_t = vx_request_type_req_connector_mute_local_mic, vx_req_connector_mute_local_mic_create {
+  connector_handle: VX_HANDLE,
+  mute_level: c_int,
+  account_handle: VX_HANDLE,
+});
+
+request!(vx_req_connector_mute_local_speaker as vx_req_connector_mute_local_speaker_t = vx_request_type_req_connector_mute_local_speaker, vx_req_connector_mute_local_speaker_create {
+  connector_handle: VX_HANDLE,
+  mute_level: c_int,
+  account_handle: VX_HANDLE,
+});
+
+request!(vx_req_connector_set_local_mic_volume as vx_req_connector_set_local_mic_volume_t = vx_request_type_req_connector_set_local_mic_volume, vx_req_connector_set_local_mic_volume_create {
+  connector_handle: VX_HANDLE,
+  volume: c_int,
+  account_handle: VX_HANDLE,
+});
+
+request!(vx_req_connector_set_local_speaker_volume as vx_req_connector_set_local_speaker_volume_t = vx_request_type_req_connector_set_local_speaker_volume, vx_req_connector_set_local_speaker_volume_create {
+  connector_handle: VX_HANDLE,
+  volume: c_int,
+  account_handle: VX_HANDLE,
+});
+
+request!(vx_req_aux_get_capture_devices as vx_req_aux_get_capture_devices_t = vx_request_type_req_aux_get_capture_devices, vx_req_aux_get_capture_devices_create {
+  account_handle: VX_HANDLE,
+});
+
+request!(vx_req_aux_get_render_devices as vx_req_aux_get_render_devices_t = vx_request_type_req_aux_get_render_devices, vx_req_aux_get_render_devices_create {
+  account_handle: VX_HANDLE,
+});
+
+request!(vx_req_aux_set_capture_device as vx_req_aux_set_capture_device_t = vx_request_type_req_aux_set_capture_device, vx_req_aux_set_capture_device_create {
+  capture_device_specifier: *mut c_char,
+  account_handle: VX_HANDLE,
+});
+
+request!(vx_req_aux_set_render_device as vx_req_aux_set_render_device_t = vx_request_type_req_aux_set_render_device, vx_req_aux_set_render_device_create {
+  render_device_specifier: *mut c_char,
+  account_handle: VX_HANDLE,
+});
+
+request!(vx_req_aux_set_vad_properties as vx_req_aux_set_vad_properties_t = vx_request_type_req_aux_set_vad_properties, vx_req_aux_set_vad_properties_create {
+  vad_hangover: c_int,
+  vad_sensitivity: c_int,
+  vad_noise_floor: c_int,
+  vad_auto: c_int,
+});
+
+request!(vx_req_account_send_message as vx_req_account_send_message_t = vx_request_type_req_account_send_message, vx_req_account_send_message_create {
+  account_handle: VX_HANDLE,
+  user_uri: *mut c_char,
+  message_header: *mut c_char,
+  message_body: *mut c_char,
+  application_stanza_namespace: *mut c_char,
+  application_stanza_body: *mut c_char,
+  language: *mut c_char,
+});
+
+request!(vx_req_account_archive_query as vx_req_account_archive_query_t = vx_request_type_req_account_archive_query, vx_req_account_archive_query_create {
+  account_handle: VX_HANDLE,
+  max: c_uint,
+  time_start: *mut c_char,
+  time_end: *mut c_char,
+  search_text: *mut c_char,
+  after_id: *mut c_char,
+  before_id: *mut c_char,
+  first_message_index: c_int,
+  channel_uri: *mut c_char,
+  participant_uri: *mut c_char,
+});
+
+request!(vx_req_account_set_presence as vx_req_account_set_presence_t = vx_request_type_req_account_set_presence, vx_req_account_set_presence_create {
+  account_handle: VX_HANDLE,
+  presence: vx_buddy_presence_state,
+  custom_message: *mut c_char,
+});
+
+request!(vx_req_account_buddy_set as vx_req_account_buddy_set_t = vx_request_type_req_account_buddy_set, vx_req_account_buddy_set_create {
+  account_handle: VX_HANDLE,
+  buddy_uri: *mut c_char,
+  display_name: *mut c_char,
+  buddy_data: *mut c_char,
+  group_id: c_int,
+  message: *mut c_char,
+});
+
+request!(vx_req_account_buddy_delete as vx_req_account_buddy_delete_t = vx_request_type_req_account_buddy_delete, vx_req_account_buddy_delete_create {
+  account_handle: VX_HANDLE,
+  buddy_uri: *mut c_char,
+});
+
+request!(vx_req_account_send_subscription_reply as vx_req_account_send_subscription_reply_t = vx_request_type_req_account_send_subscription_reply, vx_req_account_send_subscription_reply_create {
+  account_handle: VX_HANDLE,
+  rule_type: vx_rule_type,
+  auto_accept: c_int,
+  buddy_uri: *mut c_char,
+  subscription_handle: VX_HANDLE,
+});
+
+request!(vx_req_account_create_block_rule as vx_req_account_create_block_rule_t = vx_request_type_req_account_create_block_rule, vx_req_account_create_block_rule_create {
+  account_handle: VX_HANDLE,
+  block_mask: *mut c_char,
+  presence_only: c_int,
+});
+
+request!(vx_req_account_delete_block_rule as vx_req_account_delete_block_rule_t = vx_request_type_req_account_delete_block_rule, vx_req_account_delete_block_rule_create {
+  account_handle: VX_HANDLE,
+  block_mask: *mut c_char,
+});
+
+response!(vx_resp_connector_create as vx_resp_connector_create_t = vx_response_type_resp_connector_create {
+  connector_handle: VX_HANDLE,
+  version_id: *mut c_char,
+});
+
+response!(vx_resp_account_anonymous_login as vx_resp_account_anonymous_login_t = vx_response_type_resp_account_anonymous_login {
+  account_handle: VX_HANDLE,
+  account_id: c_int,
+  displayname: *mut c_char,
+  uri: *mut c_char,
+  encoded_uri_with_tag: *mut c_char,
+});
+
+response!(vx_resp_sessiongroup_add_session as vx_resp_sessiongroup_add_session_t = vx_response_type_resp_sessiongroup_add_session {
+  session_handle: VX_HANDLE,
+});
+
+response!(vx_resp_aux_get_capture_devices as vx_resp_aux_get_capture_devices_t = vx_response_type_resp_aux_get_capture_devices {
+  count: c_int,
+  capture_devices: *mut *mut vx_device_t,
+  current_capture_device: *mut vx_device_t,
+  effective_capture_device: *mut vx_device_t,
+  default_capture_device: *mut vx_device_t,
+  default_communication_capture_device: *mut vx_device_t,
+});
+
+response!(vx_resp_aux_get_render_devices as vx_resp_aux_get_render_devices_t = vx_response_type_resp_aux_get_render_devices {
+  count: c_int,
+  render_devices: *mut *mut vx_device_t,
+  current_render_device: *mut vx_device_t,
+  effective_render_device: *mut vx_device_t,
+  default_render_device: *mut vx_device_t,
+  default_communication_render_device: *mut vx_device_t,
+});
+
+response!(vx_resp_account_send_message as vx_resp_account_send_message_t = vx_response_type_resp_account_send_message {
+  request_id: *mut c_char,
+});
+
+response!(vx_resp_session_archive_query as vx_resp_session_archive_query_t = vx_response_type_resp_session_archive_query {
+  query_id: *mut c_char,
+});
+
+response!(vx_resp_account_archive_query as vx_resp_account_archive_query_t = vx_response_type_resp_account_archive_query {
+  query_id: *mut c_char,
+});
+
+event!(vx_evt_account_login_state_change as vx_evt_account_login_state_change_t = vx_event_type_evt_account_login_state_change {
+  state: vx_login_state_change_state,
+  account_handle: VX_HANDLE,
+  status_code: c_int,
+  status_string: *mut c_char,
+});
+
+event!(vx_evt_sessiongroup_added as vx_evt_sessiongroup_added_t = vx_event_type_evt_sessiongroup_added {
+  account_handle: VX_HANDLE,
+  sessiongroup_handle: VX_HANDLE,
+});
+
+event!(vx_evt_sessiongroup_removed as vx_evt_sessiongroup_removed_t = vx_event_type_evt_sessiongroup_removed {
+  sessiongroup_handle: VX_HANDLE,
+});
+
+event!(vx_evt_session_added as vx_evt_session_added_t = vx_event_type_evt_session_added {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+  uri: *mut c_char,
+  is_channel: c_int,
+  incoming: c_int,
+  channel_name: *mut c_char,
+  displayname: *mut c_char,
+});
+
+event!(vx_evt_session_removed as vx_evt_session_removed_t = vx_event_type_evt_session_removed {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+  uri: *mut c_char,
+});
+
+event!(vx_evt_media_stream_updated as vx_evt_media_stream_updated_t = vx_event_type_evt_media_stream_updated {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+  status_code: c_int,
+  status_string: *mut c_char,
+  state: vx_session_media_state,
+  incoming: c_int,
+});
+
+event!(vx_evt_text_stream_updated as vx_evt_text_stream_updated_t = vx_event_type_evt_text_stream_updated {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+  enabled: c_int,
+  state: vx_session_text_state,
+  incoming: c_int,
+  status_code: c_int,
+  status_string: *mut c_char,
+});
+
+event!(vx_evt_participant_added as vx_evt_participant_added_t = vx_event_type_evt_participant_added {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+  participant_uri: *mut c_char,
+  account_name: *mut c_char,
+  displayname: *mut c_char,
+  participant_type: c_int,
+  application: *mut c_char,
+  is_current_user: c_int,
+  encoded_uri_with_tag: *mut c_char,
+});
+
+event!(vx_evt_participant_removed as vx_evt_participant_removed_t = vx_event_type_evt_participant_removed {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+  participant_uri: *mut c_char,
+  account_name: *mut c_char,
+  reason: vx_participant_removed_reason,
+  is_current_user: c_int,
+  encoded_uri_with_tag: *mut c_char,
+});
+
+event!(vx_evt_participant_updated as vx_evt_participant_updated_t = vx_event_type_evt_participant_updated {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+  participant_uri: *mut c_char,
+  is_moderator_muted: c_int,
+  is_speaking: c_int,
+  volume: c_int,
+  energy: c_double,
+  active_media: c_int,
+  is_muted_for_me: c_int,
+  is_moderator_text_muted: c_int,
+  is_text_muted_for_me: c_int,
+  type_: c_int,
+  is_current_user: c_int,
+  encoded_uri_with_tag: *mut c_char,
+});
+
+event!(vx_evt_session_notification as vx_evt_session_notification_t = vx_event_type_evt_session_notification {
+  session_handle: VX_HANDLE,
+  participant_uri: *mut c_char,
+  notification_type: vx_notification_type,
+  encoded_uri_with_tag: *mut c_char,
+});
+
+event!(vx_evt_audio_device_hot_swap as vx_evt_audio_device_hot_swap_t = vx_event_type_evt_audio_device_hot_swap {
+  event_type: vx_audio_device_hot_swap_event_type_t,
+  relevant_device: *mut vx_device_t,
+});
+
+event!(vx_evt_message as vx_evt_message_t = vx_event_type_evt_message {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+  participant_uri: *mut c_char,
+  message_header: *mut c_char,
+  message_body: *mut c_char,
+  participant_displayname: *mut c_char,
+  application_stanza_namespace: *mut c_char,
+  application_stanza_body: *mut c_char,
+  language: *mut c_char,
+  encoded_uri_with_tag: *mut c_char,
+});
+
+event!(vx_evt_user_to_user_message as vx_evt_user_to_user_message_t = vx_event_type_evt_user_to_user_message {
+  account_handle: VX_HANDLE,
+  from_uri: *mut c_char,
+  message_header: *mut c_char,
+  message_body: *mut c_char,
+  from_displayname: *mut c_char,
+  application_stanza_namespace: *mut c_char,
+  application_stanza_body: *mut c_char,
+  language: *mut c_char,
+  encoded_uri_with_tag: *mut c_char,
+});
+
+event!(vx_evt_account_send_message_failed as vx_evt_account_send_message_failed_t = vx_event_type_evt_account_send_message_failed {
+  account_handle: VX_HANDLE,
+  request_id: *mut c_char,
+  status_code: c_int,
+  status_string: *mut c_char,
+});
+
+event!(vx_evt_session_archive_message as vx_evt_session_archive_message_t = vx_event_type_evt_session_archive_message {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+  query_id: *mut c_char,
+  time_stamp: *mut c_char,
+  message_body: *mut c_char,
+  participant_uri: *mut c_char,
+  message_id: *mut c_char,
+  is_current_user: c_int,
+  language: *mut c_char,
+});
+
+event!(vx_evt_session_archive_query_end as vx_evt_session_archive_query_end_t = vx_event_type_evt_session_archive_query_end {
+  sessiongroup_handle: VX_HANDLE,
+  session_handle: VX_HANDLE,
+  query_id: *mut c_char,
+  return_code: c_int,
+  status_code: c_int,
+  first_id: *mut c_char,
+  last_id: *mut c_char,
+  first_index: c_uint,
+  count: c_uint,
+});
+
+event!(vx_evt_account_archive_message as vx_evt_account_archive_message_t = vx_event_type_evt_account_archive_message {
+  account_handle: VX_HANDLE,
+  query_id: *mut c_char,
+  time_stamp: *mut c_char,
+  channel_uri: *mut c_char,
+  message_body: *mut c_char,
+  participant_uri: *mut c_char,
+  message_id: *mut c_char,
+  is_inbound: c_int,
+  language: *mut c_char,
+});
+
+event!(vx_evt_account_archive_query_end as vx_evt_account_archive_query_end_t = vx_event_type_evt_account_archive_query_end {
+  account_handle: VX_HANDLE,
+  query_id: *mut c_char,
+  return_code: c_int,
+  status_code: c_int,
+  first_id: *mut c_char,
+  last_id: *mut c_char,
+  first_index: c_uint,
+  count: c_uint,
+});
+
+event!(vx_evt_buddy_changed as vx_evt_buddy_changed_t = vx_event_type_evt_buddy_changed {
+  account_handle: VX_HANDLE,
+  change_type: vx_change_type_t,
+  buddy_uri: *mut c_char,
+  display_name: *mut c_char,
+  buddy_data: *mut c_char,
+  group_id: c_int,
+});
+
+event!(vx_evt_buddy_presence as vx_evt_buddy_presence_t = vx_event_type_evt_buddy_presence {
+  presence: vx_buddy_presence_state,
+  account_handle: VX_HANDLE,
+  buddy_uri: *mut c_char,
+  custom_message: *mut c_char,
+  displayname: *mut c_char,
+  application: *mut c_char,
+});
+
+event!(vx_evt_subscription as vx_evt_subscription_t = vx_event_type_evt_subscription {
+  account_handle: VX_HANDLE,
+  buddy_uri: *mut c_char,
+  subscription_handle: VX_HANDLE,
+  subscription_type: vx_subscription_type,
+  displayname: *mut c_char,
+  application: *mut c_char,
+  message: *mut c_char,
+});